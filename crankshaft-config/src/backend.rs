@@ -3,16 +3,25 @@
 use serde::Deserialize;
 use serde::Serialize;
 
+mod adaptive;
+pub mod apptainer;
 mod builder;
+mod capacity;
 mod defaults;
 pub mod docker;
 pub mod generic;
 mod kind;
+pub mod local;
+pub mod mock;
+mod rate_limit;
 pub mod tes;
 
+pub use adaptive::Adaptive;
 pub use builder::Builder;
+pub use capacity::Capacity;
 pub use defaults::Defaults;
 pub use kind::Kind;
+pub use rate_limit::RateLimit;
 
 /// A configuration object for an execution backend.
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -30,6 +39,24 @@ pub struct Config {
 
     /// The execution defaults.
     defaults: Option<Defaults>,
+
+    /// The total resources available to the tasks running at once.
+    capacity: Option<Capacity>,
+
+    /// The labels used when routing tasks to backends.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    labels: Vec<String>,
+
+    /// The name of the backend that tasks fail over to when this backend is
+    /// unavailable.
+    fallback: Option<String>,
+
+    /// The maximum rates at which the backend is called.
+    rate_limit: Option<RateLimit>,
+
+    /// The bounds within which the maximum number of concurrent tasks is
+    /// adjusted at runtime (if it is).
+    adaptive: Option<Adaptive>,
 }
 
 impl Config {
@@ -58,6 +85,34 @@ impl Config {
         self.defaults.as_ref()
     }
 
+    /// Gets the total resources available to the tasks running on the backend
+    /// at once (if specified).
+    pub fn capacity(&self) -> Option<&Capacity> {
+        self.capacity.as_ref()
+    }
+
+    /// Gets the labels used when routing tasks to the backend.
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.labels.iter().map(String::as_str)
+    }
+
+    /// Gets the name of the backend that tasks fail over to when this backend
+    /// is unavailable (if specified).
+    pub fn fallback(&self) -> Option<&str> {
+        self.fallback.as_deref()
+    }
+
+    /// Gets the maximum rates at which the backend is called (if specified).
+    pub fn rate_limit(&self) -> Option<&RateLimit> {
+        self.rate_limit.as_ref()
+    }
+
+    /// Gets the bounds within which the maximum number of concurrent tasks is
+    /// adjusted at runtime (if specified).
+    pub fn adaptive(&self) -> Option<&Adaptive> {
+        self.adaptive.as_ref()
+    }
+
     /// Consumes `self` returns the constituent parts of the [`Config`].
     pub fn into_parts(self) -> (String, Kind, usize, Option<Defaults>) {
         (self.name, self.kind, self.max_tasks, self.defaults)
@@ -0,0 +1,53 @@
+//! Configuration options related to adjusting the number of tasks a backend
+//! runs at once.
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/// The bounds within which the number of tasks a backend runs at once is
+/// adjusted at runtime.
+///
+/// The number starts at the backend's `max-tasks` (clamped to the bounds). It
+/// grows while tasks are waiting to run and submissions succeed quickly, and
+/// it shrinks when the backend is unavailable or takes longer than the target
+/// latency to accept submissions.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Adaptive {
+    /// The minimum number of tasks to run at once.
+    min_tasks: usize,
+
+    /// The maximum number of tasks to run at once.
+    max_tasks: usize,
+
+    /// The longest a submission may take (in seconds) before it is treated as
+    /// a sign of an overloaded backend.
+    target_latency: Option<f64>,
+}
+
+impl Adaptive {
+    /// Creates a new [`Adaptive`].
+    pub fn new(min_tasks: usize, max_tasks: usize, target_latency: Option<f64>) -> Self {
+        Self {
+            min_tasks,
+            max_tasks,
+            target_latency,
+        }
+    }
+
+    /// Gets the minimum number of tasks to run at once.
+    pub fn min_tasks(&self) -> usize {
+        self.min_tasks
+    }
+
+    /// Gets the maximum number of tasks to run at once.
+    pub fn max_tasks(&self) -> usize {
+        self.max_tasks
+    }
+
+    /// Gets the longest a submission may take (in seconds) before it is
+    /// treated as a sign of an overloaded backend (if specified).
+    pub fn target_latency(&self) -> Option<f64> {
+        self.target_latency
+    }
+}
@@ -0,0 +1,95 @@
+//! Configuration related to the _Apptainer_ (formerly Singularity) execution
+//! backend.
+
+mod builder;
+
+use std::path::Path;
+use std::path::PathBuf;
+
+pub use builder::Builder;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// The default executable used to run containers.
+pub const DEFAULT_EXECUTABLE: &str = "apptainer";
+
+/// The default value for cleaning up task working directories.
+pub const DEFAULT_CLEANUP: bool = true;
+
+/// A utility function used to set the default value for `executable` via
+/// serde.
+fn default_executable() -> String {
+    String::from(DEFAULT_EXECUTABLE)
+}
+
+/// A utility function used to set the default value for `cleanup` via serde.
+fn default_cleanup() -> bool {
+    DEFAULT_CLEANUP
+}
+
+/// A configuration object for an Apptainer execution backend.
+///
+/// An Apptainer backend runs each execution within its container image (as a
+/// SIF image) on the local host, which makes it suitable for clusters where
+/// Docker is unavailable.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Config {
+    /// The executable used to run containers (e.g., `apptainer` or
+    /// `singularity`).
+    #[serde(default = "default_executable")]
+    executable: String,
+
+    /// The directory within which images are cached (as SIF files).
+    ///
+    /// Defaults to a directory within the user's cache directory.
+    cache: Option<PathBuf>,
+
+    /// The directory within which the working directories of tasks are
+    /// created.
+    ///
+    /// Defaults to the temporary directory of the system.
+    root: Option<PathBuf>,
+
+    /// Whether or not to remove the working directories of tasks after their
+    /// completion (regardless of whether the task was a success or failure).
+    #[serde(default = "default_cleanup")]
+    cleanup: bool,
+}
+
+impl Config {
+    /// Gets a builder for [`Config`].
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// Gets the executable used to run containers.
+    pub fn executable(&self) -> &str {
+        &self.executable
+    }
+
+    /// Gets the directory within which images are cached (if one is
+    /// specified).
+    pub fn cache(&self) -> Option<&Path> {
+        self.cache.as_deref()
+    }
+
+    /// Gets the directory within which the working directories of tasks are
+    /// created (if one is specified).
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    /// Gets whether the backend is configured to remove the working
+    /// directories of tasks after their completion (regardless of whether the
+    /// task was a success or failure).
+    pub fn cleanup(&self) -> bool {
+        self.cleanup
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
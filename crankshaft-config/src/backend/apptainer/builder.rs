@@ -0,0 +1,114 @@
+//! Builders for the [_Apptainer_ execution backend configuration](Config).
+
+use std::path::PathBuf;
+
+use crate::backend::apptainer::Config;
+use crate::backend::apptainer::DEFAULT_CLEANUP;
+use crate::backend::apptainer::DEFAULT_EXECUTABLE;
+
+/// A builder for an [Apptainer execution backend configuration
+/// object](Config).
+// **NOTE:** all default values for this struct need to be tested below to
+// ensure the defaults never change.
+pub struct Builder {
+    /// The executable used to run containers.
+    executable: String,
+
+    /// The directory within which images are cached.
+    cache: Option<PathBuf>,
+
+    /// The directory within which the working directories of tasks are
+    /// created.
+    root: Option<PathBuf>,
+
+    /// Whether or not to remove the working directories of tasks after their
+    /// completion.
+    cleanup: bool,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            executable: String::from(DEFAULT_EXECUTABLE),
+            cache: None,
+            root: None,
+            // By default, working directories should be cleaned up.
+            cleanup: DEFAULT_CLEANUP,
+        }
+    }
+}
+
+impl Builder {
+    /// Sets the executable used to run containers for the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous executable set within the
+    /// builder.
+    pub fn executable(mut self, executable: impl Into<String>) -> Self {
+        self.executable = executable.into();
+        self
+    }
+
+    /// Sets the directory within which images are cached for the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous cache directory set within
+    /// the builder.
+    pub fn cache(mut self, cache: impl Into<PathBuf>) -> Self {
+        self.cache = Some(cache.into());
+        self
+    }
+
+    /// Sets the directory within which the working directories of tasks are
+    /// created for the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous root directory set within the
+    /// builder.
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    /// Sets the cleanup property for the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous cleanup properties set within
+    /// the builder.
+    pub fn cleanup(mut self, cleanup: bool) -> Self {
+        self.cleanup = cleanup;
+        self
+    }
+
+    /// Consumes `self` and returns a built [`Config`].
+    pub fn build(self) -> Config {
+        Config {
+            executable: self.executable,
+            cache: self.cache,
+            root: self.root,
+            cleanup: self.cleanup,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_values() {
+        let options = Config::default();
+
+        // Apptainer should be run from the `PATH`, with images cached in the
+        // user's cache directory, and working directories cleaned up by
+        // default.
+        assert_eq!(options.executable(), "apptainer");
+        assert!(options.cache().is_none());
+        assert!(options.root().is_none());
+        assert!(options.cleanup());
+    }
+}
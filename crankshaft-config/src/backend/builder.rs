@@ -1,8 +1,11 @@
 //! Builders for [execution backends](Config).
 
+use crate::backend::Adaptive;
+use crate::backend::Capacity;
 use crate::backend::Config;
 use crate::backend::Defaults;
 use crate::backend::Kind;
+use crate::backend::RateLimit;
 
 /// An error related to a [`Builder`].
 #[derive(Debug)]
@@ -41,6 +44,22 @@ pub struct Builder {
 
     /// The execution defaults.
     defaults: Option<Defaults>,
+
+    /// The total resources available to the tasks running at once.
+    capacity: Option<Capacity>,
+
+    /// The labels used when routing tasks to backends.
+    labels: Vec<String>,
+
+    /// The name of the backend that tasks fail over to.
+    fallback: Option<String>,
+
+    /// The maximum rates at which the backend is called.
+    rate_limit: Option<RateLimit>,
+
+    /// The bounds within which the maximum number of concurrent tasks is
+    /// adjusted at runtime.
+    adaptive: Option<Adaptive>,
 }
 
 impl Builder {
@@ -87,6 +106,64 @@ impl Builder {
         self
     }
 
+    /// Sets the total resources available to the tasks running at once for the
+    /// [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous capacity set within the
+    /// builder.
+    pub fn capacity(mut self, capacity: impl Into<Capacity>) -> Self {
+        self.capacity = Some(capacity.into());
+        self
+    }
+
+    /// Extends the labels used when routing tasks to backends within the
+    /// [`Builder`].
+    pub fn extend_labels<Iter>(mut self, labels: Iter) -> Self
+    where
+        Iter: IntoIterator<Item = String>,
+    {
+        self.labels.extend(labels);
+        self
+    }
+
+    /// Sets the name of the backend that tasks fail over to when this backend
+    /// is unavailable for the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous fallback set within the
+    /// builder.
+    pub fn fallback(mut self, name: impl Into<String>) -> Self {
+        self.fallback = Some(name.into());
+        self
+    }
+
+    /// Sets the maximum rates at which the backend is called for the
+    /// [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous rate limit set within the
+    /// builder.
+    pub fn rate_limit(mut self, rate_limit: impl Into<RateLimit>) -> Self {
+        self.rate_limit = Some(rate_limit.into());
+        self
+    }
+
+    /// Sets the bounds within which the maximum number of concurrent tasks is
+    /// adjusted at runtime for the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous bounds set within the
+    /// builder.
+    pub fn adaptive(mut self, adaptive: impl Into<Adaptive>) -> Self {
+        self.adaptive = Some(adaptive.into());
+        self
+    }
+
     /// Consumes `self` and attempts to build a [`Config`].
     pub fn try_build(self) -> Result<Config> {
         let name = self.name.ok_or(Error::Missing("name"))?;
@@ -98,6 +175,11 @@ impl Builder {
             kind,
             max_tasks,
             defaults: self.defaults,
+            capacity: self.capacity,
+            labels: self.labels,
+            fallback: self.fallback,
+            rate_limit: self.rate_limit,
+            adaptive: self.adaptive,
         })
     }
 }
@@ -0,0 +1,43 @@
+//! Configuration options related to the total resources a backend can provide
+//! to the tasks running on it at once.
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/// The total resources available to the tasks running on a backend at once.
+///
+/// Any resource that is not specified is treated as unlimited.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Capacity {
+    /// The total number of CPUs.
+    cpu: Option<usize>,
+
+    /// The total amount of RAM (in GB).
+    ram: Option<f64>,
+
+    /// The total amount of disk (in GB).
+    disk: Option<f64>,
+}
+
+impl Capacity {
+    /// Creates a new [`Capacity`].
+    pub fn new(cpu: Option<usize>, ram: Option<f64>, disk: Option<f64>) -> Self {
+        Self { cpu, ram, disk }
+    }
+
+    /// Gets the total number of CPUs.
+    pub fn cpu(&self) -> Option<usize> {
+        self.cpu
+    }
+
+    /// Gets the total amount of RAM (in GB).
+    pub fn ram(&self) -> Option<f64> {
+        self.ram
+    }
+
+    /// Gets the total amount of disk space (in GB).
+    pub fn disk(&self) -> Option<f64> {
+        self.disk
+    }
+}
@@ -3,8 +3,11 @@
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::backend::apptainer;
 use crate::backend::docker;
 use crate::backend::generic;
+use crate::backend::local;
+use crate::backend::mock;
 use crate::backend::tes;
 
 /// A kind of execution backend.
@@ -19,6 +22,16 @@ pub enum Kind {
 
     /// A TES backend.
     TES(tes::Config),
+
+    /// A local backend.
+    Local(local::Config),
+
+    /// An Apptainer (formerly Singularity) backend.
+    #[serde(alias = "Singularity")]
+    Apptainer(apptainer::Config),
+
+    /// A mock backend (for testing).
+    Mock(mock::Config),
 }
 
 impl Kind {
@@ -113,4 +126,97 @@ impl Kind {
             _ => panic!("the inner kind is not `Kind::TES`"),
         }
     }
+
+    /// Attempts to return a reference to the inner [local
+    /// configuration][`local::Config`].
+    pub fn as_local(&self) -> Option<&local::Config> {
+        match self {
+            Kind::Local(config) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Consumes `self` and attempts to return an inner [local
+    /// configuration][`local::Config`].
+    pub fn into_local(self) -> Option<local::Config> {
+        match self {
+            Kind::Local(config) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Consumes `self` and returns an inner [local
+    /// configuration][`local::Config`].
+    ///
+    /// # Panics
+    ///
+    /// If the inner kind is not [`Kind::Local`].
+    pub fn unwrap_local(self) -> local::Config {
+        match self {
+            Kind::Local(config) => config,
+            _ => panic!("the inner kind is not `Kind::Local`"),
+        }
+    }
+
+    /// Attempts to return a reference to the inner [Apptainer
+    /// configuration][`apptainer::Config`].
+    pub fn as_apptainer(&self) -> Option<&apptainer::Config> {
+        match self {
+            Kind::Apptainer(config) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Consumes `self` and attempts to return an inner [Apptainer
+    /// configuration][`apptainer::Config`].
+    pub fn into_apptainer(self) -> Option<apptainer::Config> {
+        match self {
+            Kind::Apptainer(config) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Consumes `self` and returns an inner [Apptainer
+    /// configuration][`apptainer::Config`].
+    ///
+    /// # Panics
+    ///
+    /// If the inner kind is not [`Kind::Apptainer`].
+    pub fn unwrap_apptainer(self) -> apptainer::Config {
+        match self {
+            Kind::Apptainer(config) => config,
+            _ => panic!("the inner kind is not `Kind::Apptainer`"),
+        }
+    }
+
+    /// Attempts to return a reference to the inner [mock
+    /// configuration][`mock::Config`].
+    pub fn as_mock(&self) -> Option<&mock::Config> {
+        match self {
+            Kind::Mock(config) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Consumes `self` and attempts to return an inner [mock
+    /// configuration][`mock::Config`].
+    pub fn into_mock(self) -> Option<mock::Config> {
+        match self {
+            Kind::Mock(config) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Consumes `self` and returns an inner [mock
+    /// configuration][`mock::Config`].
+    ///
+    /// # Panics
+    ///
+    /// If the inner kind is not [`Kind::Mock`].
+    pub fn unwrap_mock(self) -> mock::Config {
+        match self {
+            Kind::Mock(config) => config,
+            _ => panic!("the inner kind is not `Kind::Mock`"),
+        }
+    }
 }
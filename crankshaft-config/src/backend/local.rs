@@ -0,0 +1,87 @@
+//! Configuration related to the _local_ execution backend.
+
+mod builder;
+
+use std::path::Path;
+use std::path::PathBuf;
+
+pub use builder::Builder;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// The default value for cleaning up task working directories.
+pub const DEFAULT_CLEANUP: bool = true;
+
+/// A utility function used to set the default value for `cleanup` via serde.
+fn default_cleanup() -> bool {
+    DEFAULT_CLEANUP
+}
+
+/// How inputs backed by local files are placed within a task's working
+/// directory.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Localization {
+    /// Inputs are copied (so tasks cannot modify the originals).
+    #[default]
+    Copy,
+
+    /// Inputs are symlinked (which is faster for large inputs).
+    Symlink,
+}
+
+/// A configuration object for a local execution backend.
+///
+/// A local backend runs each execution directly as a child process (without
+/// a container) within a working directory dedicated to its task.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Config {
+    /// The directory within which the working directories of tasks are
+    /// created.
+    ///
+    /// Defaults to the temporary directory of the system.
+    root: Option<PathBuf>,
+
+    /// How inputs backed by local files are placed within the working
+    /// directories of tasks.
+    #[serde(default)]
+    localization: Localization,
+
+    /// Whether or not to remove the working directories of tasks after their
+    /// completion (regardless of whether the task was a success or failure).
+    #[serde(default = "default_cleanup")]
+    cleanup: bool,
+}
+
+impl Config {
+    /// Gets a builder for [`Config`].
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// Gets the directory within which the working directories of tasks are
+    /// created (if one is specified).
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    /// Gets how inputs backed by local files are placed within the working
+    /// directories of tasks.
+    pub fn localization(&self) -> Localization {
+        self.localization
+    }
+
+    /// Gets whether the backend is configured to remove the working
+    /// directories of tasks after their completion (regardless of whether the
+    /// task was a success or failure).
+    pub fn cleanup(&self) -> bool {
+        self.cleanup
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
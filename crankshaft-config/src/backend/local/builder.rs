@@ -0,0 +1,98 @@
+//! Builders for the [_local_ execution backend configuration](Config).
+
+use std::path::PathBuf;
+
+use crate::backend::local::Config;
+use crate::backend::local::DEFAULT_CLEANUP;
+use crate::backend::local::Localization;
+
+/// A builder for a [local execution backend configuration object](Config).
+// **NOTE:** all default values for this struct need to be tested below to
+// ensure the defaults never change.
+pub struct Builder {
+    /// The directory within which the working directories of tasks are
+    /// created.
+    root: Option<PathBuf>,
+
+    /// How inputs backed by local files are placed within the working
+    /// directories of tasks.
+    localization: Localization,
+
+    /// Whether or not to remove the working directories of tasks after their
+    /// completion.
+    cleanup: bool,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            root: None,
+            // By default, inputs are copied so tasks cannot modify them.
+            localization: Localization::Copy,
+            // By default, working directories should be cleaned up.
+            cleanup: DEFAULT_CLEANUP,
+        }
+    }
+}
+
+impl Builder {
+    /// Sets the directory within which the working directories of tasks are
+    /// created for the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous root directory set within the
+    /// builder.
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    /// Sets how inputs backed by local files are placed within the working
+    /// directories of tasks for the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous localization set within the
+    /// builder.
+    pub fn localization(mut self, localization: Localization) -> Self {
+        self.localization = localization;
+        self
+    }
+
+    /// Sets the cleanup property for the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous cleanup properties set within
+    /// the builder.
+    pub fn cleanup(mut self, cleanup: bool) -> Self {
+        self.cleanup = cleanup;
+        self
+    }
+
+    /// Consumes `self` and returns a built [`Config`].
+    pub fn build(self) -> Config {
+        Config {
+            root: self.root,
+            localization: self.localization,
+            cleanup: self.cleanup,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_values() {
+        let options = Config::default();
+
+        // Local working directories should be created in the temporary
+        // directory, with copied inputs, and cleaned up by default.
+        assert!(options.root().is_none());
+        assert_eq!(options.localization(), Localization::Copy);
+        assert!(options.cleanup());
+    }
+}
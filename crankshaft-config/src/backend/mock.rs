@@ -0,0 +1,109 @@
+//! Configuration related to the _mock_ execution backend.
+
+mod builder;
+
+pub use builder::Builder;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// A configuration object for a mock execution backend.
+///
+/// A mock backend doesn't run anything: each execution "completes" after a
+/// simulated duration with scripted output.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Config {
+    /// The shortest amount of time (in seconds) an execution takes.
+    #[serde(default)]
+    min_duration: f64,
+
+    /// The longest amount of time (in seconds) an execution takes.
+    ///
+    /// When specified, the duration of each execution is picked uniformly at
+    /// random between the minimum and maximum durations.
+    max_duration: Option<f64>,
+
+    /// The standard output of each execution.
+    #[serde(default)]
+    stdout: String,
+
+    /// The exit code of each execution (between zero and 255).
+    #[serde(default)]
+    exit_code: u8,
+
+    /// The fraction of attempts (between zero and one) that fail as if the
+    /// backend were unavailable.
+    #[serde(default)]
+    failure_rate: f64,
+
+    /// The seed for the random durations and failures (if they should be
+    /// reproducible).
+    seed: Option<u64>,
+}
+
+impl Config {
+    /// Gets a builder for [`Config`].
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// Gets the shortest amount of time (in seconds) an execution takes.
+    pub fn min_duration(&self) -> f64 {
+        self.min_duration
+    }
+
+    /// Gets the longest amount of time (in seconds) an execution takes (if
+    /// specified).
+    pub fn max_duration(&self) -> Option<f64> {
+        self.max_duration
+    }
+
+    /// Gets the standard output of each execution.
+    pub fn stdout(&self) -> &str {
+        &self.stdout
+    }
+
+    /// Gets the exit code of each execution.
+    pub fn exit_code(&self) -> u8 {
+        self.exit_code
+    }
+
+    /// Gets the fraction of attempts that fail as if the backend were
+    /// unavailable.
+    pub fn failure_rate(&self) -> f64 {
+        self.failure_rate
+    }
+
+    /// Gets the seed for the random durations and failures (if specified).
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::File;
+    use config::FileFormat;
+
+    use super::*;
+
+    fn parse(source: &str) -> Result<Config, config::ConfigError> {
+        config::Config::builder()
+            .add_source(File::from_str(source, FileFormat::Toml))
+            .build()?
+            .try_deserialize()
+    }
+
+    #[test]
+    fn exit_codes_must_be_valid() {
+        assert_eq!(parse("exit-code = 255").unwrap().exit_code(), 255);
+        assert!(parse("exit-code = 256").is_err());
+        assert!(parse("exit-code = -1").is_err());
+    }
+}
@@ -0,0 +1,113 @@
+//! Builders for the [_mock_ execution backend configuration](Config).
+
+use crate::backend::mock::Config;
+
+/// A builder for a [mock execution backend configuration object](Config).
+///
+/// By default, executions complete immediately, successfully, and without any
+/// output, and no failures are injected.
+#[derive(Default)]
+pub struct Builder {
+    /// The shortest amount of time (in seconds) an execution takes.
+    min_duration: f64,
+
+    /// The longest amount of time (in seconds) an execution takes.
+    max_duration: Option<f64>,
+
+    /// The standard output of each execution.
+    stdout: String,
+
+    /// The exit code of each execution.
+    exit_code: u8,
+
+    /// The fraction of attempts that fail as if the backend were unavailable.
+    failure_rate: f64,
+
+    /// The seed for the random durations and failures.
+    seed: Option<u64>,
+}
+
+impl Builder {
+    /// Sets a fixed duration (in seconds) for each execution for the
+    /// [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous durations set within the
+    /// builder.
+    pub fn duration(mut self, seconds: f64) -> Self {
+        self.min_duration = seconds;
+        self.max_duration = None;
+        self
+    }
+
+    /// Sets the range of durations (in seconds) the duration of each execution
+    /// is picked from (uniformly at random) for the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous durations set within the
+    /// builder.
+    pub fn duration_between(mut self, min: f64, max: f64) -> Self {
+        self.min_duration = min;
+        self.max_duration = Some(max);
+        self
+    }
+
+    /// Sets the standard output of each execution for the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous standard output set within the
+    /// builder.
+    pub fn stdout(mut self, stdout: impl Into<String>) -> Self {
+        self.stdout = stdout.into();
+        self
+    }
+
+    /// Sets the exit code of each execution for the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous exit code set within the
+    /// builder.
+    pub fn exit_code(mut self, code: u8) -> Self {
+        self.exit_code = code;
+        self
+    }
+
+    /// Sets the fraction of attempts (between zero and one) that fail as if the
+    /// backend were unavailable for the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous failure rate set within the
+    /// builder.
+    pub fn failure_rate(mut self, rate: f64) -> Self {
+        self.failure_rate = rate;
+        self
+    }
+
+    /// Sets the seed for the random durations and failures for the
+    /// [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous seed set within the builder.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Consumes `self` and returns a built [`Config`].
+    pub fn build(self) -> Config {
+        Config {
+            min_duration: self.min_duration,
+            max_duration: self.max_duration,
+            stdout: self.stdout,
+            exit_code: self.exit_code,
+            failure_rate: self.failure_rate,
+            seed: self.seed,
+        }
+    }
+}
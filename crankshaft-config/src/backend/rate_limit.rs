@@ -0,0 +1,40 @@
+//! Configuration options related to how quickly the engine may call a
+//! backend.
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/// The maximum rates at which the engine calls a backend.
+///
+/// Each rate is enforced as a token bucket that holds up to a second's worth
+/// of calls, so short bursts are allowed. Any rate that is not specified is
+/// treated as unlimited.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RateLimit {
+    /// The maximum number of submissions per second.
+    submissions: Option<f64>,
+
+    /// The maximum number of calls to monitor submitted tasks per second.
+    monitors: Option<f64>,
+}
+
+impl RateLimit {
+    /// Creates a new [`RateLimit`].
+    pub fn new(submissions: Option<f64>, monitors: Option<f64>) -> Self {
+        Self {
+            submissions,
+            monitors,
+        }
+    }
+
+    /// Gets the maximum number of submissions per second.
+    pub fn submissions(&self) -> Option<f64> {
+        self.submissions
+    }
+
+    /// Gets the maximum number of calls to monitor submitted tasks per second.
+    pub fn monitors(&self) -> Option<f64> {
+        self.monitors
+    }
+}
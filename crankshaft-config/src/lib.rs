@@ -150,5 +150,10 @@ mod tests {
         assert_eq!(backend.name(), "quux");
         assert_eq!(backend.defaults().unwrap().cpu(), Some(1));
         assert_eq!(backend.defaults().unwrap().ram(), Some(1.0));
+        assert_eq!(backend.capacity().unwrap().cpu(), Some(64));
+        assert_eq!(backend.capacity().unwrap().disk(), None);
+        assert_eq!(backend.rate_limit().unwrap().submissions(), Some(5.0));
+        assert_eq!(backend.rate_limit().unwrap().monitors(), Some(20.0));
+        assert_eq!(backend.adaptive().unwrap().max_tasks(), 50);
     }
 }
@@ -15,8 +15,10 @@ use bollard::container::AttachContainerOptions;
 use bollard::container::LogOutput;
 use bollard::container::RemoveContainerOptions;
 use bollard::container::StartContainerOptions;
+use bollard::container::StopContainerOptions;
 use bollard::container::UploadToContainerOptions;
 use bollard::container::WaitContainerOptions;
+use bollard::secret::ContainerInspectResponse;
 pub use builder::Builder;
 use futures::TryStreamExt as _;
 use tokio_stream::StreamExt as _;
@@ -35,6 +37,10 @@ use crate::Result;
 /// allocations.
 const DEFAULT_TAR_CAPACITY: usize = 0xFFFF;
 
+/// The number of seconds to wait for a container to exit after being asked to
+/// stop before it is killed.
+pub const STOP_TIMEOUT_SECS: i64 = 10;
+
 /// A container.
 pub struct Container {
     /// A reference to the [`Docker`] client that will be used to create this
@@ -67,6 +73,11 @@ impl Container {
         }
     }
 
+    /// Gets the name of the container.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     /// Uploads an input file to the container.
     pub async fn upload_file(&self, path: &str, contents: Vec<u8>) -> Result<()> {
         let mut tar = tar::Builder::new(Vec::with_capacity(DEFAULT_TAR_CAPACITY));
@@ -151,19 +162,24 @@ impl Container {
             .wait_container(&self.name, None::<WaitContainerOptions<String>>);
 
         while let Some(result) = wait_stream.next().await {
-            let response = result.map_err(Error::Docker)?;
-
-            if enabled!(Level::TRACE) {
-                trace!("{response:?}");
+            match result {
+                Ok(response) => {
+                    if enabled!(Level::TRACE) {
+                        trace!("{response:?}");
+                    }
+                }
+                // NOTE: the daemon reports a non-zero exit code as an error
+                // when waiting. That's not an error from our perspective—the
+                // exit code is retrieved below.
+                Err(bollard::errors::Error::DockerContainerWaitError { code, .. }) => {
+                    trace!("container exited with code {code}");
+                }
+                Err(err) => return Err(Error::Docker(err)),
             }
         }
 
         // (5) Get the exit code.
-        let inspect = self
-            .client
-            .inspect_container(&self.name, None)
-            .await
-            .map_err(Error::Docker)?;
+        let inspect = self.inspect().await?;
 
         let status = inspect
             .state
@@ -171,9 +187,11 @@ impl Container {
             .exit_code
             .expect("exit code should be present at this point") as i32;
 
+        // NOTE: on Unix, the raw status is a wait status, which holds the exit
+        // code in its second byte.
         #[cfg(unix)]
         let output = Output {
-            status: ExitStatus::from_raw(status),
+            status: ExitStatus::from_raw(status << 8),
             stdout,
             stderr,
         };
@@ -188,6 +206,32 @@ impl Container {
         Ok(output)
     }
 
+    /// Inspects the container.
+    pub async fn inspect(&self) -> Result<ContainerInspectResponse> {
+        self.client
+            .inspect_container(&self.name, None)
+            .await
+            .map_err(Error::Docker)
+    }
+
+    /// Stops a running container.
+    ///
+    /// The container is sent a `SIGTERM` and, if it has not exited after
+    /// [`STOP_TIMEOUT_SECS`] seconds, a `SIGKILL`.
+    pub async fn stop(&self) -> Result<()> {
+        debug!("stopping container: `{}`", self.name);
+
+        self.client
+            .stop_container(
+                &self.name,
+                Some(StopContainerOptions {
+                    t: STOP_TIMEOUT_SECS,
+                }),
+            )
+            .await
+            .map_err(Error::Docker)
+    }
+
     /// Removes a container with the level of force specified.
     ///
     /// This is an inner function, meaning it's not public. There are two public
@@ -126,23 +126,50 @@ impl Builder {
         self
     }
 
-    /// Consumes `self` and attempts to create a Docker container.
+    /// Gets the configuration the container would be created with (without
+    /// creating it).
     ///
-    /// Note that the creation of a container does not indicate that it has
-    /// started.
-    pub async fn try_create(self, name: impl AsRef<str>) -> Result<Container> {
-        let name = name.as_ref();
-
+    /// This is useful for inspecting what would be sent to the Docker daemon
+    /// (e.g., during a dry run).
+    pub fn to_config(&self) -> Config<String> {
         let image = self
             .image
+            .clone()
             .expect("the `image` field must be set for a container builder");
         let command = self
             .command
+            .clone()
             .expect("the `command` field must be set for a container builder");
         let attached = self
             .attached
             .expect("the `attached` field must be set for a container builder");
 
+        Config {
+            // NOTE: even though the following fields are optional, I
+            // want _this_ struct to require the explicit designation
+            // one way or the other and not rely on the default.
+            cmd: Some(command),
+            image: Some(image),
+            attach_stdout: Some(attached),
+            attach_stderr: Some(attached),
+            // END NOTE
+            working_dir: self.workdir.clone(),
+            host_config: self.host_config.clone(),
+            env: self.env.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Consumes `self` and attempts to create a Docker container.
+    ///
+    /// Note that the creation of a container does not indicate that it has
+    /// started.
+    pub async fn try_create(self, name: impl AsRef<str>) -> Result<Container> {
+        let name = name.as_ref();
+        let config = self.to_config();
+        // SAFETY: `to_config()` always sets whether the streams are attached.
+        let attached = config.attach_stdout.unwrap();
+
         let response = self
             .client
             .create_container(
@@ -150,20 +177,7 @@ impl Builder {
                     name,
                     ..Default::default()
                 }),
-                Config {
-                    // NOTE: even though the following fields are optional, I
-                    // want _this_ struct to require the explicit designation
-                    // one way or the other and not rely on the default.
-                    cmd: Some(command),
-                    image: Some(image),
-                    attach_stdout: Some(attached),
-                    attach_stderr: Some(attached),
-                    // END NOTE
-                    working_dir: self.workdir,
-                    host_config: self.host_config,
-                    env: self.env,
-                    ..Default::default()
-                },
+                config,
             )
             .await
             .map_err(Error::Docker)?;
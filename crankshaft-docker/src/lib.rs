@@ -2,6 +2,7 @@
 
 use bollard::secret::ImageDeleteResponseItem;
 use bollard::secret::ImageSummary;
+use bollard::secret::SystemInfo;
 
 pub mod container;
 pub mod images;
@@ -62,6 +63,16 @@ impl Docker {
         &self.0
     }
 
+    //----------------------------------------------------------------------------------
+    // System
+    //----------------------------------------------------------------------------------
+
+    /// Gets system-wide information about the Docker daemon (including the
+    /// resources of the host it runs on).
+    pub async fn info(&self) -> Result<SystemInfo> {
+        self.0.info().await.map_err(Error::Docker)
+    }
+
     //----------------------------------------------------------------------------------
     // Images
     //----------------------------------------------------------------------------------
@@ -0,0 +1,344 @@
+//! Call caching.
+//!
+//! When a [`Store`] is configured (see [`Engine::with_cache()`]), each task is
+//! keyed by a [`Key`] computed from its content before it is run. If a result
+//! was previously stored under the same key, that result is returned without
+//! the task ever reaching the backend. Otherwise, the task is run and its
+//! result (if successful) is stored for next time.
+//!
+//! [`Engine::with_cache()`]: crate::Engine::with_cache
+
+use std::fmt::Debug;
+use std::io::Read as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use sha2::Digest as _;
+use sha2::Sha256;
+
+use crate::Task;
+use crate::service::runner::backend::TaskResult;
+use crate::task::input::Contents;
+use crate::task::input::Type;
+use crate::task::output;
+
+pub mod disk;
+pub(crate) mod record;
+
+/// The version of the hashing scheme.
+///
+/// This is included in every key so that changing how keys are computed never
+/// produces hits on results stored under the previous scheme.
+const VERSION: &str = "crankshaft-call-cache-v2";
+
+/// The size of the buffer used when hashing files.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// A stable hash over the content of a task.
+///
+/// The key covers the image, arguments, environment variables, and working
+/// directory of each execution; the contents of each input (along with where
+/// it is placed); where each output is collected from and delivered to; and
+/// the requested resources. Notably, the name and description of the task are
+/// _not_ included.
+///
+/// Inputs with local (`file://`) URLs are hashed by their contents (for
+/// directories, the contents of every file within them). Inputs with other
+/// URLs cannot be read locally, so they are hashed by their URL.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Key(pub(crate) String);
+
+impl Key {
+    /// Computes the key for a task.
+    ///
+    /// This reads the contents of every local input, so an error is returned
+    /// if any of them cannot be read.
+    pub async fn compute(task: &Task) -> std::io::Result<Self> {
+        let task = task.clone();
+
+        // NOTE: hashing local inputs is blocking file i/o, so the whole key is
+        // computed on a blocking thread.
+        tokio::task::spawn_blocking(move || Self::compute_blocking(&task))
+            .await
+            .map_err(std::io::Error::other)?
+    }
+
+    /// Computes the key for a task on the current thread.
+    fn compute_blocking(task: &Task) -> std::io::Result<Self> {
+        let mut hasher = Hasher::default();
+        hasher.field(VERSION);
+
+        for execution in task.executions() {
+            hasher.field("execution");
+            hasher.field(execution.image());
+
+            hasher.count(execution.args().len());
+            for arg in execution.args() {
+                hasher.field(arg);
+            }
+
+            let mut env = execution
+                .env()
+                .map(|env| env.iter().collect::<Vec<_>>())
+                .unwrap_or_default();
+            env.sort();
+
+            hasher.count(env.len());
+            for (name, value) in env {
+                hasher.field(name);
+                hasher.field(value);
+            }
+
+            hasher.optional(execution.workdir().map(String::as_str));
+        }
+
+        for input in task.inputs().into_iter().flatten() {
+            hasher.field("input");
+            hasher.field(input.path());
+
+            match input.contents() {
+                Contents::Literal(contents) => {
+                    hasher.field("literal");
+                    hasher.field(contents);
+                }
+                Contents::URL(url) if url.scheme() == "file" => {
+                    let path = url.to_file_path().map_err(|_| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            format!("invalid file URL: {url}"),
+                        )
+                    })?;
+
+                    match input.r#type() {
+                        Type::File => {
+                            hasher.field("file");
+                            hasher.file(&path)?;
+                        }
+                        Type::Directory => {
+                            hasher.field("directory");
+                            hasher.directory(&path, &path)?;
+                        }
+                    }
+                }
+                Contents::URL(url) => {
+                    hasher.field("url");
+                    hasher.field(url.as_str());
+                }
+            }
+        }
+
+        // NOTE: the outputs are part of the key because a hit never runs the
+        // task, so a task whose outputs are delivered elsewhere must never hit
+        // on a result that was delivered to different places.
+        for output in task.outputs().into_iter().flatten() {
+            hasher.field("output");
+            hasher.optional(output.name());
+            hasher.field(output.url());
+            hasher.field(output.path());
+            hasher.field(match output.r#type() {
+                output::Type::File => "file",
+                output::Type::Directory => "directory",
+            });
+        }
+
+        match task.resources() {
+            Some(resources) => {
+                hasher.field("resources");
+                hasher.optional(resources.cpu().map(|cpu| cpu.to_string()).as_deref());
+                hasher.optional(resources.ram().map(|ram| ram.to_string()).as_deref());
+                hasher.optional(resources.disk().map(|disk| disk.to_string()).as_deref());
+                hasher.optional(
+                    resources
+                        .preemptible()
+                        .map(|preemptible| preemptible.to_string())
+                        .as_deref(),
+                );
+
+                let zones = resources.zones().map(|zones| zones.len()).unwrap_or(0);
+                hasher.count(zones);
+                for zone in resources.zones().into_iter().flatten() {
+                    hasher.field(zone);
+                }
+            }
+            None => hasher.field("no resources"),
+        }
+
+        Ok(Self(format!("{:x}", hasher.0.finalize())))
+    }
+
+    /// Gets the key as a (hexadecimal) string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A hasher that writes each field with its length so that adjacent fields
+/// can never be confused with one another.
+#[derive(Default)]
+struct Hasher(Sha256);
+
+impl Hasher {
+    /// Writes a count (e.g., the number of items in a list).
+    fn count(&mut self, count: usize) {
+        self.0.update((count as u64).to_le_bytes());
+    }
+
+    /// Writes a field.
+    fn field(&mut self, value: impl AsRef<[u8]>) {
+        let value = value.as_ref();
+        self.count(value.len());
+        self.0.update(value);
+    }
+
+    /// Writes a field that may not be present.
+    fn optional(&mut self, value: Option<&str>) {
+        match value {
+            Some(value) => {
+                self.0.update([1]);
+                self.field(value);
+            }
+            None => self.0.update([0]),
+        }
+    }
+
+    /// Writes the contents of a file.
+    fn file(&mut self, path: &Path) -> std::io::Result<()> {
+        let mut file = std::fs::File::open(path)?;
+        self.count(file.metadata()?.len() as usize);
+
+        let mut buffer = vec![0; BUFFER_SIZE];
+        loop {
+            match file.read(&mut buffer)? {
+                0 => return Ok(()),
+                n => self.0.update(&buffer[..n]),
+            }
+        }
+    }
+
+    /// Writes the relative path and contents of every file within a directory
+    /// (in a stable order).
+    fn directory(&mut self, root: &Path, path: &Path) -> std::io::Result<()> {
+        let mut entries = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<PathBuf>>>()?;
+        entries.sort();
+
+        self.count(entries.len());
+        for entry in entries {
+            // SAFETY: every entry is within the root directory.
+            let relative = entry.strip_prefix(root).unwrap();
+            self.field(relative.to_string_lossy().as_bytes());
+
+            if entry.is_dir() {
+                self.directory(root, &entry)?;
+            } else {
+                self.file(&entry)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A store for the results of previously run tasks.
+#[async_trait]
+pub trait Store: Debug + Send + Sync + 'static {
+    /// Gets the result stored under a key (if one exists).
+    async fn get(&self, key: &Key) -> std::io::Result<Option<TaskResult>>;
+
+    /// Stores a result under a key.
+    ///
+    /// Any result previously stored under the key is replaced.
+    async fn put(&self, key: &Key, result: &TaskResult) -> std::io::Result<()>;
+}
+
+/// Returns whether the local (`file://`) outputs of a task still exist.
+///
+/// A stored result is only useful if the outputs it refers to are still
+/// around, so results for tasks with missing outputs are treated as misses.
+pub(crate) async fn outputs_exist(task: &Task) -> bool {
+    for output in task.outputs().into_iter().flatten() {
+        let url = match url::Url::parse(output.url()) {
+            Ok(url) if url.scheme() == "file" => url,
+            _ => continue,
+        };
+
+        let exists = match url.to_file_path() {
+            Ok(path) => tokio::fs::try_exists(path).await.unwrap_or(false),
+            Err(_) => false,
+        };
+
+        if !exists {
+            return false;
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::Execution;
+    use crate::task::Input;
+
+    fn task(arg: &str, input: &str) -> Task {
+        Task::builder()
+            .extend_executions([Execution::builder()
+                .image("ubuntu")
+                .args(["echo", arg])
+                .env("B", "2")
+                .env("A", "1")
+                .try_build()
+                .unwrap()])
+            .extend_inputs([Input::builder()
+                .contents(Contents::Literal(input.to_owned()))
+                .path("/input.txt")
+                .r#type(Type::File)
+                .try_build()
+                .unwrap()])
+            .try_build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn keys_depend_only_on_content() {
+        let key = Key::compute(&task("hello", "world")).await.unwrap();
+
+        assert_eq!(key, Key::compute(&task("hello", "world")).await.unwrap());
+        assert_ne!(key, Key::compute(&task("hello!", "world")).await.unwrap());
+        assert_ne!(key, Key::compute(&task("hello", "world!")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn keys_depend_on_where_outputs_are_delivered() {
+        let delivered = |url: &str| {
+            Task::builder()
+                .extend_executions([Execution::builder()
+                    .image("ubuntu")
+                    .args(["echo", "hello"])
+                    .try_build()
+                    .unwrap()])
+                .extend_outputs([output::Builder::default()
+                    .url(url::Url::parse(url).unwrap())
+                    .path("/out.txt")
+                    .r#type(output::Type::File)
+                    .try_build()
+                    .unwrap()])
+                .try_build()
+                .unwrap()
+        };
+
+        assert_ne!(
+            Key::compute(&delivered("s3://bucket/a.txt")).await.unwrap(),
+            Key::compute(&delivered("s3://bucket/b.txt")).await.unwrap()
+        );
+    }
+}
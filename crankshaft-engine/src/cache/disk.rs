@@ -0,0 +1,118 @@
+//! An on-disk call cache store.
+
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+
+use crate::cache::Key;
+use crate::cache::record::Record;
+use crate::service::runner::backend::TaskResult;
+
+/// The name of the directory (within the user's cache directory) that results
+/// are stored in by default.
+pub const DEFAULT_DIRECTORY: &str = "crankshaft/calls";
+
+/// A call cache store that keeps each result in a JSON file named by its key.
+#[derive(Clone, Debug)]
+pub struct Store {
+    /// The directory the results are stored in.
+    root: PathBuf,
+}
+
+impl Store {
+    /// Creates a new [`Store`] that keeps results within the provided
+    /// directory.
+    ///
+    /// The directory is created when the first result is stored.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Creates a new [`Store`] that keeps results within the
+    /// [default directory](DEFAULT_DIRECTORY) of the user's cache directory.
+    ///
+    /// Returns [`None`] if the user's cache directory cannot be determined.
+    pub fn with_defaults() -> Option<Self> {
+        dirs::cache_dir().map(|dir| Self::new(dir.join(DEFAULT_DIRECTORY)))
+    }
+
+    /// Gets the directory the results are stored in.
+    pub fn root(&self) -> &std::path::Path {
+        &self.root
+    }
+
+    /// Gets the path to the file for a key.
+    fn path(&self, key: &Key) -> PathBuf {
+        self.root.join(format!("{key}.json"))
+    }
+}
+
+#[async_trait]
+impl crate::cache::Store for Store {
+    async fn get(&self, key: &Key) -> std::io::Result<Option<TaskResult>> {
+        let contents = match tokio::fs::read(self.path(key)).await {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let record = serde_json::from_slice::<Record>(&contents)?;
+
+        // NOTE: a record without executions can only be the result of the file
+        // being tampered with, so it is treated as a miss.
+        Ok(record.into_result())
+    }
+
+    async fn put(&self, key: &Key, result: &TaskResult) -> std::io::Result<()> {
+        let record = Record::from(result);
+
+        tokio::fs::create_dir_all(&self.root).await?;
+
+        // NOTE: the record is written to a temporary file and then renamed so
+        // that a concurrent reader never sees a partially written record.
+        let path = self.path(key);
+        let temporary = path.with_extension(format!("json.{}", uuid::Uuid::new_v4()));
+        tokio::fs::write(&temporary, serde_json::to_vec(&record)?).await?;
+        tokio::fs::rename(&temporary, &path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    use std::os::unix::process::ExitStatusExt;
+    #[cfg(windows)]
+    use std::os::windows::process::ExitStatusExt;
+    use std::process::ExitStatus;
+    use std::process::Output;
+
+    use nonempty::NonEmpty;
+
+    use super::*;
+    use crate::cache::Store as _;
+
+    #[tokio::test]
+    async fn results_round_trip() {
+        let root = tempfile::tempdir().unwrap();
+        let store = Store::new(root.path());
+        let key = Key(String::from("abc123"));
+
+        assert!(store.get(&key).await.unwrap().is_none());
+
+        let result = TaskResult::new(NonEmpty::new(Output {
+            status: ExitStatus::from_raw(0),
+            stdout: b"hello".to_vec(),
+            stderr: Vec::new(),
+        }));
+        store.put(&key, &result).await.unwrap();
+
+        // NOTE: output is stored as base64 rather than as an array of numbers.
+        let contents = std::fs::read_to_string(root.path().join("abc123.json")).unwrap();
+        assert!(contents.contains(r#""stdout":"aGVsbG8=""#));
+
+        let stored = store.get(&key).await.unwrap().unwrap();
+        assert!(stored.is_cached());
+        assert_eq!(stored.executions().first().stdout, b"hello");
+        assert!(stored.executions().first().status.success());
+    }
+}
@@ -0,0 +1,109 @@
+//! Serializable records of task results.
+//!
+//! These are shared by everything that persists results (e.g., the
+//! [on-disk store](crate::cache::disk::Store) and the
+//! [journal](crate::journal::Journal)).
+
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+#[cfg(windows)]
+use std::os::windows::process::ExitStatusExt;
+use std::process::ExitStatus;
+use std::process::Output;
+
+use nonempty::NonEmpty;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::service::runner::backend::TaskResult;
+
+/// (De)serializes raw bytes as base64 strings.
+///
+/// Serde writes a `Vec<u8>` as an array of numbers by default, which takes up
+/// to four times as much space as the output it holds.
+mod bytes {
+    use base64::Engine as _;
+    use base64::engine::general_purpose::STANDARD;
+    use serde::Deserialize as _;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    /// Serializes bytes as a base64 string.
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    /// Deserializes bytes from a base64 string.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A stored execution.
+#[derive(Serialize, Deserialize)]
+struct Execution {
+    /// The raw exit status.
+    status: i64,
+
+    /// The standard output.
+    #[serde(with = "bytes")]
+    stdout: Vec<u8>,
+
+    /// The standard error.
+    #[serde(with = "bytes")]
+    stderr: Vec<u8>,
+}
+
+impl From<&Output> for Execution {
+    fn from(output: &Output) -> Self {
+        Self {
+            status: output.status.into_raw() as i64,
+            stdout: output.stdout.clone(),
+            stderr: output.stderr.clone(),
+        }
+    }
+}
+
+impl From<Execution> for Output {
+    fn from(execution: Execution) -> Self {
+        #[cfg(unix)]
+        let status = ExitStatus::from_raw(execution.status as i32);
+
+        #[cfg(windows)]
+        let status = ExitStatus::from_raw(execution.status as u32);
+
+        Self {
+            status,
+            stdout: execution.stdout,
+            stderr: execution.stderr,
+        }
+    }
+}
+
+/// A stored result.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Record {
+    /// The stored executions.
+    executions: Vec<Execution>,
+}
+
+impl Record {
+    /// Converts the record back into a [`TaskResult`].
+    ///
+    /// The result is marked as [cached](TaskResult::is_cached), as it did not
+    /// come from a backend. Returns [`None`] if the record has no executions,
+    /// which can only be the result of it being tampered with.
+    pub(crate) fn into_result(self) -> Option<TaskResult> {
+        NonEmpty::from_vec(self.executions.into_iter().map(Output::from).collect())
+            .map(|executions| TaskResult::new(executions).cached())
+    }
+}
+
+impl From<&TaskResult> for Record {
+    fn from(result: &TaskResult) -> Self {
+        Self {
+            executions: result.executions().iter().map(Execution::from).collect(),
+        }
+    }
+}
@@ -0,0 +1,665 @@
+//! Graphs of dependent tasks.
+//!
+//! A [`Graph`] is a set of tasks (each destined for a named runner) along with
+//! the dependencies between them. A task is only submitted once all of its
+//! upstream tasks have succeeded, and it is skipped (along with all of its
+//! descendants) if any of them fail. Because each task names its own runner, a
+//! single graph can span multiple backends.
+//!
+//! A task may also [bind](Graph::bind) the [`Output`](crate::task::Output)s of
+//! an upstream task as its own [`Input`]s, which implies a dependency on that
+//! task.
+//!
+//! Each node is assigned the [`TaskId`] of its task when the graph is submitted
+//! (see [`GraphHandle::task()`]), so a node can be queried through
+//! [`Engine::status()`] once it is submitted and [cancelled](Engine::cancel)
+//! at any point.
+
+use std::collections::VecDeque;
+
+use futures::StreamExt as _;
+use futures::stream::FuturesUnordered;
+use tokio::sync::oneshot::Receiver;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+use url::Url;
+
+use crate::Engine;
+use crate::SubmitError;
+use crate::Task;
+use crate::service::runner::TaskId;
+use crate::service::runner::backend::TaskError;
+use crate::service::runner::backend::TaskResult;
+use crate::task::Input;
+use crate::task::input;
+use crate::task::output;
+
+/// An error related to a [`Graph`].
+#[derive(Debug)]
+pub enum Error {
+    /// A node does not belong to the graph.
+    UnknownNode(NodeId),
+
+    /// A bound output does not exist on the upstream task.
+    UnknownOutput {
+        /// The upstream node.
+        node: NodeId,
+
+        /// The name of the output.
+        output: String,
+    },
+
+    /// The dependencies between the nodes form a cycle.
+    Cycle,
+
+    /// A node could not be submitted to its runner.
+    Submit(SubmitError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::UnknownNode(node) => write!(f, "node {node} does not belong to the graph"),
+            Error::UnknownOutput { node, output } => {
+                write!(f, "node {node} has no output named `{output}`")
+            }
+            Error::Cycle => write!(f, "the graph contains a cycle"),
+            Error::Submit(err) => write!(f, "submit error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A [`Result`](std::result::Result) with an [`Error`].
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The identifier of a node within a [`Graph`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// Gets the index of the node (i.e., the order in which it was added to
+    /// the graph).
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+impl std::fmt::Display for NodeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// A node within a [`Graph`].
+#[derive(Debug)]
+struct Node {
+    /// The name of the runner to submit the task to.
+    runner: String,
+
+    /// The task.
+    task: Task,
+
+    /// The nodes this node depends on.
+    dependencies: Vec<NodeId>,
+
+    /// The inputs bound from the outputs of upstream nodes.
+    bindings: Vec<Input>,
+}
+
+/// A graph of dependent tasks.
+#[derive(Debug, Default)]
+pub struct Graph {
+    /// The nodes within the graph.
+    nodes: Vec<Node>,
+}
+
+impl Graph {
+    /// Adds a task to be submitted to the runner with the provided name.
+    pub fn add(&mut self, runner: impl Into<String>, task: Task) -> NodeId {
+        self.nodes.push(Node {
+            runner: runner.into(),
+            task,
+            dependencies: Default::default(),
+            bindings: Default::default(),
+        });
+
+        NodeId(self.nodes.len() - 1)
+    }
+
+    /// Declares that `node` depends on `upstream`.
+    ///
+    /// `node` is only submitted once `upstream` succeeds.
+    pub fn depend(&mut self, node: NodeId, upstream: NodeId) -> Result<()> {
+        self.node(upstream)?;
+
+        let dependencies = &mut self.node_mut(node)?.dependencies;
+        if !dependencies.contains(&upstream) {
+            dependencies.push(upstream);
+        }
+
+        Ok(())
+    }
+
+    /// Binds the output named `output` of `upstream` as an input of `node`
+    /// placed at `path`.
+    ///
+    /// This implies that `node` [depends](Self::depend) on `upstream`.
+    pub fn bind(
+        &mut self,
+        node: NodeId,
+        upstream: NodeId,
+        output: &str,
+        path: impl Into<String>,
+    ) -> Result<()> {
+        let found = self
+            .node(upstream)?
+            .task
+            .outputs()
+            .and_then(|mut outputs| outputs.find(|candidate| candidate.name() == Some(output)))
+            .ok_or_else(|| Error::UnknownOutput {
+                node: upstream,
+                output: output.to_owned(),
+            })?;
+
+        // SAFETY: an output's URL is always parsed from a valid URL, and every
+        // required field is provided to the builder, so these will always
+        // unwrap.
+        let input = Input::builder()
+            .name(output)
+            .contents(input::Contents::URL(Url::parse(found.url()).unwrap()))
+            .path(path)
+            .r#type(match found.r#type() {
+                output::Type::File => input::Type::File,
+                output::Type::Directory => input::Type::Directory,
+            })
+            .try_build()
+            .unwrap();
+
+        self.depend(node, upstream)?;
+        self.node_mut(node)?.bindings.push(input);
+        Ok(())
+    }
+
+    /// Gets the number of nodes in the graph.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns whether the graph has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Gets a node.
+    fn node(&self, id: NodeId) -> Result<&Node> {
+        self.nodes.get(id.0).ok_or(Error::UnknownNode(id))
+    }
+
+    /// Gets a node mutably.
+    fn node_mut(&mut self, id: NodeId) -> Result<&mut Node> {
+        self.nodes.get_mut(id.0).ok_or(Error::UnknownNode(id))
+    }
+
+    /// Gets the direct dependents of each node.
+    fn dependents(&self) -> Vec<Vec<NodeId>> {
+        let mut dependents = vec![Vec::new(); self.nodes.len()];
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            for dependency in &node.dependencies {
+                dependents[dependency.0].push(NodeId(index));
+            }
+        }
+
+        dependents
+    }
+
+    /// Ensures the dependencies between the nodes do not form a cycle.
+    fn ensure_acyclic(&self) -> Result<()> {
+        let dependents = self.dependents();
+        let mut remaining = self
+            .nodes
+            .iter()
+            .map(|node| node.dependencies.len())
+            .collect::<Vec<_>>();
+        let mut ready = (0..self.nodes.len())
+            .filter(|index| remaining[*index] == 0)
+            .collect::<VecDeque<_>>();
+        let mut visited = 0;
+
+        while let Some(index) = ready.pop_front() {
+            visited += 1;
+
+            for dependent in &dependents[index] {
+                remaining[dependent.0] -= 1;
+                if remaining[dependent.0] == 0 {
+                    ready.push_back(dependent.0);
+                }
+            }
+        }
+
+        if visited == self.nodes.len() {
+            Ok(())
+        } else {
+            Err(Error::Cycle)
+        }
+    }
+}
+
+/// An error for a node within a [`Graph`] that did not succeed.
+#[derive(Clone, Debug)]
+pub enum NodeError {
+    /// The task was submitted but did not complete successfully.
+    Failed(TaskError),
+
+    /// The task was never submitted because an upstream node did not succeed.
+    Skipped {
+        /// The upstream node that did not succeed.
+        upstream: NodeId,
+    },
+
+    /// The task was rejected by its runner.
+    Rejected(SubmitError),
+}
+
+impl std::fmt::Display for NodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NodeError::Failed(err) => write!(f, "{err}"),
+            NodeError::Skipped { upstream } => {
+                write!(f, "skipped because node {upstream} did not succeed")
+            }
+            NodeError::Rejected(err) => write!(f, "task was rejected: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for NodeError {}
+
+/// The outcome of each node within a [`Graph`].
+#[derive(Debug)]
+pub struct GraphResult {
+    /// The outcome of each node (indexed by [`NodeId`]).
+    outcomes: Vec<std::result::Result<TaskResult, NodeError>>,
+}
+
+impl GraphResult {
+    /// Gets the outcome of a node.
+    pub fn get(&self, node: NodeId) -> Option<&std::result::Result<TaskResult, NodeError>> {
+        self.outcomes.get(node.0)
+    }
+
+    /// Gets the outcome of every node (in the order they were added).
+    pub fn iter(
+        &self,
+    ) -> impl Iterator<Item = (NodeId, &std::result::Result<TaskResult, NodeError>)> {
+        self.outcomes
+            .iter()
+            .enumerate()
+            .map(|(index, outcome)| (NodeId(index), outcome))
+    }
+
+    /// Returns whether every node succeeded.
+    pub fn is_success(&self) -> bool {
+        self.outcomes.iter().all(|outcome| outcome.is_ok())
+    }
+}
+
+/// A submitted graph handle.
+#[derive(Debug)]
+pub struct GraphHandle {
+    /// A callback that is executed when every node has either completed or
+    /// been skipped.
+    pub callback: Receiver<GraphResult>,
+
+    /// The id of the task of each node (indexed by [`NodeId`]).
+    tasks: Vec<TaskId>,
+
+    /// The token used to cancel the graph.
+    token: CancellationToken,
+}
+
+impl GraphHandle {
+    /// Gets the id of the task of a node.
+    ///
+    /// Returns `None` if the node does not belong to the graph.
+    pub fn task(&self, node: NodeId) -> Option<TaskId> {
+        self.tasks.get(node.0).copied()
+    }
+
+    /// Cancels the graph.
+    ///
+    /// Tasks that are running are [cancelled](crate::TaskHandle::cancel), and
+    /// tasks that have not yet been submitted never will be.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+}
+
+/// Submits a graph to the engine.
+///
+/// Every runner named within the graph is checked (and the graph is checked
+/// for cycles) before any task is submitted.
+pub(crate) fn submit(engine: Engine, graph: Graph) -> Result<GraphHandle> {
+    if engine.tracker.is_closed() {
+        return Err(Error::Submit(SubmitError::ShutDown));
+    }
+
+    for node in &graph.nodes {
+        if !engine.runners().any(|name| name == node.runner) {
+            return Err(Error::Submit(SubmitError::UnknownRunner {
+                name: node.runner.clone(),
+                known: engine.runners().map(String::from).collect(),
+            }));
+        }
+    }
+
+    graph.ensure_acyclic()?;
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let token = CancellationToken::new();
+
+    // NOTE: each node's token is registered with the engine up front so that
+    // a node can be cancelled before it is submitted.
+    let tasks = graph
+        .nodes
+        .iter()
+        .map(|_| (TaskId::generate(), token.child_token()))
+        .collect::<Vec<_>>();
+    engine.tokens.lock().unwrap().extend(tasks.iter().cloned());
+
+    let handle = GraphHandle {
+        callback: rx,
+        tasks: tasks.iter().map(|(id, _)| *id).collect(),
+        token,
+    };
+
+    engine.tracker.clone().spawn(async move {
+        let result = run(&engine, graph, &tasks).await;
+
+        let mut tokens = engine.tokens.lock().unwrap();
+        for (id, _) in &tasks {
+            tokens.remove(id);
+        }
+        drop(tokens);
+
+        // NOTE: if the send does not succeed, that is almost certainly because
+        // the receiver was dropped, which is fine.
+        let _ = tx.send(result);
+    });
+
+    Ok(handle)
+}
+
+/// Runs a graph to completion.
+///
+/// The task of each node is submitted under the provided id and cancellation
+/// token (indexed by [`NodeId`]).
+async fn run(engine: &Engine, graph: Graph, tasks: &[(TaskId, CancellationToken)]) -> GraphResult {
+    let dependents = graph.dependents();
+    let mut remaining = graph
+        .nodes
+        .iter()
+        .map(|node| node.dependencies.len())
+        .collect::<Vec<_>>();
+    let mut nodes = graph.nodes.into_iter().map(Some).collect::<Vec<_>>();
+    let mut outcomes = (0..nodes.len()).map(|_| None).collect::<Vec<_>>();
+
+    let mut ready = (0..nodes.len())
+        .filter(|index| remaining[*index] == 0)
+        .collect::<VecDeque<_>>();
+    let mut running = FuturesUnordered::new();
+
+    loop {
+        while let Some(index) = ready.pop_front() {
+            // SAFETY: each node is only ever made ready once.
+            let Node {
+                runner,
+                mut task,
+                bindings,
+                ..
+            } = nodes[index].take().unwrap();
+
+            let (id, token) = &tasks[index];
+
+            if token.is_cancelled() {
+                outcomes[index] = Some(Err(NodeError::Failed(TaskError::Cancelled)));
+                skip(index, &dependents, &mut outcomes);
+                continue;
+            }
+
+            task.extend_inputs(bindings);
+            debug!("submitting graph node #{index} to the `{runner}` runner");
+
+            // NOTE: the graph was accepted before any shutdown, so its nodes
+            // are submitted even if the engine has since been shut down.
+            match engine.resubmit(&runner, *id, token.clone(), task) {
+                Ok(handle) => {
+                    running.push(async move { (index, handle.callback.await) });
+                }
+                Err(err) => {
+                    outcomes[index] = Some(Err(NodeError::Rejected(err)));
+                    skip(index, &dependents, &mut outcomes);
+                }
+            }
+        }
+
+        // NOTE: cancelling the graph cancels the token of every node, so the
+        // running nodes resolve on their own.
+        let Some((index, result)) = running.next().await else {
+            break;
+        };
+
+        // NOTE: the runner always sends a result unless the runtime itself is
+        // shutting down, in which case the task will never complete.
+        let result = result.unwrap_or(Err(TaskError::Cancelled));
+        let succeeded = result.is_ok();
+        outcomes[index] = Some(result.map_err(NodeError::Failed));
+
+        if succeeded {
+            for dependent in &dependents[index] {
+                remaining[dependent.0] -= 1;
+                if remaining[dependent.0] == 0 && outcomes[dependent.0].is_none() {
+                    ready.push_back(dependent.0);
+                }
+            }
+        } else {
+            skip(index, &dependents, &mut outcomes);
+        }
+    }
+
+    GraphResult {
+        // SAFETY: every node is either completed or skipped once nothing is
+        // running and nothing is ready, as the graph is acyclic.
+        outcomes: outcomes.into_iter().map(Option::unwrap).collect(),
+    }
+}
+
+/// Skips every (undecided) descendant of a node that did not succeed.
+fn skip(
+    index: usize,
+    dependents: &[Vec<NodeId>],
+    outcomes: &mut [Option<std::result::Result<TaskResult, NodeError>>],
+) {
+    let mut stack = dependents[index].clone();
+
+    while let Some(dependent) = stack.pop() {
+        if outcomes[dependent.0].is_none() {
+            outcomes[dependent.0] = Some(Err(NodeError::Skipped {
+                upstream: NodeId(index),
+            }));
+            stack.extend(dependents[dependent.0].iter().copied());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::ExitStatus;
+    use std::process::Output;
+    use std::sync::Arc;
+
+    use futures::FutureExt as _;
+    use futures::future::BoxFuture;
+    use nonempty::NonEmpty;
+
+    use super::*;
+    use crate::Backend;
+    use crate::service::Runner;
+    use crate::service::runner::event::Emitter;
+    use crate::task::Execution;
+
+    /// A backend that succeeds for tasks whose command is `true` and fails
+    /// for everything else.
+    #[derive(Debug)]
+    struct Exit;
+
+    impl Backend for Exit {
+        fn default_name(&self) -> &'static str {
+            "exit"
+        }
+
+        fn run(
+            &self,
+            task: Task,
+            _: Emitter,
+            _: CancellationToken,
+        ) -> BoxFuture<'static, std::result::Result<TaskResult, TaskError>> {
+            let succeeds = task.executions().next().unwrap().args().first() == "true";
+
+            async move {
+                let output = Output {
+                    status: ExitStatus::default(),
+                    stdout: Vec::new(),
+                    stderr: Vec::new(),
+                };
+
+                if succeeds {
+                    Ok(TaskResult::new(NonEmpty::new(output)))
+                } else {
+                    Err(TaskError::execution_failed(output))
+                }
+            }
+            .boxed()
+        }
+    }
+
+    fn task(command: &str) -> Task {
+        Task::builder()
+            .extend_executions([Execution::builder()
+                .image("ubuntu")
+                .args([command])
+                .try_build()
+                .unwrap()])
+            .extend_outputs([output::Builder::default()
+                .name("out")
+                .url(Url::parse("file:///tmp/out.txt").unwrap())
+                .path("/out.txt")
+                .r#type(output::Type::File)
+                .try_build()
+                .unwrap()])
+            .try_build()
+            .unwrap()
+    }
+
+    fn engine() -> Engine {
+        Engine::default()
+            .with_runner("a", Runner::new(Arc::new(Exit), 1))
+            .with_runner("b", Runner::new(Arc::new(Exit), 1))
+    }
+
+    #[tokio::test]
+    async fn descendants_of_failed_nodes_are_skipped() {
+        let mut graph = Graph::default();
+        let root = graph.add("a", task("true"));
+        let failed = graph.add("b", task("false"));
+        let skipped = graph.add("a", task("true"));
+        let independent = graph.add("b", task("true"));
+
+        graph.depend(failed, root).unwrap();
+        graph.bind(skipped, failed, "out", "/in.txt").unwrap();
+        graph.depend(independent, root).unwrap();
+
+        let handle = engine().submit_graph(graph).unwrap();
+        let result = handle.callback.await.unwrap();
+
+        assert!(!result.is_success());
+        assert!(result.get(root).unwrap().is_ok());
+        assert!(matches!(
+            result.get(failed).unwrap(),
+            Err(NodeError::Failed(TaskError::ExecutionFailed { .. }))
+        ));
+        assert!(matches!(
+            result.get(skipped).unwrap(),
+            Err(NodeError::Skipped { upstream }) if *upstream == failed
+        ));
+        assert!(result.get(independent).unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn graphs_finish_while_the_engine_drains() {
+        let mut graph = Graph::default();
+        let root = graph.add("a", task("true"));
+        let child = graph.add("b", task("true"));
+        graph.depend(child, root).unwrap();
+
+        let engine = engine();
+        let mut handle = engine.submit_graph(graph).unwrap();
+        engine.clone().run().await;
+
+        // NOTE: the engine only finishes running once the graph has finished.
+        let result = handle.callback.try_recv().unwrap();
+        assert!(result.is_success());
+
+        assert!(matches!(
+            engine.submit_graph(Graph::default()),
+            Err(Error::Submit(SubmitError::ShutDown))
+        ));
+    }
+
+    #[tokio::test]
+    async fn nodes_can_be_queried_and_cancelled_by_task_id() {
+        let mut graph = Graph::default();
+        let root = graph.add("a", task("true"));
+        let child = graph.add("b", task("true"));
+        let grandchild = graph.add("a", task("true"));
+        graph.depend(child, root).unwrap();
+        graph.depend(grandchild, child).unwrap();
+
+        let engine = engine();
+        let mut handle = engine.submit_graph(graph).unwrap();
+
+        // NOTE: the child is cancelled before it is submitted.
+        assert!(engine.cancel(handle.task(child).unwrap()));
+        let result = (&mut handle.callback).await.unwrap();
+
+        assert!(result.get(root).unwrap().is_ok());
+        assert!(engine.status(handle.task(root).unwrap()).is_some());
+        assert!(matches!(
+            result.get(child).unwrap(),
+            Err(NodeError::Failed(TaskError::Cancelled))
+        ));
+        assert!(matches!(
+            result.get(grandchild).unwrap(),
+            Err(NodeError::Skipped { upstream }) if *upstream == child
+        ));
+        assert!(!engine.cancel(handle.task(grandchild).unwrap()));
+    }
+
+    #[test]
+    fn invalid_graphs_are_rejected() {
+        let mut graph = Graph::default();
+        let a = graph.add("a", task("true"));
+        let b = graph.add("a", task("true"));
+
+        assert!(matches!(
+            graph.bind(b, a, "missing", "/in.txt"),
+            Err(Error::UnknownOutput { .. })
+        ));
+
+        graph.depend(a, b).unwrap();
+        graph.depend(b, a).unwrap();
+        assert!(matches!(graph.ensure_acyclic(), Err(Error::Cycle)));
+    }
+}
@@ -0,0 +1,585 @@
+//! A persistent journal of task runs.
+//!
+//! A [`Journal`] is an append-only file of JSON lines that records each task
+//! submitted to the engine (keyed by its [call cache key](crate::cache::Key)),
+//! the identifiers the backends report for the work they submit, the state
+//! transitions of each task, and the result of each task.
+//!
+//! If the process driving the engine dies, the journal can be
+//! [resumed](Journal::resume). When the same tasks are submitted again, tasks
+//! that had completed resolve with their recorded result, and tasks whose
+//! work was still in flight are reattached to through the backend (when the
+//! backend [supports it](crate::Backend::reattach) and is the same kind of
+//! backend the work was submitted to) rather than being run again.
+//!
+//! Entries are appended by a dedicated thread so that writing to the journal
+//! never blocks the runtime. The file is synced to disk after each entry that
+//! records a task finishing.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read as _;
+use std::io::Seek as _;
+use std::io::SeekFrom;
+use std::io::Write as _;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::SystemTime;
+
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::cache::Key;
+use crate::cache::record::Record;
+use crate::service::runner::TaskId;
+use crate::service::runner::backend::Reattach;
+use crate::service::runner::backend::TaskResult;
+use crate::service::runner::event::TaskState;
+
+/// The size of the buffer used when searching for the end of the last
+/// complete line of a journal.
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// An entry within the journal.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Entry {
+    /// A task was submitted to the engine.
+    Task {
+        /// The call cache key of the task.
+        key: String,
+
+        /// The name of the backend the task was submitted to.
+        backend: String,
+
+        /// The index of the execution the run starts from (which is only
+        /// non-zero when reattaching to work submitted by a previous run).
+        #[serde(default)]
+        execution: usize,
+    },
+
+    /// Work for the task was submitted to the backend.
+    Submitted {
+        /// The identifier the backend reported for the work (if any).
+        job_id: Option<String>,
+    },
+
+    /// The task began running.
+    Running,
+
+    /// An attempt at running the task failed and will be retried.
+    Retrying {
+        /// The number of the attempt that failed.
+        attempt: u32,
+
+        /// The error the attempt failed with.
+        error: String,
+    },
+
+    /// The task completed successfully.
+    Completed {
+        /// The result of the task.
+        result: Record,
+    },
+
+    /// The task failed.
+    Failed {
+        /// The error the task failed with.
+        error: String,
+    },
+}
+
+/// A line within the journal.
+#[derive(Serialize, Deserialize)]
+struct Line {
+    /// The id of the task the entry is for.
+    id: String,
+
+    /// The time at which the entry was recorded.
+    timestamp: SystemTime,
+
+    /// The entry.
+    #[serde(flatten)]
+    entry: Entry,
+}
+
+/// The state of a task as of the end of a journal.
+#[derive(Debug)]
+enum Recovered {
+    /// The task completed successfully.
+    Completed(TaskResult),
+
+    /// Work for the task was submitted to the backend and never finished.
+    InFlight {
+        /// The name of the backend the work was submitted to.
+        backend: String,
+
+        /// The work to reattach to.
+        reattach: Reattach,
+    },
+}
+
+/// How a task submitted to a resumed engine should proceed.
+pub(crate) enum Resumed {
+    /// The task had completed, so its recorded result should be returned.
+    Completed(TaskResult),
+
+    /// The task was in flight, so the backend should reattach to it.
+    Reattach(Reattach),
+}
+
+/// The thread that appends lines to a journal's file.
+#[derive(Debug)]
+struct Writer {
+    /// The channel over which lines are sent to the thread (along with whether
+    /// the file should be synced to disk once the line is written).
+    sender: mpsc::Sender<(String, bool)>,
+
+    /// The thread.
+    thread: JoinHandle<()>,
+}
+
+impl Writer {
+    /// Spawns a thread that appends lines to the provided file.
+    fn spawn(mut file: File) -> std::io::Result<Self> {
+        let (sender, receiver) = mpsc::channel::<(String, bool)>();
+
+        let thread = std::thread::Builder::new()
+            .name(String::from("crankshaft-journal"))
+            .spawn(move || {
+                for (line, sync) in receiver {
+                    // NOTE: each line is written with a single call so that a
+                    // crash can at most leave a partial line at the end of the
+                    // file (which is ignored when recovering).
+                    let result = file.write_all(line.as_bytes()).and_then(|_| match sync {
+                        true => file.sync_data(),
+                        false => Ok(()),
+                    });
+
+                    if let Err(err) = result {
+                        warn!("unable to write to the journal: {err}");
+                    }
+                }
+            })?;
+
+        Ok(Self { sender, thread })
+    }
+}
+
+/// A persistent journal of task runs.
+#[derive(Debug)]
+pub struct Journal {
+    /// The writer of the file being appended to.
+    ///
+    /// This is only ever `None` once the journal is being dropped.
+    writer: Option<Writer>,
+
+    /// The tasks recovered from a previous run (keyed by call cache key).
+    recovered: Mutex<HashMap<Key, Recovered>>,
+}
+
+impl Journal {
+    /// Opens a journal for appending, creating the file if it does not exist.
+    ///
+    /// Any entries already within the file are kept but otherwise ignored (use
+    /// [`Journal::resume()`] to pick up where a previous run left off).
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self {
+            writer: Some(Writer::spawn(append(path.as_ref())?)?),
+            recovered: Default::default(),
+        })
+    }
+
+    /// Opens a journal for appending after recovering the state of every task
+    /// recorded within it.
+    ///
+    /// Submitting a task whose key matches a recovered task that completed
+    /// resolves with the recorded result, and submitting one whose key matches
+    /// a recovered task that was still in flight reattaches to that work.
+    pub fn resume(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+
+        let recovered = match File::open(path) {
+            Ok(file) => recover(BufReader::new(file))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self {
+            writer: Some(Writer::spawn(append(path)?)?),
+            recovered: Mutex::new(recovered),
+        })
+    }
+
+    /// Gets the number of recovered tasks that have not yet been resubmitted.
+    pub fn recovered(&self) -> usize {
+        self.recovered.lock().unwrap().len()
+    }
+
+    /// Determines how a task with the provided key should proceed when it is
+    /// submitted to a backend with the provided name.
+    ///
+    /// In-flight work can only be reattached to by the same kind of backend it
+    /// was submitted to and only once, so it is forgotten once it has been
+    /// handed out (or once a different kind of backend runs the task from
+    /// scratch).
+    pub(crate) fn resumed(&self, key: &Key, backend: &str) -> Option<Resumed> {
+        let mut recovered = self.recovered.lock().unwrap();
+
+        match recovered.remove(key)? {
+            Recovered::Completed(result) => {
+                recovered.insert(key.clone(), Recovered::Completed(result.clone()));
+                Some(Resumed::Completed(result))
+            }
+            Recovered::InFlight {
+                backend: submitted,
+                reattach,
+            } if submitted == backend => Some(Resumed::Reattach(reattach)),
+            Recovered::InFlight { .. } => None,
+        }
+    }
+
+    /// Records that a task was submitted.
+    ///
+    /// `execution` is the index of the execution the run starts from (see
+    /// [`Reattach::execution`]).
+    pub(crate) fn task(&self, id: TaskId, key: &Key, backend: &str, execution: usize) {
+        self.write(
+            id,
+            Entry::Task {
+                key: key.to_string(),
+                backend: backend.to_owned(),
+                execution,
+            },
+        );
+    }
+
+    /// Records that a task completed successfully.
+    pub(crate) fn completed(&self, id: TaskId, result: &TaskResult) {
+        self.write(
+            id,
+            Entry::Completed {
+                result: Record::from(result),
+            },
+        );
+    }
+
+    /// Records a task transitioning to a new state.
+    ///
+    /// Completions are not recorded here, as they are recorded along with
+    /// their result (see [`Journal::completed()`]), and neither is queueing, as
+    /// that is recorded along with the key of the task (see
+    /// [`Journal::task()`]).
+    pub(crate) fn state(&self, id: TaskId, state: &TaskState) {
+        let entry = match state {
+            TaskState::Queued | TaskState::Completed => return,
+            TaskState::Submitted { id } => Entry::Submitted { job_id: id.clone() },
+            TaskState::Running => Entry::Running,
+            TaskState::Retrying { attempt, error, .. } => Entry::Retrying {
+                attempt: *attempt,
+                error: error.to_string(),
+            },
+            TaskState::Failed(error) => Entry::Failed {
+                error: error.to_string(),
+            },
+        };
+
+        self.write(id, entry);
+    }
+
+    /// Writes an entry to the journal.
+    ///
+    /// The entry is handed to the writer thread, and the file is synced to disk
+    /// once entries that record a task finishing are written. Failures are
+    /// logged rather than returned, as a journal that cannot be written to
+    /// should not prevent tasks from running.
+    fn write(&self, id: TaskId, entry: Entry) {
+        let sync = matches!(entry, Entry::Completed { .. } | Entry::Failed { .. });
+        let line = Line {
+            id: id.to_string(),
+            timestamp: SystemTime::now(),
+            entry,
+        };
+
+        let mut line = match serde_json::to_string(&line) {
+            Ok(line) => line,
+            Err(err) => {
+                warn!("unable to serialize journal entry: {err}");
+                return;
+            }
+        };
+        line.push('\n');
+
+        // SAFETY: the writer is only taken when the journal is dropped.
+        let writer = self.writer.as_ref().unwrap();
+
+        if writer.sender.send((line, sync)).is_err() {
+            warn!("unable to write to the journal: the writer thread has stopped");
+        }
+    }
+}
+
+impl Drop for Journal {
+    fn drop(&mut self) {
+        if let Some(Writer { sender, thread }) = self.writer.take() {
+            // NOTE: closing the channel stops the thread once every line sent
+            // so far is written, so nothing recorded is lost.
+            drop(sender);
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Opens a file for appending.
+///
+/// A partial line left at the end of the file by a crash is truncated first so
+/// that the entries appended from then on each begin on a line of their own.
+fn append(path: &Path) -> std::io::Result<File> {
+    truncate_partial_line(path)?;
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Truncates the file to just after its last newline (if it exists).
+fn truncate_partial_line(path: &Path) -> std::io::Result<()> {
+    let mut file = match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    let mut end = file.metadata()?.len();
+    let mut buffer = vec![0; BUFFER_SIZE];
+
+    while end > 0 {
+        let start = end.saturating_sub(BUFFER_SIZE as u64);
+        let chunk = &mut buffer[..(end - start) as usize];
+
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+
+        if let Some(newline) = chunk.iter().rposition(|byte| *byte == b'\n') {
+            let len = start + newline as u64 + 1;
+            if len < file.metadata()?.len() {
+                file.set_len(len)?;
+            }
+
+            return Ok(());
+        }
+
+        end = start;
+    }
+
+    // NOTE: the file has no complete line at all.
+    file.set_len(0)
+}
+
+/// Recovers the last known state of each task recorded within a journal.
+fn recover(reader: impl BufRead) -> std::io::Result<HashMap<Key, Recovered>> {
+    /// The state of a single task run.
+    #[derive(Default)]
+    struct Run {
+        /// The key of the task.
+        key: Option<Key>,
+
+        /// The name of the backend the task was submitted to.
+        backend: String,
+
+        /// The index of the execution the run started from.
+        execution: usize,
+
+        /// The number of times work was submitted to the backend during the
+        /// current attempt.
+        submissions: usize,
+
+        /// The last identifier the backend reported.
+        job_id: Option<String>,
+
+        /// Whether the task finished (successfully or not).
+        finished: bool,
+
+        /// The result of the task (if it completed successfully).
+        result: Option<TaskResult>,
+    }
+
+    let mut runs = HashMap::<String, Run>::new();
+    let mut order = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+
+        // NOTE: a partial line can only be the result of a crash while the
+        // line was being written, so it is skipped.
+        let Ok(line) = serde_json::from_str::<Line>(&line) else {
+            continue;
+        };
+
+        let run = runs.entry(line.id.clone()).or_default();
+
+        match line.entry {
+            Entry::Task {
+                key,
+                backend,
+                execution,
+            } => {
+                run.key = Some(Key(key));
+                run.backend = backend;
+                run.execution = execution;
+                order.push(line.id);
+            }
+            Entry::Submitted { job_id } => {
+                run.submissions += 1;
+                run.job_id = job_id;
+            }
+            Entry::Running => {}
+            Entry::Retrying { .. } => {
+                run.execution = 0;
+                run.submissions = 0;
+                run.job_id = None;
+            }
+            Entry::Completed { result } => {
+                run.finished = true;
+                run.result = result.into_result();
+            }
+            Entry::Failed { .. } => run.finished = true,
+        }
+    }
+
+    let mut recovered = HashMap::new();
+
+    // NOTE: the runs are visited in the order they were submitted so that the
+    // most recent run of a task takes precedence.
+    for id in order {
+        // SAFETY: every id in the order was inserted into the runs above.
+        let run = runs.remove(&id).unwrap();
+
+        let Some(key) = run.key else {
+            continue;
+        };
+
+        let state = match (run.finished, run.result, run.job_id) {
+            (true, Some(result), _) => Some(Recovered::Completed(result)),
+            (false, _, Some(id)) if run.submissions > 0 => Some(Recovered::InFlight {
+                backend: run.backend,
+                reattach: Reattach {
+                    execution: run.execution + run.submissions - 1,
+                    id,
+                },
+            }),
+            _ => None,
+        };
+
+        match state {
+            Some(state) => recovered.insert(key, state),
+            None => recovered.remove(&key),
+        };
+    }
+
+    Ok(recovered)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::ExitStatus;
+    use std::process::Output;
+
+    use nonempty::NonEmpty;
+
+    use super::*;
+
+    #[test]
+    fn completed_and_in_flight_tasks_are_recovered() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+
+        let completed = (TaskId::generate(), Key(String::from("completed")));
+        let in_flight = (TaskId::generate(), Key(String::from("in-flight")));
+        let elsewhere = (TaskId::generate(), Key(String::from("elsewhere")));
+        let failed = (TaskId::generate(), Key(String::from("failed")));
+
+        {
+            let journal = Journal::open(&path).unwrap();
+
+            for (id, key) in [&completed, &in_flight, &elsewhere, &failed] {
+                journal.task(*id, key, "generic", 0);
+                journal.state(
+                    *id,
+                    &TaskState::Submitted {
+                        id: Some(format!("job-{key}")),
+                    },
+                );
+            }
+
+            journal.completed(
+                completed.0,
+                &TaskResult::new(NonEmpty::new(Output {
+                    status: ExitStatus::default(),
+                    stdout: b"done".to_vec(),
+                    stderr: Vec::new(),
+                })),
+            );
+            journal.state(
+                failed.0,
+                &TaskState::Failed(crate::service::runner::backend::TaskError::TimedOut),
+            );
+        }
+
+        // NOTE: simulates a crash while a line was being written.
+        let mut file = append(&path).unwrap();
+        file.write_all(br#"{"id":"#).unwrap();
+
+        let journal = Journal::resume(&path).unwrap();
+        assert_eq!(journal.recovered(), 3);
+
+        match journal.resumed(&completed.1, "generic") {
+            Some(Resumed::Completed(result)) => {
+                assert!(result.is_cached());
+                assert_eq!(result.executions().first().stdout, b"done");
+            }
+            _ => panic!("expected the completed task to be recovered"),
+        }
+
+        match journal.resumed(&in_flight.1, "generic") {
+            Some(Resumed::Reattach(reattach)) => {
+                assert_eq!(reattach.execution, 0);
+                assert_eq!(reattach.id, "job-in-flight");
+            }
+            _ => panic!("expected the in-flight task to be recovered"),
+        }
+
+        assert!(journal.resumed(&in_flight.1, "generic").is_none());
+        assert!(journal.resumed(&failed.1, "generic").is_none());
+
+        // NOTE: work is only reattached to by the kind of backend it was
+        // submitted to; otherwise, it is forgotten and the task runs again.
+        assert!(journal.resumed(&elsewhere.1, "tes").is_none());
+        assert!(journal.resumed(&elsewhere.1, "generic").is_none());
+
+        // NOTE: entries written after resuming must survive another crash,
+        // so they cannot share a line with the partial line above.
+        let later = (TaskId::generate(), Key(String::from("later")));
+        journal.task(later.0, &later.1, "generic", 0);
+        journal.state(
+            later.0,
+            &TaskState::Submitted {
+                id: Some(String::from("job-later")),
+            },
+        );
+        drop(journal);
+
+        let journal = Journal::resume(&path).unwrap();
+        assert!(matches!(
+            journal.resumed(&later.1, "generic"),
+            Some(Resumed::Reattach(reattach)) if reattach.id == "job-later"
+        ));
+        assert!(matches!(
+            journal.resumed(&completed.1, "generic"),
+            Some(Resumed::Completed(_))
+        ));
+    }
+}
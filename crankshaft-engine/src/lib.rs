@@ -1,23 +1,45 @@
 //! The engine that powers Crankshaft.
 
+use std::sync::Arc;
 use std::time::Duration;
 
 use crankshaft_config::backend::Config;
-use futures::StreamExt;
-use futures::stream::FuturesUnordered;
+use futures::future::join_all;
 use indexmap::IndexMap;
-use indicatif::ProgressBar;
-use indicatif::ProgressStyle;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
 use tracing::debug;
+use tracing::info;
+use tracing::warn;
 
+pub mod cache;
+pub mod graph;
+pub mod journal;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod progress;
+pub mod routing;
 pub mod service;
+pub mod status;
 pub mod task;
 
 pub use task::Task;
 
+use crate::progress::Progress;
+use crate::progress::Reporter;
 use crate::service::Runner;
 use crate::service::runner::Backend;
+use crate::service::runner::CancellationTokens;
 use crate::service::runner::TaskHandle;
+use crate::service::runner::TaskId;
+use crate::service::runner::backend::Plan;
+use crate::service::runner::event;
+use crate::service::runner::event::TaskEvent;
+use crate::service::runner::queue::Group;
+use crate::status::Filter;
+use crate::status::Status;
 
 /// The top-level result returned within the engine.
 ///
@@ -30,23 +52,329 @@ use crate::service::runner::TaskHandle;
 /// [`anyhow`] equivalent for display).
 pub type Result<T> = eyre::Result<T>;
 
+/// An error related to submitting a [`Task`].
+#[derive(Clone, Debug)]
+pub enum SubmitError {
+    /// No runner is registered with the requested name.
+    UnknownRunner {
+        /// The requested name.
+        name: String,
+
+        /// The names of the runners that are registered.
+        known: Vec<String>,
+    },
+
+    /// A task was submitted without a runner name, but no default runner is
+    /// configured.
+    NoDefaultRunner,
+
+    /// The runner has been shut down and no longer accepts tasks.
+    ShutDown,
+
+    /// A task was routed, but no runner can run it.
+    NoEligibleRunner,
+}
+
+impl std::fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubmitError::UnknownRunner { name, known } if known.is_empty() => {
+                write!(f, "unknown runner `{name}`; no runners are registered")
+            }
+            SubmitError::UnknownRunner { name, known } => write!(
+                f,
+                "unknown runner `{name}`; known runners are {}",
+                known
+                    .iter()
+                    .map(|name| format!("`{name}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            SubmitError::NoDefaultRunner => {
+                write!(f, "no runner was named and no default runner is configured")
+            }
+            SubmitError::ShutDown => write!(f, "the runner has been shut down"),
+            SubmitError::NoEligibleRunner => write!(f, "no runner can run the task"),
+        }
+    }
+}
+
+impl std::error::Error for SubmitError {}
+
+/// How the engine is [shut down](Engine::shutdown).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Shutdown {
+    /// Stops accepting new tasks and lets submitted tasks run to completion.
+    #[default]
+    Drain,
+
+    /// Stops accepting new tasks and cancels every in-flight task.
+    ///
+    /// Tasks are cancelled through their backend: containers are stopped and
+    /// removed, generic jobs are killed with the configured `kill` command,
+    /// and TES tasks are cancelled.
+    Cancel,
+}
+
+/// The amount of time between progress reports while the engine
+/// [runs](Engine::run).
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
 /// Runners stored within the engine.
 type Runners = IndexMap<String, Runner>;
 
 /// A workflow execution engine.
-#[derive(Debug, Default)]
+///
+/// Tasks begin executing as soon as they are [submitted](Engine::submit), so
+/// an engine can be used as a long-lived service. Cloning an engine is cheap,
+/// and all clones share the same runners, so clones can be handed out to
+/// submit tasks from many places concurrently. When no more tasks will be
+/// submitted, call [`Engine::shutdown()`] and then [`Engine::join()`] to wait
+/// for the submitted tasks to complete.
+#[derive(Clone, Debug)]
 pub struct Engine {
     /// The task runner(s).
     runners: Runners,
+
+    /// The channel over which the events for all tasks are sent.
+    events: broadcast::Sender<TaskEvent>,
+
+    /// The statuses of all tasks.
+    statuses: Arc<status::Registry>,
+
+    /// The name of the runner used when submitting without a name.
+    default: Option<String>,
+
+    /// The call cache (if one is configured).
+    cache: Option<Arc<dyn cache::Store>>,
+
+    /// The run journal (if one is configured).
+    journal: Option<Arc<journal::Journal>>,
+
+    /// The configured groups of tasks.
+    groups: IndexMap<String, Group>,
+
+    /// The runner each runner fails over to (by name).
+    fallbacks: IndexMap<String, String>,
+
+    /// The tracker for tasks that may fail over and for graphs.
+    tracker: TaskTracker,
+
+    /// The cancellation tokens of the in-flight tasks that the engine tracks
+    /// across runners (i.e., tasks that may fail over and the nodes of graphs).
+    tokens: CancellationTokens,
+
+    /// The reporter of the engine's progress.
+    reporter: Arc<dyn Reporter>,
+
+    /// The metrics registry (if one is configured).
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<metrics::Metrics>>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self {
+            runners: Default::default(),
+            events: broadcast::Sender::new(event::DEFAULT_CAPACITY),
+            statuses: Default::default(),
+            default: Default::default(),
+            cache: Default::default(),
+            journal: Default::default(),
+            groups: Default::default(),
+            fallbacks: Default::default(),
+            tracker: TaskTracker::new(),
+            tokens: Default::default(),
+            reporter: Arc::new(progress::Silent),
+            #[cfg(feature = "metrics")]
+            metrics: Default::default(),
+        }
+    }
 }
 
 impl Engine {
     /// Adds a [`Backend`] to the engine.
-    pub async fn with(mut self, config: Config) -> Result<Self> {
+    pub async fn with(self, config: Config) -> Result<Self> {
+        let capacity = config.capacity().cloned();
+        let labels = config.labels().map(String::from).collect::<Vec<_>>();
+        let fallback = config.fallback().map(String::from);
+        let rate_limit = config.rate_limit().cloned();
+        let adaptive = config.adaptive().cloned();
         let (name, kind, max_tasks, defaults) = config.into_parts();
-        let runner = Runner::initialize(kind, max_tasks, defaults).await?;
+        let mut runner = Runner::initialize(kind, max_tasks, defaults)
+            .await?
+            .with_labels(labels);
+
+        if let Some(capacity) = capacity {
+            runner = runner.with_capacity(capacity);
+        }
+
+        if let Some(rate_limit) = &rate_limit {
+            runner = runner.with_rate_limit(rate_limit);
+        }
+
+        if let Some(adaptive) = &adaptive {
+            runner = runner.with_adaptive(adaptive);
+        }
+
+        let engine = match fallback {
+            Some(fallback) => self.with_fallback(name.clone(), fallback),
+            None => self,
+        };
+
+        Ok(engine.with_runner(name, runner))
+    }
+
+    /// Adds an already initialized [`Runner`] to the engine under the provided
+    /// name.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous runner with the same name.
+    pub fn with_runner(mut self, name: impl Into<String>, runner: Runner) -> Self {
+        let name = name.into();
+        let mut runner = runner
+            .with_events(self.events.clone())
+            .with_statuses(self.statuses.clone());
+
+        if let Some(cache) = &self.cache {
+            runner = runner.with_cache(cache.clone());
+        }
+
+        if let Some(journal) = &self.journal {
+            runner = runner.with_journal(journal.clone());
+        }
+
+        for (name, group) in &self.groups {
+            runner = runner.with_group(name.clone(), group.clone());
+        }
+
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            runner = runner.with_metrics(metrics, &name);
+        }
+
         self.runners.insert(name, runner);
-        Ok(self)
+        self
+    }
+
+    /// Enables call caching for every runner using the provided store.
+    ///
+    /// Tasks whose [key](cache::Key) matches a previously stored result
+    /// resolve with that result without ever reaching a backend. See the
+    /// [`cache`] module for more details.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous store provided to the engine.
+    pub fn with_cache(mut self, store: Arc<dyn cache::Store>) -> Self {
+        for runner in self.runners.values_mut() {
+            *runner = runner.clone().with_cache(store.clone());
+        }
+
+        self.cache = Some(store);
+        self
+    }
+
+    /// Records every task submitted to the engine in the provided journal.
+    ///
+    /// To resume a previous run, pass a [resumed](journal::Journal::resume)
+    /// journal and submit the same tasks again: tasks that completed resolve
+    /// with their recorded result, and tasks that were still in flight are
+    /// reattached to through their backend. See the [`journal`] module for
+    /// more details.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous journal provided to the
+    /// engine.
+    pub fn with_journal(mut self, journal: Arc<journal::Journal>) -> Self {
+        for runner in self.runners.values_mut() {
+            *runner = runner.clone().with_journal(journal.clone());
+        }
+
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Records the metrics of every runner in the provided registry.
+    ///
+    /// The registry can be rendered in the Prometheus text format or served
+    /// over HTTP. See the [`metrics`] module for the metrics that are
+    /// recorded.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous registry provided to the
+    /// engine.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: Arc<metrics::Metrics>) -> Self {
+        for (name, runner) in self.runners.iter_mut() {
+            *runner = runner.clone().with_metrics(&metrics, name);
+        }
+
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Configures the share of every runner's slots for a group of tasks.
+    ///
+    /// When tasks from multiple groups are waiting to run, each runner shares
+    /// its slots between the groups by weighted fair share, and a group may be
+    /// capped at a maximum number of running tasks (per runner). See the
+    /// [`queue`](service::runner::queue) module for more details.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous configuration provided to the
+    /// engine for the same group.
+    pub fn with_group(mut self, name: impl Into<String>, group: Group) -> Self {
+        let name = name.into();
+
+        for runner in self.runners.values_mut() {
+            *runner = runner.clone().with_group(name.clone(), group.clone());
+        }
+
+        self.groups.insert(name, group);
+        self
+    }
+
+    /// Fails tasks over from one runner to another.
+    ///
+    /// When a task submitted to the runner named `from` fails because the
+    /// backend was unavailable, the task is resubmitted to the runner named
+    /// `to`. See the [`routing`] module for more details.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous fallback provided to the
+    /// engine for the same runner.
+    pub fn with_fallback(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.fallbacks.insert(from.into(), to.into());
+        self
+    }
+
+    /// Reports the engine's progress to the provided reporter while the engine
+    /// [runs](Engine::run).
+    ///
+    /// By default, progress is not reported. See the [`progress`] module for
+    /// the reporters that are available.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous reporter provided to the
+    /// engine.
+    pub fn with_reporter(mut self, reporter: Arc<dyn Reporter>) -> Self {
+        self.reporter = reporter;
+        self
+    }
+
+    /// Subscribes to the lifecycle events of all tasks submitted to the
+    /// engine.
+    ///
+    /// Only events that occur after subscribing are received.
+    pub fn subscribe(&self) -> broadcast::Receiver<TaskEvent> {
+        self.events.subscribe()
     }
 
     /// Gets the names of the runners.
@@ -54,16 +382,41 @@ impl Engine {
         self.runners.keys().map(|key| key.as_ref())
     }
 
-    /// Submits a [`Task`] to be executed.
+    /// Sets the runner used by [`Engine::submit_default()`].
+    ///
+    /// # Notes
     ///
-    /// A [`Handle`] is returned, which contains a channel that can be awaited
-    /// for the result of the job.
-    pub fn submit(&self, name: impl AsRef<str>, task: Task) -> TaskHandle {
+    /// This will silently overwrite any previous default runner. The name is
+    /// not checked until a task is submitted, so it may refer to a runner that
+    /// is added later.
+    pub fn with_default(mut self, name: impl Into<String>) -> Self {
+        self.default = Some(name.into());
+        self
+    }
+
+    /// Gets the name of the default runner (if one is configured).
+    pub fn default_runner(&self) -> Option<&str> {
+        self.default.as_deref()
+    }
+
+    /// Submits a [`Task`] to be executed by the runner with the provided
+    /// name.
+    ///
+    /// A [`TaskHandle`] is returned, which contains a channel that can be
+    /// awaited for the result of the job.
+    pub fn submit(
+        &self,
+        name: impl AsRef<str>,
+        task: Task,
+    ) -> std::result::Result<TaskHandle, SubmitError> {
         let name = name.as_ref();
         let backend = self
             .runners
             .get(name)
-            .unwrap_or_else(|| panic!("backend not found: {name}"));
+            .ok_or_else(|| SubmitError::UnknownRunner {
+                name: name.to_owned(),
+                known: self.runners().map(String::from).collect(),
+            })?;
 
         debug!(
             "submitting job{} to the `{}` backend",
@@ -73,7 +426,131 @@ impl Engine {
             name
         );
 
-        backend.submit(task)
+        match self.fallbacks.contains_key(name) {
+            true => {
+                let handle = backend.submit(task.clone())?;
+                Ok(routing::failover(self, name, task, handle))
+            }
+            false => backend.submit(task),
+        }
+    }
+
+    /// Submits a [`Task`] under an existing id and cancellation token to the
+    /// runner with the provided name, even if the engine has been shut down.
+    ///
+    /// This is used for tasks that belong to work the engine accepted before
+    /// it was shut down (e.g., the nodes of a graph) so that the work is
+    /// allowed to finish.
+    pub(crate) fn resubmit(
+        &self,
+        name: &str,
+        id: TaskId,
+        token: CancellationToken,
+        task: Task,
+    ) -> std::result::Result<TaskHandle, SubmitError> {
+        let runner = self
+            .runners
+            .get(name)
+            .ok_or_else(|| SubmitError::UnknownRunner {
+                name: name.to_owned(),
+                known: self.runners().map(String::from).collect(),
+            })?;
+
+        match self.fallbacks.contains_key(name) {
+            true => {
+                let handle = runner.resubmit(id, token, task.clone());
+                Ok(routing::failover(self, name, task, handle))
+            }
+            false => Ok(runner.resubmit(id, token, task)),
+        }
+    }
+
+    /// Renders what the runner with the provided name would submit for a
+    /// [`Task`] without running anything (i.e., a dry run).
+    ///
+    /// Depending on the backend, the plan contains the resolved submit command
+    /// (generic backends), the container configuration (Docker), or the task
+    /// JSON (TES) for the task. See [`Runner::plan()`] for more details.
+    pub fn plan(&self, name: impl AsRef<str>, task: Task) -> Result<Plan> {
+        let name = name.as_ref();
+        let runner = self
+            .runners
+            .get(name)
+            .ok_or_else(|| SubmitError::UnknownRunner {
+                name: name.to_owned(),
+                known: self.runners().map(String::from).collect(),
+            })?;
+
+        runner.plan(task)
+    }
+
+    /// Submits a [`Task`] to the least loaded runner that can run it.
+    ///
+    /// See the [`routing`] module for how runners are picked. If no runner can
+    /// run the task, a [`SubmitError::NoEligibleRunner`] error is returned.
+    pub fn submit_routed(&self, task: Task) -> std::result::Result<TaskHandle, SubmitError> {
+        let name =
+            routing::pick(self.runners.iter(), &task).ok_or(SubmitError::NoEligibleRunner)?;
+        self.submit(name, task)
+    }
+
+    /// Submits a [`Task`] to be executed by the [default
+    /// runner](Engine::with_default).
+    pub fn submit_default(&self, task: Task) -> std::result::Result<TaskHandle, SubmitError> {
+        let name = self
+            .default
+            .as_deref()
+            .ok_or(SubmitError::NoDefaultRunner)?;
+        self.submit(name, task)
+    }
+
+    /// Submits a [`Graph`](graph::Graph) of dependent tasks to be executed.
+    ///
+    /// Each task is submitted to its runner once all of its upstream tasks
+    /// have succeeded. Every runner named within the graph must exist, and the
+    /// graph must not contain a cycle; otherwise, an error is returned and
+    /// nothing is submitted.
+    ///
+    /// A graph that is submitted before the engine is shut down keeps
+    /// submitting its tasks while the engine drains, and
+    /// [`Engine::join()`] waits for it to finish. Submitting a graph after
+    /// shutdown returns a [`SubmitError::ShutDown`] error.
+    pub fn submit_graph(&self, graph: graph::Graph) -> graph::Result<graph::GraphHandle> {
+        graph::submit(self.clone(), graph)
+    }
+
+    /// Cancels a submitted task.
+    ///
+    /// The task resolves with a
+    /// [`TaskError::Cancelled`](crate::service::runner::backend::TaskError::Cancelled)
+    /// error. Returns `false` if no runner knows of an in-flight task with the
+    /// provided id.
+    pub fn cancel(&self, id: TaskId) -> bool {
+        // NOTE: a task that may fail over (or a node of a graph) is cancelled
+        // through its own token, as it may be between runners or not yet
+        // submitted (and thus unknown to all of them).
+        if let Some(token) = self.tokens.lock().unwrap().get(&id) {
+            token.cancel();
+            return true;
+        }
+
+        self.runners.values().any(|runner| runner.cancel(id))
+    }
+
+    /// Gets the status of a submitted task.
+    ///
+    /// Returns `None` if no task with the provided id was submitted to the
+    /// engine. See the [`status`] module for more details.
+    pub fn status(&self, id: TaskId) -> Option<Status> {
+        self.statuses.get(id)
+    }
+
+    /// Lists the statuses of the submitted tasks that match the provided
+    /// filter (in the order they were submitted).
+    ///
+    /// Pass [`Filter::default()`] to list every task.
+    pub fn list(&self, filter: &Filter) -> Vec<Status> {
+        self.statuses.list(filter)
     }
 
     /// Starts an instrumentation loop.
@@ -93,32 +570,179 @@ impl Engine {
         });
     }
 
-    /// Runs all of the tasks scheduled in the engine.
-    pub async fn run(self) {
-        let mut futures = FuturesUnordered::new();
+    /// Sets the number of tasks the runner with the provided name runs at
+    /// once (see [`Runner::resize()`]).
+    ///
+    /// Returns `false` if no runner has the provided name.
+    pub fn resize(&self, name: impl AsRef<str>, max_tasks: usize) -> bool {
+        match self.runners.get(name.as_ref()) {
+            Some(runner) => {
+                runner.resize(max_tasks);
+                true
+            }
+            None => false,
+        }
+    }
 
-        for (_, runner) in self.runners {
-            futures.extend(runner.tasks());
+    /// Stops the engine from accepting new tasks.
+    ///
+    /// Depending on the mode, tasks that have already been submitted either
+    /// continue to execute or are cancelled (see [`Shutdown`]). Either way,
+    /// [`Engine::join()`] waits for them to resolve. Submitting after shutdown
+    /// returns a [`SubmitError::ShutDown`] error.
+    pub fn shutdown(&self, mode: Shutdown) {
+        for runner in self.runners.values() {
+            runner.shutdown();
         }
 
-        let task_completion_bar = ProgressBar::new(futures.len() as u64);
-        task_completion_bar.set_style(
-            ProgressStyle::with_template(
-                "{spinner:.cyan/blue} [{elapsed_precise}] [{wide_bar:.cyan/blue}] \
-                 {pos:>7}/{len:7} {msg}",
-            )
-            .unwrap()
-            .progress_chars("#>-"),
-        );
+        self.tracker.close();
 
-        let mut count = 1;
-        task_completion_bar.inc(0);
-        task_completion_bar.enable_steady_tick(Duration::from_millis(100));
+        if mode == Shutdown::Cancel {
+            // NOTE: tasks between runners (and nodes of graphs that are not
+            // yet submitted) are not known to any runner, so they are
+            // cancelled through their own tokens.
+            for token in self.tokens.lock().unwrap().values() {
+                token.cancel();
+            }
 
-        while (futures.next().await).is_some() {
-            task_completion_bar.set_message(format!("task #{}", count));
-            task_completion_bar.inc(1);
-            count += 1;
+            let cancelled = self
+                .runners
+                .values()
+                .map(|runner| runner.cancel_all())
+                .sum::<usize>();
+            debug!("cancelled {cancelled} in-flight task(s)");
         }
     }
+
+    /// Shuts the engine down when the process receives `SIGINT` (e.g.,
+    /// `Ctrl-C`) or, on Unix, `SIGTERM`.
+    ///
+    /// The engine is shut down in the provided mode on the first signal. Any
+    /// further signal cancels the tasks that are still in flight, so a second
+    /// `Ctrl-C` always cleans up. The signals are handled until the returned
+    /// handle is aborted, so this must be called from within a runtime.
+    pub fn shutdown_on_signal(&self, mode: Shutdown) -> JoinHandle<()> {
+        let engine = self.clone();
+
+        tokio::spawn(async move {
+            #[cfg(unix)]
+            let mut terminate =
+                match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                    Ok(terminate) => Some(terminate),
+                    Err(err) => {
+                        warn!("unable to listen for `SIGTERM`: {err}");
+                        None
+                    }
+                };
+
+            let mut mode = mode;
+
+            loop {
+                #[cfg(unix)]
+                let received = async {
+                    match terminate.as_mut() {
+                        Some(terminate) => tokio::select! {
+                            result = tokio::signal::ctrl_c() => result.is_ok(),
+                            _ = terminate.recv() => true,
+                        },
+                        None => tokio::signal::ctrl_c().await.is_ok(),
+                    }
+                }
+                .await;
+
+                #[cfg(not(unix))]
+                let received = tokio::signal::ctrl_c().await.is_ok();
+
+                if !received {
+                    warn!("unable to listen for `SIGINT`; signals will not shut down the engine");
+                    return;
+                }
+
+                info!("received a signal; shutting down ({mode:?})");
+                engine.shutdown(mode);
+                mode = Shutdown::Cancel;
+            }
+        })
+    }
+
+    /// Waits for the engine to be [shut down](Engine::shutdown) and for all
+    /// submitted tasks to complete.
+    pub async fn join(&self) {
+        join_all(self.runners.values().map(|runner| runner.join())).await;
+        self.tracker.wait().await;
+    }
+
+    /// Shuts down the engine and waits for all of the submitted tasks to
+    /// complete while reporting progress to the engine's
+    /// [reporter](Engine::with_reporter).
+    pub async fn run(self) {
+        self.shutdown(Shutdown::Drain);
+
+        let join = self.join();
+        tokio::pin!(join);
+
+        loop {
+            tokio::select! {
+                _ = &mut join => break,
+                _ = tokio::time::sleep(REPORT_INTERVAL) => self.reporter.report(&self.progress()),
+            }
+        }
+
+        self.reporter.finish(&self.progress());
+    }
+
+    /// Gets the progress of the tasks submitted to each runner.
+    pub fn progress(&self) -> Vec<Progress> {
+        self.runners
+            .iter()
+            .map(|(name, runner)| {
+                // NOTE: the number of pending tasks is read first so that a
+                // task submitted between the two reads can't make the number
+                // of completed tasks underflow.
+                let pending = runner.pending();
+                let submitted = runner.submitted();
+
+                Progress {
+                    runner: name.clone(),
+                    submitted,
+                    queued: runner.queued(),
+                    running: runner.running(),
+                    completed: submitted.saturating_sub(pending),
+                    failed: runner.failed(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::Execution;
+
+    #[tokio::test]
+    async fn unknown_runners_are_submission_errors() {
+        let task = Task::builder()
+            .extend_executions([Execution::builder()
+                .image("ubuntu")
+                .args(["echo", "hello"])
+                .try_build()
+                .unwrap()])
+            .try_build()
+            .unwrap();
+
+        let engine = Engine::default().with_default("docker");
+
+        let err = engine.submit("lsf", task.clone()).unwrap_err();
+        assert!(matches!(err, SubmitError::UnknownRunner { ref name, .. } if name == "lsf"));
+        assert_eq!(
+            err.to_string(),
+            "unknown runner `lsf`; no runners are registered"
+        );
+
+        assert!(matches!(
+            engine.submit_default(task),
+            Err(SubmitError::UnknownRunner { .. })
+        ));
+    }
 }
@@ -0,0 +1,493 @@
+//! Prometheus metrics.
+//!
+//! When a [`Metrics`] registry is provided to the engine (see
+//! [`Engine::with_metrics()`]), each runner records the following metrics
+//! (labeled by the name of the runner):
+//!
+//! * `crankshaft_tasks_submitted_total`, `crankshaft_tasks_completed_total`,
+//!   and `crankshaft_tasks_failed_total`: the number of tasks submitted, that
+//!   completed successfully, and that failed.
+//! * `crankshaft_tasks_queued` and `crankshaft_tasks_running`: the number of
+//!   tasks waiting for a slot and holding one.
+//! * `crankshaft_slot_utilization`: the fraction of the runner's slots that are
+//!   held.
+//! * `crankshaft_task_retries_total`: the number of retried attempts.
+//! * `crankshaft_backend_errors_total`: the number of attempts that failed
+//!   because the backend was unavailable or rejected the submission (further
+//!   labeled by the `backend` and the `kind` of error).
+//! * `crankshaft_submission_latency_seconds`: a histogram of the time from a
+//!   task being queued (or retried) to the backend reporting its submission.
+//! * `crankshaft_execution_duration_seconds`: a histogram of the time from a
+//!   task starting to run to it finishing (per attempt).
+//!
+//! The metrics can be [rendered](Metrics::render) in the Prometheus text
+//! format or [served](Metrics::serve) over HTTP.
+//!
+//! [`Engine::with_metrics()`]: crate::Engine::with_metrics
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use indexmap::IndexMap;
+use tokio::io::AsyncBufReadExt as _;
+use tokio::io::AsyncReadExt as _;
+use tokio::io::AsyncWriteExt as _;
+use tokio::io::BufReader;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::debug;
+use tracing::warn;
+
+use crate::service::runner::backend::TaskError;
+use crate::service::runner::event::TaskState;
+use crate::service::runner::queue::Queue;
+
+/// The upper bounds (in seconds) of the histogram buckets.
+const BUCKETS: &[f64] = &[
+    0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0, 3600.0, 14400.0,
+];
+
+/// The content type of the Prometheus text format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// The longest a client may take to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The most bytes of a request (i.e., its request line and headers) that are
+/// read.
+const MAX_REQUEST_SIZE: u64 = 16 * 1024;
+
+/// How long to wait before accepting connections again after failing to
+/// accept one (e.g., because the process has run out of file descriptors).
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// A histogram of durations.
+#[derive(Debug)]
+struct Histogram {
+    /// The (non-cumulative) count of observations within each bucket.
+    counts: Vec<AtomicU64>,
+
+    /// The total number of observations.
+    count: AtomicU64,
+
+    /// The sum of the observations.
+    sum: Mutex<f64>,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: BUCKETS.iter().map(|_| AtomicU64::new(0)).collect(),
+            count: Default::default(),
+            sum: Default::default(),
+        }
+    }
+}
+
+impl Histogram {
+    /// Records an observation.
+    fn observe(&self, seconds: f64) {
+        if let Some(bucket) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+
+        self.count.fetch_add(1, Ordering::Relaxed);
+        *self.sum.lock().unwrap() += seconds;
+    }
+
+    /// Renders the histogram.
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+
+        for (bound, count) in BUCKETS.iter().zip(&self.counts) {
+            cumulative += count.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {cumulative}");
+        }
+
+        let count = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {count}");
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {}", self.sum.lock().unwrap());
+        let _ = writeln!(out, "{name}_count{{{labels}}} {count}");
+    }
+}
+
+/// The metrics of a single runner.
+#[derive(Debug)]
+pub(crate) struct Runner {
+    /// The default name of the runner's backend.
+    backend: &'static str,
+
+    /// The runner's queue.
+    queue: Arc<Queue>,
+
+    /// The total number of tasks submitted.
+    submitted: AtomicU64,
+
+    /// The total number of tasks that completed successfully.
+    completed: AtomicU64,
+
+    /// The total number of tasks that failed.
+    failed: AtomicU64,
+
+    /// The total number of retried attempts.
+    retries: AtomicU64,
+
+    /// The total number of backend errors (by kind).
+    errors: Mutex<BTreeMap<&'static str, u64>>,
+
+    /// The time from tasks being queued to being submitted to the backend.
+    latency: Histogram,
+
+    /// The time from tasks starting to run to finishing.
+    duration: Histogram,
+}
+
+impl Runner {
+    /// Records a backend error (if the error is one).
+    fn error(&self, error: &TaskError) {
+        let kind = match error {
+            TaskError::BackendUnavailable(_) => "unavailable",
+            TaskError::SubmissionFailed(_) => "submission",
+            _ => return,
+        };
+
+        *self.errors.lock().unwrap().entry(kind).or_default() += 1;
+    }
+}
+
+/// A registry of metrics.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// The metrics of each runner (by name).
+    runners: Mutex<IndexMap<String, Arc<Runner>>>,
+}
+
+impl Metrics {
+    /// Creates a new, empty [`Metrics`] registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a runner.
+    ///
+    /// Registering a runner with the same name again replaces its metrics.
+    pub(crate) fn register(
+        &self,
+        name: &str,
+        backend: &'static str,
+        queue: Arc<Queue>,
+    ) -> Arc<Runner> {
+        let runner = Arc::new(Runner {
+            backend,
+            queue,
+            submitted: Default::default(),
+            completed: Default::default(),
+            failed: Default::default(),
+            retries: Default::default(),
+            errors: Default::default(),
+            latency: Default::default(),
+            duration: Default::default(),
+        });
+
+        self.runners
+            .lock()
+            .unwrap()
+            .insert(name.to_owned(), runner.clone());
+        runner
+    }
+
+    /// Renders the metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let runners = self.runners.lock().unwrap();
+        let mut out = String::new();
+
+        let mut family = |name: &str, kind: &str, help: &str, value: &dyn Fn(&Runner) -> f64| {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+
+            for (runner, metrics) in runners.iter() {
+                let _ = writeln!(
+                    out,
+                    "{name}{{runner=\"{}\"}} {}",
+                    escape(runner),
+                    value(metrics)
+                );
+            }
+        };
+
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed) as f64;
+        family(
+            "crankshaft_tasks_submitted_total",
+            "counter",
+            "The total number of tasks submitted.",
+            &|runner| load(&runner.submitted),
+        );
+        family(
+            "crankshaft_tasks_completed_total",
+            "counter",
+            "The total number of tasks that completed successfully.",
+            &|runner| load(&runner.completed),
+        );
+        family(
+            "crankshaft_tasks_failed_total",
+            "counter",
+            "The total number of tasks that failed.",
+            &|runner| load(&runner.failed),
+        );
+        family(
+            "crankshaft_task_retries_total",
+            "counter",
+            "The total number of retried attempts.",
+            &|runner| load(&runner.retries),
+        );
+        family(
+            "crankshaft_tasks_queued",
+            "gauge",
+            "The number of tasks waiting for a slot.",
+            &|runner| runner.queue.waiting() as f64,
+        );
+        family(
+            "crankshaft_tasks_running",
+            "gauge",
+            "The number of tasks holding a slot.",
+            &|runner| runner.queue.running() as f64,
+        );
+        family(
+            "crankshaft_slot_utilization",
+            "gauge",
+            "The fraction of slots that are held.",
+            &|runner| runner.queue.running() as f64 / runner.queue.max_tasks().max(1) as f64,
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP crankshaft_backend_errors_total The total number of attempts that failed \
+             because of the backend."
+        );
+        let _ = writeln!(out, "# TYPE crankshaft_backend_errors_total counter");
+        for (runner, metrics) in runners.iter() {
+            for (kind, count) in metrics.errors.lock().unwrap().iter() {
+                let _ = writeln!(
+                    out,
+                    "crankshaft_backend_errors_total{{runner=\"{}\",backend=\"{}\",kind=\"{kind}\"\
+                     }} {count}",
+                    escape(runner),
+                    metrics.backend
+                );
+            }
+        }
+
+        for (name, help, histogram) in [
+            (
+                "crankshaft_submission_latency_seconds",
+                "The time from tasks being queued to being submitted to the backend.",
+                (|runner: &Runner| &runner.latency) as fn(&Runner) -> &Histogram,
+            ),
+            (
+                "crankshaft_execution_duration_seconds",
+                "The time from tasks starting to run to finishing.",
+                |runner: &Runner| &runner.duration,
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} histogram");
+
+            for (runner, metrics) in runners.iter() {
+                let labels = format!("runner=\"{}\"", escape(runner));
+                histogram(metrics).render(&mut out, name, &labels);
+            }
+        }
+
+        out
+    }
+
+    /// Serves the metrics over HTTP at `/metrics` on the provided address.
+    ///
+    /// The server runs on the current [`tokio`] runtime until the returned
+    /// handle is aborted.
+    pub async fn serve(self: Arc<Self>, addr: SocketAddr) -> std::io::Result<JoinHandle<()>> {
+        let listener = TcpListener::bind(addr).await?;
+
+        Ok(tokio::spawn(async move {
+            loop {
+                let (stream, peer) = match listener.accept().await {
+                    Ok(connection) => connection,
+                    Err(err) => {
+                        // NOTE: errors accepting connections tend to persist
+                        // for a while, so retrying immediately would spin.
+                        warn!("failed to accept a metrics connection: {err}");
+                        tokio::time::sleep(ACCEPT_BACKOFF).await;
+                        continue;
+                    }
+                };
+
+                let metrics = self.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = respond(&metrics, stream).await {
+                        debug!("failed to serve metrics to {peer}: {err}");
+                    }
+                });
+            }
+        }))
+    }
+}
+
+/// Responds to a single HTTP request for the metrics.
+async fn respond(metrics: &Metrics, mut stream: TcpStream) -> std::io::Result<()> {
+    let request = tokio::time::timeout(REQUEST_TIMEOUT, request(&mut stream))
+        .await
+        .map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out reading request")
+        })??;
+
+    let (status, body) = match request.split_whitespace().nth(1) {
+        Some("/metrics") => ("200 OK", metrics.render()),
+        _ => ("404 Not Found", String::new()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\ncontent-type: {CONTENT_TYPE}\r\ncontent-length: {}\r\nconnection: \
+         close\r\n\r\n{body}",
+        body.len()
+    );
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Reads the request line of an HTTP request.
+///
+/// At most [`MAX_REQUEST_SIZE`] bytes are read.
+async fn request(stream: &mut TcpStream) -> std::io::Result<String> {
+    let mut stream = BufReader::new(stream.take(MAX_REQUEST_SIZE));
+
+    let mut request = String::new();
+    stream.read_line(&mut request).await?;
+
+    // NOTE: the headers are read (and ignored) so that the client isn't
+    // reset before it has finished sending its request.
+    let mut header = String::new();
+    while stream.read_line(&mut header).await? > 2 {
+        header.clear();
+    }
+
+    Ok(request)
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Records the metrics for a single task as it transitions between states.
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    /// The metrics of the task's runner.
+    runner: Arc<Runner>,
+
+    /// When the task was last queued or retried (until it is submitted).
+    waiting: Mutex<Option<Instant>>,
+
+    /// When the current attempt started running.
+    started: Mutex<Option<Instant>>,
+}
+
+impl Recorder {
+    /// Creates a new [`Recorder`] for a task submitted to a runner.
+    pub(crate) fn new(runner: Arc<Runner>) -> Self {
+        Self {
+            runner,
+            waiting: Default::default(),
+            started: Default::default(),
+        }
+    }
+
+    /// Records the task transitioning to a new state.
+    pub(crate) fn record(&self, state: &TaskState) {
+        let runner = &self.runner;
+
+        match state {
+            TaskState::Queued => {
+                runner.submitted.fetch_add(1, Ordering::Relaxed);
+                *self.waiting.lock().unwrap() = Some(Instant::now());
+            }
+            TaskState::Submitted { .. } => {
+                if let Some(waiting) = self.waiting.lock().unwrap().take() {
+                    runner.latency.observe(waiting.elapsed().as_secs_f64());
+                }
+            }
+            TaskState::Running => {
+                self.started
+                    .lock()
+                    .unwrap()
+                    .get_or_insert_with(Instant::now);
+            }
+            TaskState::Retrying { error, .. } => {
+                runner.retries.fetch_add(1, Ordering::Relaxed);
+                runner.error(error);
+                self.finished();
+                *self.waiting.lock().unwrap() = Some(Instant::now());
+            }
+            TaskState::Completed => {
+                runner.completed.fetch_add(1, Ordering::Relaxed);
+                self.finished();
+            }
+            TaskState::Failed(error) => {
+                runner.failed.fetch_add(1, Ordering::Relaxed);
+                runner.error(error);
+                self.finished();
+            }
+        }
+    }
+
+    /// Records the end of an attempt that started running.
+    fn finished(&self) {
+        if let Some(started) = self.started.lock().unwrap().take() {
+            self.runner
+                .duration
+                .observe(started.elapsed().as_secs_f64());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_render_in_the_text_format() {
+        let metrics = Metrics::new();
+        let runner = metrics.register("docker", "docker", Queue::new(4));
+
+        let recorder = Recorder::new(runner);
+        recorder.record(&TaskState::Queued);
+        recorder.record(&TaskState::Submitted { id: None });
+        recorder.record(&TaskState::Running);
+        recorder.record(&TaskState::Failed(TaskError::BackendUnavailable(
+            String::from("down"),
+        )));
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("crankshaft_tasks_submitted_total{runner=\"docker\"} 1\n"));
+        assert!(rendered.contains("crankshaft_tasks_failed_total{runner=\"docker\"} 1\n"));
+        assert!(rendered.contains("crankshaft_slot_utilization{runner=\"docker\"} 0\n"));
+        assert!(rendered.contains(
+            "crankshaft_backend_errors_total{runner=\"docker\",backend=\"docker\",kind=\"\
+             unavailable\"} 1\n"
+        ));
+        assert!(
+            rendered.contains("crankshaft_execution_duration_seconds_count{runner=\"docker\"} 1\n")
+        );
+    }
+}
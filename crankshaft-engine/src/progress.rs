@@ -0,0 +1,90 @@
+//! Progress reporting.
+//!
+//! While the engine [runs](crate::Engine::run) to completion, it periodically
+//! hands a [`Reporter`] the [`Progress`] of each of its runners. By default,
+//! nothing is reported (see [`Silent`]). This crate also ships a reporter that
+//! draws a progress bar per runner to the terminal ([`bars::Bars`]) and one
+//! that writes the progress as log lines ([`log::Log`]).
+
+use std::fmt::Debug;
+
+pub mod bars;
+pub mod log;
+
+/// The progress of the tasks submitted to a runner.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// The name of the runner.
+    pub(crate) runner: String,
+
+    /// The total number of tasks submitted.
+    pub(crate) submitted: usize,
+
+    /// The number of tasks waiting for a slot to run in.
+    pub(crate) queued: usize,
+
+    /// The number of tasks running.
+    pub(crate) running: usize,
+
+    /// The total number of tasks that have completed (successfully or not).
+    pub(crate) completed: usize,
+
+    /// The total number of tasks that failed.
+    pub(crate) failed: usize,
+}
+
+impl Progress {
+    /// Gets the name of the runner.
+    pub fn runner(&self) -> &str {
+        &self.runner
+    }
+
+    /// Gets the total number of tasks submitted.
+    pub fn submitted(&self) -> usize {
+        self.submitted
+    }
+
+    /// Gets the number of tasks waiting for a slot to run in.
+    pub fn queued(&self) -> usize {
+        self.queued
+    }
+
+    /// Gets the number of tasks running.
+    pub fn running(&self) -> usize {
+        self.running
+    }
+
+    /// Gets the total number of tasks that have completed (successfully or
+    /// not).
+    pub fn completed(&self) -> usize {
+        self.completed
+    }
+
+    /// Gets the total number of tasks that failed.
+    pub fn failed(&self) -> usize {
+        self.failed
+    }
+}
+
+/// A reporter of the progress of an engine.
+pub trait Reporter: Debug + Send + Sync + 'static {
+    /// Reports the progress of each runner.
+    ///
+    /// This is called periodically while the engine runs, so implementations
+    /// should be cheap (and rate limit themselves if needed).
+    fn report(&self, progress: &[Progress]);
+
+    /// Reports the final progress of each runner once every task has
+    /// completed.
+    fn finish(&self, progress: &[Progress]) {
+        self.report(progress);
+    }
+}
+
+/// A reporter that reports nothing.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Silent;
+
+impl Reporter for Silent {
+    fn report(&self, _: &[Progress]) {}
+}
@@ -0,0 +1,78 @@
+//! A reporter that draws progress bars to the terminal.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use indexmap::IndexMap;
+use indicatif::MultiProgress;
+use indicatif::ProgressBar;
+use indicatif::ProgressStyle;
+
+use crate::progress::Progress;
+use crate::progress::Reporter;
+
+/// The template for each progress bar.
+const TEMPLATE: &str = "{spinner:.cyan/blue} [{elapsed_precise}] {prefix:>12} \
+                        [{wide_bar:.cyan/blue}] {pos:>7}/{len:7} {msg}";
+
+/// A reporter that draws a progress bar per runner to the terminal.
+///
+/// Each bar shows the number of tasks that have completed out of the number
+/// submitted to the runner, along with the number of tasks that are running,
+/// queued, and that have failed.
+#[derive(Debug, Default)]
+pub struct Bars {
+    /// The bars being drawn.
+    bars: MultiProgress,
+
+    /// The bar for each runner.
+    runners: Mutex<IndexMap<String, ProgressBar>>,
+}
+
+impl Bars {
+    /// Creates a new [`Bars`] reporter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a bar for a runner.
+    fn bar(&self, runner: &str) -> ProgressBar {
+        let bar = self.bars.add(ProgressBar::new(0));
+        // SAFETY: the template is known to be valid.
+        bar.set_style(
+            ProgressStyle::with_template(TEMPLATE)
+                .unwrap()
+                .progress_chars("#>-"),
+        );
+        bar.set_prefix(runner.to_owned());
+        bar.enable_steady_tick(Duration::from_millis(100));
+        bar
+    }
+}
+
+impl Reporter for Bars {
+    fn report(&self, progress: &[Progress]) {
+        let mut runners = self.runners.lock().unwrap();
+
+        for progress in progress {
+            let bar = runners
+                .entry(progress.runner.clone())
+                .or_insert_with(|| self.bar(&progress.runner));
+
+            bar.set_length(progress.submitted as u64);
+            bar.set_position(progress.completed as u64);
+            bar.set_message(format!(
+                "running: {}, queued: {}, failed: {}",
+                progress.running, progress.queued, progress.failed
+            ));
+        }
+    }
+
+    fn finish(&self, progress: &[Progress]) {
+        self.report(progress);
+
+        for bar in self.runners.lock().unwrap().values() {
+            bar.finish();
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! A reporter that writes progress as log lines.
+
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use tracing::info;
+
+use crate::progress::Progress;
+use crate::progress::Reporter;
+
+/// The default minimum amount of time between log lines.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A reporter that writes the progress of each runner as an `INFO` log line.
+///
+/// Lines are written at most once per interval, and only when the progress has
+/// changed since the lines were last written.
+#[derive(Debug)]
+pub struct Log {
+    /// The minimum amount of time between log lines.
+    interval: Duration,
+
+    /// When the last line was written and the progress that was written.
+    last: Mutex<Option<(Instant, Vec<Progress>)>>,
+}
+
+impl Log {
+    /// Creates a new [`Log`] reporter that writes a line at most once per
+    /// interval.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: Default::default(),
+        }
+    }
+
+    /// Writes a line for each runner.
+    fn write(progress: &[Progress]) {
+        for progress in progress {
+            info!(
+                "runner `{}`: {}/{} tasks completed (running: {}, queued: {}, failed: {})",
+                progress.runner,
+                progress.completed,
+                progress.submitted,
+                progress.running,
+                progress.queued,
+                progress.failed
+            );
+        }
+    }
+}
+
+impl Default for Log {
+    fn default() -> Self {
+        Self::new(DEFAULT_INTERVAL)
+    }
+}
+
+impl Reporter for Log {
+    fn report(&self, progress: &[Progress]) {
+        let mut last = self.last.lock().unwrap();
+
+        if let Some((at, previous)) = last.as_ref() {
+            if at.elapsed() < self.interval || previous == progress {
+                return;
+            }
+        }
+
+        Self::write(progress);
+        *last = Some((Instant::now(), progress.to_vec()));
+    }
+
+    fn finish(&self, progress: &[Progress]) {
+        Self::write(progress);
+    }
+}
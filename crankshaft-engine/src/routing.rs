@@ -0,0 +1,225 @@
+//! Routing tasks between runners.
+//!
+//! A task can be [routed](crate::Engine::submit_routed) rather than submitted
+//! to a named runner. The task is then sent to the least loaded runner that
+//! [accepts it](Runner::accepts) (based on the labels and container it
+//! requires and on its resources), preferring runners in the order they were
+//! added when loads are equal.
+//!
+//! Independently of routing, a runner can be configured with a
+//! [fallback](crate::Engine::with_fallback). When a task fails on a runner
+//! because the backend was unavailable (even after any retries), the task is
+//! resubmitted to the fallback runner (e.g., to spill from a local Docker
+//! daemon to a TES endpoint). Fallbacks chain, but a task never visits the
+//! same runner twice. A task that was submitted before the engine was shut
+//! down still fails over (as it was already accepted), and it can be
+//! [cancelled](crate::Engine::cancel) at any point (even between runners).
+
+use tokio::sync::oneshot;
+use tracing::warn;
+
+use crate::Engine;
+use crate::Task;
+use crate::service::runner::Runner;
+use crate::service::runner::TaskHandle;
+use crate::service::runner::backend::TaskError;
+use crate::task::retry::Class;
+
+/// Picks the runner a task should be routed to (if any accept it).
+pub(crate) fn pick<'a>(
+    runners: impl Iterator<Item = (&'a String, &'a Runner)>,
+    task: &Task,
+) -> Option<&'a str> {
+    runners
+        .filter(|(_, runner)| runner.accepts(task))
+        .map(|(name, runner)| (name, runner.load()))
+        // NOTE: `min_by` keeps the first of equal elements, so ties go to the
+        // runner that was added first.
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(name, _)| name.as_str())
+}
+
+/// Fails a submitted task over to the fallback runners of the runner it was
+/// submitted to (if any are configured).
+///
+/// The returned handle resolves with the result from the last runner the task
+/// was submitted to. The task keeps its id (and cancellation token) across
+/// runners, and its token is registered with the engine until it resolves.
+pub(crate) fn failover(engine: &Engine, name: &str, task: Task, handle: TaskHandle) -> TaskHandle {
+    if !engine.fallbacks.contains_key(name) {
+        return handle;
+    }
+
+    let id = handle.id();
+    let token = handle.token().clone();
+    let (tx, rx) = oneshot::channel();
+
+    let engine = engine.clone();
+    let cancel = token.clone();
+    let mut visited = vec![name.to_owned()];
+
+    engine.tokens.lock().unwrap().insert(id, token.clone());
+
+    engine.tracker.clone().spawn(async move {
+        let mut handle = handle;
+
+        let result = loop {
+            let result = (&mut handle.callback)
+                .await
+                .unwrap_or(Err(TaskError::Cancelled));
+
+            let error = match result {
+                Err(error) if Class::of(&error) == Some(Class::Transient) => error,
+                result => break result,
+            };
+
+            // SAFETY: at least one runner is always visited.
+            let current = visited.last().unwrap();
+            let next = match engine.fallbacks.get(current) {
+                Some(next) if !visited.contains(next) => next.clone(),
+                _ => break Err(error),
+            };
+
+            let Some(runner) = engine.runners.get(&next) else {
+                warn!("task {id} cannot fail over to unknown runner `{next}`");
+                break Err(error);
+            };
+
+            warn!("task {id} failed on runner `{current}` ({error}); failing over to `{next}`");
+
+            // NOTE: the task was accepted before any shutdown, so it is
+            // resubmitted even if the engine has since been shut down.
+            handle = runner.resubmit(id, cancel.clone(), task.clone());
+            visited.push(next);
+        };
+
+        engine.tokens.lock().unwrap().remove(&id);
+
+        // NOTE: see the note in the runner about ignoring send errors.
+        let _ = tx.send(result);
+    });
+
+    TaskHandle::new(id, rx, token)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::ExitStatus;
+    use std::process::Output;
+    use std::sync::Arc;
+
+    use futures::FutureExt as _;
+    use futures::future::BoxFuture;
+    use nonempty::NonEmpty;
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+    use crate::SubmitError;
+    use crate::service::runner::Backend;
+    use crate::service::runner::backend::TaskResult;
+    use crate::service::runner::event::Emitter;
+    use crate::task::Execution;
+
+    /// A backend that either always succeeds or is always unavailable.
+    #[derive(Debug)]
+    struct Fixed(bool);
+
+    impl Backend for Fixed {
+        fn default_name(&self) -> &'static str {
+            "fixed"
+        }
+
+        fn run(
+            &self,
+            _: Task,
+            _: Emitter,
+            _: CancellationToken,
+        ) -> BoxFuture<'static, Result<TaskResult, TaskError>> {
+            let available = self.0;
+
+            async move {
+                match available {
+                    true => Ok(TaskResult::new(NonEmpty::new(Output {
+                        status: ExitStatus::default(),
+                        stdout: Vec::new(),
+                        stderr: Vec::new(),
+                    }))),
+                    false => Err(TaskError::BackendUnavailable(String::from("down"))),
+                }
+            }
+            .boxed()
+        }
+    }
+
+    fn task(labels: &[&str]) -> Task {
+        Task::builder()
+            .extend_executions([Execution::builder()
+                .image("ubuntu")
+                .args(["echo", "hello"])
+                .try_build()
+                .unwrap()])
+            .extend_labels(labels.iter().map(|label| label.to_string()))
+            .try_build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn tasks_are_routed_to_runners_with_their_labels() {
+        let engine = Engine::default()
+            .with_runner("plain", Runner::new(Arc::new(Fixed(true)), 1))
+            .with_runner(
+                "gpu",
+                Runner::new(Arc::new(Fixed(true)), 1).with_labels([String::from("gpu")]),
+            );
+
+        assert_eq!(pick(engine.runners.iter(), &task(&[])), Some("plain"));
+        assert_eq!(pick(engine.runners.iter(), &task(&["gpu"])), Some("gpu"));
+        assert!(matches!(
+            engine.submit_routed(task(&["tpu"])),
+            Err(SubmitError::NoEligibleRunner)
+        ));
+    }
+
+    #[tokio::test]
+    async fn unavailable_runners_fail_over() {
+        let engine = Engine::default()
+            .with_runner("local", Runner::new(Arc::new(Fixed(false)), 1))
+            .with_runner("remote", Runner::new(Arc::new(Fixed(true)), 1))
+            .with_fallback("local", "remote");
+
+        let handle = engine.submit("local", task(&[])).unwrap();
+        assert!(handle.callback.await.unwrap().is_ok());
+
+        let handle = engine.submit("remote", task(&[])).unwrap();
+        assert!(handle.callback.await.unwrap().is_ok());
+
+        let engine = engine.with_fallback("remote", "local");
+        let engine = engine.with_runner("remote", Runner::new(Arc::new(Fixed(false)), 1));
+        let handle = engine.submit("local", task(&[])).unwrap();
+        assert!(matches!(
+            handle.callback.await.unwrap(),
+            Err(TaskError::BackendUnavailable(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn tasks_fail_over_while_the_engine_drains() {
+        let engine = Engine::default()
+            .with_runner("local", Runner::new(Arc::new(Fixed(false)), 1))
+            .with_runner("remote", Runner::new(Arc::new(Fixed(true)), 1))
+            .with_fallback("local", "remote");
+
+        let handles = [
+            engine.submit("local", task(&[])).unwrap(),
+            engine.submit("local", task(&[])).unwrap(),
+        ];
+
+        // NOTE: running the engine shuts it down before the tasks fail on the
+        // first runner.
+        engine.run().await;
+
+        for handle in handles {
+            assert!(handle.callback.await.unwrap().is_ok());
+        }
+    }
+}
@@ -1,51 +1,207 @@
 //! Task runner services.
 
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::SystemTime;
 
+use crankshaft_config::backend::Adaptive;
+use crankshaft_config::backend::Capacity;
 use crankshaft_config::backend::Defaults;
 use crankshaft_config::backend::Kind;
-use futures::future::BoxFuture;
-use futures::future::join_all;
-use futures::stream::FuturesUnordered;
-use tokio::sync::Semaphore;
+use crankshaft_config::backend::RateLimit;
+use tokio::sync::broadcast;
 use tokio::sync::oneshot::Receiver;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::Instrument as _;
+use tracing::debug;
+use tracing::field;
+use tracing::info_span;
 use tracing::trace;
+use tracing::warn;
+use uuid::Uuid;
 
+mod adaptive;
 pub mod backend;
+pub mod event;
+mod limit;
+pub mod queue;
 
 pub use backend::Backend;
 
 use crate::Result;
+use crate::SubmitError;
 use crate::Task;
+use crate::cache;
+use crate::cache::Key;
+use crate::cache::Store;
+use crate::journal::Journal;
+use crate::journal::Resumed;
+#[cfg(feature = "metrics")]
+use crate::metrics;
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+#[cfg(feature = "metrics")]
+use crate::metrics::Recorder;
 use crate::service::name::GeneratorIterator;
 use crate::service::name::UniqueAlphanumeric;
+use crate::service::runner::adaptive::Controller;
+use crate::service::runner::adaptive::Feedback;
+use crate::service::runner::backend::Attempt;
+use crate::service::runner::backend::Plan;
+use crate::service::runner::backend::Reattach;
+use crate::service::runner::backend::TaskError;
 use crate::service::runner::backend::TaskResult;
+use crate::service::runner::backend::apptainer;
 use crate::service::runner::backend::docker;
 use crate::service::runner::backend::generic;
+use crate::service::runner::backend::local;
+use crate::service::runner::backend::mock;
 use crate::service::runner::backend::tes;
+use crate::service::runner::event::Emitter;
+use crate::service::runner::event::TaskEvent;
+use crate::service::runner::event::TaskState;
+use crate::service::runner::limit::Limits;
+use crate::service::runner::queue::Group;
+use crate::service::runner::queue::Queue;
+use crate::service::runner::queue::Usage;
+use crate::status::Filter;
+use crate::status::Registry;
+use crate::status::Status;
+use crate::task::Resources;
 
 /// The size of the name buffer.
 const NAME_BUFFER_LEN: usize = 4096;
 
+/// A unique identifier assigned to a task when it is submitted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TaskId(Uuid);
+
+impl TaskId {
+    /// Generates a new, random [`TaskId`].
+    pub(crate) fn generate() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl std::fmt::Display for TaskId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 /// A submitted task handle.
 #[derive(Debug)]
 pub struct TaskHandle {
+    /// The id assigned to the task.
+    id: TaskId,
+
     /// A callback that is executed when a task is completed.
-    pub callback: Receiver<TaskResult>,
+    pub callback: Receiver<std::result::Result<TaskResult, TaskError>>,
+
+    /// The token used to cancel the task.
+    token: CancellationToken,
+}
+
+impl TaskHandle {
+    /// Creates a new [`TaskHandle`].
+    pub(crate) fn new(
+        id: TaskId,
+        callback: Receiver<std::result::Result<TaskResult, TaskError>>,
+        token: CancellationToken,
+    ) -> Self {
+        Self {
+            id,
+            callback,
+            token,
+        }
+    }
+
+    /// Gets the token used to cancel the task.
+    pub(crate) fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Gets the id assigned to the task.
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    /// Cancels the task.
+    ///
+    /// If the task has not yet started, it will never be sent to the backend.
+    /// Otherwise, the backend will stop any work it has started for the task.
+    /// Either way, the task resolves with [`TaskError::Cancelled`] (unless it
+    /// had already completed).
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
 }
 
+/// Tokens for cancelling the tasks that have been submitted but have not yet
+/// completed.
+pub(crate) type CancellationTokens = Arc<Mutex<HashMap<TaskId, CancellationToken>>>;
+
 /// A generic task runner.
-#[derive(Debug)]
+///
+/// Tasks begin executing as soon as they are submitted (or, if the runner is
+/// already running as many tasks as it can, as soon as a slot frees up and no
+/// waiting task has a higher [priority](Task::priority)). Cloning a runner is
+/// cheap, and all clones share the same backend and set of tasks.
+#[derive(Clone, Debug)]
 pub struct Runner {
     /// The task runner itself.
     backend: Arc<dyn Backend>,
 
-    /// The task lock.
-    lock: Arc<tokio::sync::Semaphore>,
+    /// The queue of tasks waiting for a slot to run in.
+    queue: Arc<Queue>,
+
+    /// The resources assumed for tasks that don't specify them.
+    ///
+    /// Only the configured defaults are assumed, so tasks are not charged
+    /// against the runner's capacity for resources nobody asked for.
+    defaults: Resources,
+
+    /// The rate limits of the calls made to the backend.
+    limits: Arc<Limits>,
+
+    /// The controller of the runner's concurrency (if it is adaptive).
+    controller: Option<Arc<Controller>>,
+
+    /// The labels used when routing tasks to runners.
+    labels: Vec<String>,
+
+    /// The tracker for submitted tasks.
+    tracker: TaskTracker,
+
+    /// The total number of tasks submitted.
+    submitted: Arc<AtomicUsize>,
+
+    /// The total number of tasks that failed.
+    failed: Arc<AtomicUsize>,
+
+    /// The cancellation tokens for in-flight tasks.
+    tokens: CancellationTokens,
+
+    /// The channel over which task events are sent.
+    events: broadcast::Sender<TaskEvent>,
+
+    /// The statuses of submitted tasks.
+    statuses: Arc<Registry>,
+
+    /// The call cache (if one is configured).
+    cache: Option<Arc<dyn Store>>,
 
-    /// The list of submitted tasks.
-    pub tasks: FuturesUnordered<BoxFuture<'static, TaskResult>>,
+    /// The run journal (if one is configured).
+    journal: Option<Arc<Journal>>,
+
+    /// The metrics of the runner (if metrics are enabled).
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<metrics::Runner>>,
 
     /// The unique name generator for tasks without names being sent to backends
     /// that may need names.
@@ -59,38 +215,325 @@ impl Runner {
         max_tasks: usize,
         defaults: Option<Defaults>,
     ) -> Result<Self> {
-        let backend = match config {
+        let (backend, capacity) = match config {
             Kind::Docker(config) => {
                 let backend = docker::Backend::initialize_default_with(config)?;
-                Arc::new(backend) as Arc<dyn Backend>
+
+                // NOTE: a local Docker daemon can only run as much as its host
+                // can fit, so its capacity is detected (unless configured).
+                let capacity = match backend.capacity().await {
+                    Ok(capacity) => Some(capacity),
+                    Err(err) => {
+                        warn!("unable to detect the capacity of the Docker host: {err:#}");
+                        None
+                    }
+                };
+
+                (Arc::new(backend) as Arc<dyn Backend>, capacity)
             }
             Kind::Generic(config) => {
-                let backend = generic::Backend::initialize(config, defaults).await?;
-                Arc::new(backend)
+                let backend = generic::Backend::initialize(config, defaults.clone()).await?;
+                (Arc::new(backend) as Arc<dyn Backend>, None)
+            }
+            Kind::TES(config) => (
+                Arc::new(tes::Backend::initialize(config)) as Arc<dyn Backend>,
+                None,
+            ),
+            Kind::Local(config) => {
+                let backend = local::Backend::new(config, defaults.clone());
+
+                // NOTE: as with a local Docker daemon, processes can only use
+                // as many CPUs as the host has.
+                let capacity = backend.capacity();
+                (Arc::new(backend) as Arc<dyn Backend>, Some(capacity))
+            }
+            Kind::Apptainer(config) => {
+                let backend = apptainer::Backend::new(config);
+                let capacity = backend.capacity();
+                (Arc::new(backend) as Arc<dyn Backend>, Some(capacity))
             }
-            Kind::TES(config) => Arc::new(tes::Backend::initialize(config)),
+            Kind::Mock(config) => (
+                Arc::new(mock::Backend::new(config)) as Arc<dyn Backend>,
+                None,
+            ),
         };
 
+        let mut runner = Self::new(backend, max_tasks);
+
+        if let Some(defaults) = &defaults {
+            runner.defaults = runner.defaults.apply(&Resources::from(defaults));
+        }
+
+        if let Some(capacity) = capacity {
+            runner = runner.with_capacity(capacity);
+        }
+
+        Ok(runner)
+    }
+
+    /// Creates a new [`Runner`] around an already initialized [`Backend`].
+    pub fn new(backend: Arc<dyn Backend>, max_tasks: usize) -> Self {
         let generator = UniqueAlphanumeric::default_with_expected_generations(max_tasks);
 
-        Ok(Self {
+        Self {
             backend,
-            lock: Arc::new(Semaphore::new(max_tasks)),
-            tasks: Default::default(),
+            queue: Queue::new(max_tasks),
+            defaults: Resources::empty(),
+            limits: Default::default(),
+            controller: None,
+            labels: Default::default(),
+            tracker: TaskTracker::new(),
+            submitted: Default::default(),
+            failed: Default::default(),
+            tokens: Default::default(),
+            events: broadcast::Sender::new(event::DEFAULT_CAPACITY),
+            statuses: Default::default(),
+            cache: None,
+            journal: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
             name_generator: Arc::new(Mutex::new(GeneratorIterator::new(
                 generator,
                 NAME_BUFFER_LEN,
             ))),
-        })
+        }
+    }
+
+    /// Sends the events for tasks submitted to this runner over the provided
+    /// channel (rather than the runner's own channel).
+    ///
+    /// This is how multiple runners share a single event stream.
+    pub fn with_events(mut self, events: broadcast::Sender<TaskEvent>) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Keeps the statuses of tasks submitted to this runner in the provided
+    /// registry (rather than the runner's own registry).
+    ///
+    /// This is how multiple runners share a single view of their tasks.
+    pub(crate) fn with_statuses(mut self, statuses: Arc<Registry>) -> Self {
+        self.statuses = statuses;
+        self
+    }
+
+    /// Caches the results of tasks submitted to this runner in the provided
+    /// [`Store`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous store provided to the runner.
+    pub fn with_cache(mut self, store: Arc<dyn Store>) -> Self {
+        self.cache = Some(store);
+        self
+    }
+
+    /// Records the tasks submitted to this runner in the provided journal.
+    ///
+    /// If the journal was [resumed](Journal::resume), tasks that match a
+    /// recorded task pick up where that task left off.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous journal provided to the
+    /// runner.
+    pub fn with_journal(mut self, journal: Arc<Journal>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Records the metrics of this runner (under the provided name) in the
+    /// provided registry.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous registry provided to the
+    /// runner.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: &Metrics, name: &str) -> Self {
+        self.metrics =
+            Some(metrics.register(name, self.backend.default_name(), self.queue.clone()));
+        self
+    }
+
+    /// Only admits tasks while the sum of the resources of the running tasks
+    /// fits within the provided capacity (see the [`queue`] module).
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous capacity provided to the
+    /// runner (including one detected from the backend).
+    pub fn with_capacity(self, capacity: Capacity) -> Self {
+        self.queue.set_capacity(&capacity);
+        self
+    }
+
+    /// Limits the rates at which the runner calls its backend.
+    ///
+    /// Each rate is enforced as a token bucket that holds up to a second's
+    /// worth of calls. Rate limits apply independently of the maximum number of
+    /// concurrent tasks.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous rate limit provided to the
+    /// runner.
+    pub fn with_rate_limit(mut self, limit: &RateLimit) -> Self {
+        self.limits = Arc::new(Limits::new(limit));
+        self
+    }
+
+    /// Adjusts the number of tasks the runner runs at once within the provided
+    /// bounds based on feedback from the backend.
+    ///
+    /// The runner starts from its current maximum number of tasks (clamped to
+    /// the bounds). The limit grows while tasks are waiting and the backend
+    /// accepts submissions quickly, and it halves when the backend is
+    /// unavailable or slow to accept submissions.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous bounds provided to the
+    /// runner.
+    pub fn with_adaptive(mut self, adaptive: &Adaptive) -> Self {
+        self.controller = Some(Arc::new(Controller::new(self.queue.clone(), adaptive)));
+        self
+    }
+
+    /// Sets the number of tasks the runner runs at once.
+    ///
+    /// Lowering the number never interrupts running tasks. If the runner is
+    /// [adaptive](Self::with_adaptive), the number is clamped to its bounds
+    /// and adjusted from there.
+    pub fn resize(&self, max_tasks: usize) {
+        match &self.controller {
+            Some(controller) => controller.resize(max_tasks),
+            None => self.queue.set_max_tasks(max_tasks.max(1)),
+        }
+    }
+
+    /// Gets the number of tasks the runner currently runs at once.
+    pub fn max_tasks(&self) -> usize {
+        self.queue.max_tasks()
+    }
+
+    /// Adds labels to the runner.
+    ///
+    /// When tasks are [routed](crate::Engine::submit_routed), a task that
+    /// requires labels is only sent to runners that have all of them.
+    pub fn with_labels<Iter>(mut self, labels: Iter) -> Self
+    where
+        Iter: IntoIterator<Item = String>,
+    {
+        self.labels.extend(labels);
+        self
+    }
+
+    /// Configures the share of the runner's slots for a group of tasks (see
+    /// the [`queue`] module).
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous configuration provided to the
+    /// runner for the same group.
+    pub fn with_group(self, name: impl Into<String>, group: Group) -> Self {
+        self.queue.set_group(name.into(), group);
+        self
+    }
+
+    /// Sets the amount of time a task must spend waiting for a slot to have
+    /// its priority raised by one (see the [`queue`] module).
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous interval provided to the
+    /// runner.
+    pub fn with_aging(self, interval: Duration) -> Self {
+        self.queue.set_aging(interval);
+        self
+    }
+
+    /// Subscribes to the events for tasks submitted to this runner.
+    pub fn subscribe(&self) -> broadcast::Receiver<TaskEvent> {
+        self.events.subscribe()
     }
 
     /// Submits a task to be executed by the backend.
-    pub fn submit(&self, mut task: Task) -> TaskHandle {
-        trace!(backend = ?self.backend, task = ?task);
+    ///
+    /// The task is spawned onto the current [`tokio`] runtime immediately, so
+    /// this must be called from within a runtime.
+    ///
+    /// If the runner has been [shut down](Self::shutdown), a
+    /// [`SubmitError::ShutDown`] error is returned.
+    pub fn submit(&self, task: Task) -> std::result::Result<TaskHandle, SubmitError> {
+        self.submit_as(TaskId::generate(), CancellationToken::new(), task)
+    }
+
+    /// Submits a task under an existing id and cancellation token.
+    ///
+    /// If the runner has been [shut down](Self::shutdown), a
+    /// [`SubmitError::ShutDown`] error is returned.
+    pub(crate) fn submit_as(
+        &self,
+        id: TaskId,
+        token: CancellationToken,
+        task: Task,
+    ) -> std::result::Result<TaskHandle, SubmitError> {
+        if self.tracker.is_closed() {
+            return Err(SubmitError::ShutDown);
+        }
+
+        Ok(self.resubmit(id, token, task))
+    }
+
+    /// Submits a task under an existing id and cancellation token, even if the
+    /// runner has been [shut down](Self::shutdown).
+    ///
+    /// This is used when a task that was accepted before the shutdown moves
+    /// between runners (e.g., when failing over) so that it keeps its identity
+    /// and is allowed to finish.
+    pub(crate) fn resubmit(
+        &self,
+        id: TaskId,
+        token: CancellationToken,
+        mut task: Task,
+    ) -> TaskHandle {
+        // NOTE: the backend's identifier for the task (`job`) is recorded once
+        // the backend reports that the task was submitted.
+        let span = info_span!(
+            "task",
+            %id,
+            backend = self.backend.default_name(),
+            job = field::Empty
+        );
+        trace!(parent: &span, task = ?task);
 
         let (tx, rx) = tokio::sync::oneshot::channel();
+
         let backend = self.backend.clone();
-        let lock = self.lock.clone();
+        let queue = self.queue.clone();
+        let group = task.group().map(ToOwned::to_owned);
+        let usage = self.usage(&task);
+        let tokens = self.tokens.clone();
+        let failed = self.failed.clone();
+        let journal = self.journal.clone();
+        let emitter = Emitter::new(id, self.events.clone())
+            .with_journal(journal.clone())
+            .with_statuses(self.statuses.clone())
+            .with_span(span.clone())
+            .with_limits(self.limits.clone())
+            .with_feedback(
+                self.controller
+                    .clone()
+                    .map(|controller| Arc::new(Feedback::new(controller))),
+            );
+        #[cfg(feature = "metrics")]
+        let emitter = emitter.with_recorder(
+            self.metrics
+                .clone()
+                .map(|metrics| Arc::new(Recorder::new(metrics))),
+        );
+        let cache = self.cache.clone();
 
         if backend.default_name() == "docker" && task.name().is_none() {
             let mut generator = self.name_generator.lock().unwrap();
@@ -98,32 +541,730 @@ impl Runner {
             task.override_name(generator.next().unwrap());
         }
 
+        tokens.lock().unwrap().insert(id, token.clone());
+        self.statuses
+            .submitted(id, task.name(), backend.default_name());
+        emitter.emit(TaskState::Queued);
+
+        let handle = TaskHandle {
+            id,
+            callback: rx,
+            token: token.clone(),
+        };
+
         let fun = async move {
-            let _permit = lock.acquire().await;
+            let policy = task.retry_policy().cloned().unwrap_or_default();
+            let mut attempts = Vec::new();
+
+            let key = match cache.is_some() || journal.is_some() {
+                true => key(&task).await,
+                false => None,
+            };
+
+            let resumed = match (&journal, &key) {
+                (Some(journal), Some(key)) => journal.resumed(key, backend.default_name()),
+                _ => None,
+            };
+
+            let (hit, mut reattach) = match resumed {
+                Some(Resumed::Completed(result)) => (Some(result), None),
+                Some(Resumed::Reattach(reattach)) => (None, Some(reattach)),
+                None => match (&cache, &key) {
+                    (Some(store), Some(key)) => (lookup(store.as_ref(), &task, key).await, None),
+                    _ => (None, None),
+                },
+            };
+
+            if let (Some(journal), Some(key)) = (&journal, &key) {
+                // NOTE: if the backend turns out not to support reattaching,
+                // the run starts from the first execution regardless. That is
+                // harmless, as such backends never reattach on later resumes
+                // either.
+                let execution = reattach.as_ref().map(|r| r.execution).unwrap_or(0);
+                journal.task(id, key, backend.default_name(), execution);
+            }
+
+            let result = match hit {
+                Some(result) => Ok(result),
+                None => loop {
+                    let number = attempts.len() as u32 + 1;
+
+                    let result = tokio::select! {
+                        permit = queue.acquire(group.clone(), task.priority(), usage) => {
+                            let _permit = permit;
+                            let started = SystemTime::now();
+                            let result = run(&backend, &task, reattach.take(), &emitter, &token).await;
+                            attempts.push(Attempt::new(number, started, result.as_ref().err().cloned()));
+                            result
+                        }
+                        _ = token.cancelled() => Err(TaskError::Cancelled),
+                    };
+
+                    match result {
+                        Err(error) if policy.should_retry(number, &error) => {
+                            let delay = policy.backoff(number);
+                            debug!(
+                                "retrying task {id} in {delay:?} after attempt {number} failed: \
+                                 {error}"
+                            );
+                            emitter.emit(TaskState::Retrying {
+                                attempt: number,
+                                error,
+                                delay,
+                            });
+
+                            // NOTE: the permit is released while waiting so that
+                            // other tasks can run in the meantime.
+                            tokio::select! {
+                                _ = tokio::time::sleep(delay) => {}
+                                _ = token.cancelled() => break Err(TaskError::Cancelled),
+                            }
+                        }
+                        result => {
+                            break result.map(|mut result| {
+                                result.attempts = attempts;
+                                result
+                            });
+                        }
+                    }
+                },
+            };
+
+            if let (Some(key), Ok(result)) = (&key, &result) {
+                if let Some(journal) = &journal {
+                    journal.completed(id, result);
+                }
 
-            let result = backend.clone().run(task).await;
+                if let (Some(store), false) = (&cache, result.is_cached()) {
+                    if let Err(err) = store.put(key, result).await {
+                        warn!("failed to store the result of task {id} in the call cache: {err}");
+                    }
+                }
+            }
+
+            tokens.lock().unwrap().remove(&id);
+
+            match &result {
+                Ok(_) => emitter.emit(TaskState::Completed),
+                Err(err) => {
+                    failed.fetch_add(1, Ordering::SeqCst);
+                    emitter.emit(TaskState::Failed(err.clone()));
+                }
+            }
 
             // NOTE: if the send does not succeed, that is almost certainly
             // because the receiver was dropped. That is a relatively standard
             // practice if you don't specifically _want_ to keep a handle to the
             // returned result, so we ignore any errors related to that.
-            let _ = tx.send(result.clone());
-            drop(_permit);
+            let _ = tx.send(result);
+        };
+
+        self.submitted.fetch_add(1, Ordering::SeqCst);
+        self.tracker.spawn(fun.instrument(span));
+        handle
+    }
+
+    /// Renders what the backend would submit for a task without running
+    /// anything (i.e., a dry run).
+    ///
+    /// An error is returned if the backend does not support dry runs or the
+    /// task cannot be rendered (e.g., a placeholder in a generic backend's
+    /// submit command has no value).
+    pub fn plan(&self, mut task: Task) -> Result<Plan> {
+        if self.backend.default_name() == "docker" && task.name().is_none() {
+            let mut generator = self.name_generator.lock().unwrap();
+            // SAFETY: this generator should _never_ run out of entries.
+            task.override_name(generator.next().unwrap());
+        }
+
+        match self.backend.plan(&task) {
+            Some(plan) => Ok(plan?),
+            None => eyre::bail!(
+                "the `{}` backend does not support dry runs",
+                self.backend.default_name()
+            ),
+        }
+    }
+
+    /// Cancels a submitted task.
+    ///
+    /// Returns `false` if the task is not known to this runner or has already
+    /// completed.
+    pub fn cancel(&self, id: TaskId) -> bool {
+        match self.tokens.lock().unwrap().get(&id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancels every in-flight task.
+    ///
+    /// Each task is cancelled through its backend (e.g., containers are
+    /// stopped and removed) before it resolves. Returns the number of tasks
+    /// that were cancelled.
+    pub fn cancel_all(&self) -> usize {
+        let tokens = self.tokens.lock().unwrap();
+
+        for token in tokens.values() {
+            token.cancel();
+        }
+
+        tokens.len()
+    }
+
+    /// Gets the status of a task submitted to the runner.
+    ///
+    /// Returns `None` if the task is not known to this runner.
+    pub fn status(&self, id: TaskId) -> Option<Status> {
+        self.statuses.get(id)
+    }
+
+    /// Lists the statuses of the tasks submitted to the runner that match the
+    /// provided filter (in the order they were submitted).
+    pub fn list(&self, filter: &Filter) -> Vec<Status> {
+        self.statuses.list(filter)
+    }
+
+    /// Gets the total number of tasks submitted to the runner.
+    pub fn submitted(&self) -> usize {
+        self.submitted.load(Ordering::SeqCst)
+    }
+
+    /// Gets the total number of tasks submitted to the runner that failed.
+    pub fn failed(&self) -> usize {
+        self.failed.load(Ordering::SeqCst)
+    }
 
-            result
+    /// Gets the number of submitted tasks that are holding a slot (i.e., that
+    /// are running).
+    pub fn running(&self) -> usize {
+        self.queue.running()
+    }
+
+    /// Gets the number of submitted tasks that are waiting for a slot to run
+    /// in.
+    pub fn queued(&self) -> usize {
+        self.queue.waiting()
+    }
+
+    /// Gets the number of submitted tasks that have not yet completed.
+    pub fn pending(&self) -> usize {
+        self.tracker.len()
+    }
+
+    /// Gets the labels of the runner.
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.labels.iter().map(String::as_str)
+    }
+
+    /// Gets the load of the runner.
+    ///
+    /// This is the number of submitted tasks that have not yet completed
+    /// relative to the number of tasks the runner can run at once.
+    pub fn load(&self) -> f64 {
+        self.pending() as f64 / self.queue.max_tasks().max(1) as f64
+    }
+
+    /// Returns whether the runner can run a task.
+    ///
+    /// A runner can run a task if it has every label the task
+    /// [requires](Task::labels), if its backend runs executions within their
+    /// container images (when the task [requires
+    /// it](Task::requires_container)), and if the task's resources fit within
+    /// the runner's capacity.
+    pub fn accepts(&self, task: &Task) -> bool {
+        task.labels()
+            .into_iter()
+            .flatten()
+            .all(|label| self.labels.iter().any(|l| l == label))
+            && (!task.requires_container() || self.backend.containers())
+            && self.queue.fits(self.usage(task))
+    }
+
+    /// Gets the resources a task uses once resolved against the runner's
+    /// defaults.
+    fn usage(&self, task: &Task) -> Usage {
+        Usage::of(&match task.resources() {
+            Some(resources) => self.defaults.clone().apply(resources),
+            None => self.defaults.clone(),
+        })
+    }
+
+    /// Stops the runner from accepting new tasks.
+    ///
+    /// Tasks that have already been submitted continue to execute.
+    pub fn shutdown(&self) {
+        self.tracker.close();
+    }
+
+    /// Waits for the runner to be [shut down](Self::shutdown) and for all
+    /// submitted tasks to complete.
+    pub async fn join(&self) {
+        self.tracker.wait().await;
+    }
+}
+
+/// Computes the call cache key for a task.
+///
+/// Failures are logged and treated as if caching (and journaling) were disabled
+/// for the task, as the task can always be run instead.
+async fn key(task: &Task) -> Option<Key> {
+    match Key::compute(task).await {
+        Ok(key) => Some(key),
+        Err(err) => {
+            warn!("unable to compute the call cache key for a task: {err}");
+            None
+        }
+    }
+}
+
+/// Looks up a task in the call cache.
+///
+/// Failures are logged and treated as misses, as the task can always be run
+/// instead.
+async fn lookup(store: &dyn Store, task: &Task, key: &Key) -> Option<TaskResult> {
+    match store.get(key).await {
+        Ok(Some(result)) => {
+            if !cache::outputs_exist(task).await {
+                return None;
+            }
+
+            debug!("call cache hit for key `{key}`");
+            Some(result)
+        }
+        Ok(None) => None,
+        Err(err) => {
+            warn!("unable to read the call cache for key `{key}`: {err}");
+            None
+        }
+    }
+}
+
+/// Runs a single attempt of a task on a backend while enforcing the task's
+/// [timeout](Task::timeout).
+///
+/// When the timeout expires, the backend is cancelled (so that it stops any
+/// work it started) and the attempt resolves with [`TaskError::TimedOut`].
+///
+/// If work from a previous run is provided, the backend is asked to reattach
+/// to it (falling back to running the task if the backend cannot).
+///
+/// The backend is only called once the runner's submission rate limit allows
+/// it, and the timeout only begins after that.
+async fn run(
+    backend: &Arc<dyn Backend>,
+    task: &Task,
+    reattach: Option<Reattach>,
+    emitter: &Emitter,
+    token: &CancellationToken,
+) -> std::result::Result<TaskResult, TaskError> {
+    let start = |token: CancellationToken| {
+        reattach
+            .and_then(|reattach| {
+                backend.reattach(task.clone(), reattach, emitter.clone(), token.clone())
+            })
+            .unwrap_or_else(|| backend.run(task.clone(), emitter.clone(), token))
+    };
+
+    // NOTE: the rate limit is enforced here rather than left to the backend so
+    // that no backend can submit work faster than the runner allows.
+    tokio::select! {
+        _ = emitter.dispatched() => {}
+        _ = token.cancelled() => return Err(TaskError::Cancelled),
+    }
+
+    let Some(timeout) = task.timeout() else {
+        return start(token.clone()).await;
+    };
+
+    let child = token.child_token();
+    let run = start(child.clone());
+    tokio::pin!(run);
+
+    tokio::select! {
+        result = &mut run => return result,
+        _ = tokio::time::sleep(timeout) => child.cancel(),
+    }
+
+    match run.await {
+        Err(TaskError::Cancelled) if !token.is_cancelled() => Err(TaskError::TimedOut),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::ExitStatus;
+    use std::process::Output;
+
+    use futures::FutureExt as _;
+    use futures::future::BoxFuture;
+    use nonempty::NonEmpty;
+
+    use super::*;
+    use crate::task::Execution;
+
+    /// A backend whose tasks complete successfully right away.
+    #[derive(Debug)]
+    struct Immediate;
+
+    impl Backend for Immediate {
+        fn default_name(&self) -> &'static str {
+            "immediate"
+        }
+
+        fn run(
+            &self,
+            _: Task,
+            _: Emitter,
+            _: CancellationToken,
+        ) -> BoxFuture<'static, std::result::Result<TaskResult, TaskError>> {
+            async move {
+                Ok(TaskResult::new(NonEmpty::new(Output {
+                    status: ExitStatus::default(),
+                    stdout: Vec::new(),
+                    stderr: Vec::new(),
+                })))
+            }
+            .boxed()
+        }
+    }
+
+    /// A backend that accepts each task as a submission and completes it right
+    /// away.
+    #[derive(Debug)]
+    struct Accepting;
+
+    impl Backend for Accepting {
+        fn default_name(&self) -> &'static str {
+            "accepting"
+        }
+
+        fn run(
+            &self,
+            _: Task,
+            emitter: Emitter,
+            _: CancellationToken,
+        ) -> BoxFuture<'static, std::result::Result<TaskResult, TaskError>> {
+            async move {
+                emitter.submitting().await;
+                emitter.submitted(None);
+
+                Ok(TaskResult::new(NonEmpty::new(Output {
+                    status: ExitStatus::default(),
+                    stdout: Vec::new(),
+                    stderr: Vec::new(),
+                })))
+            }
+            .boxed()
+        }
+    }
+
+    /// A backend whose tasks fail with a transient error a number of times
+    /// before completing successfully.
+    #[derive(Debug)]
+    struct Flaky(Arc<AtomicUsize>);
+
+    impl Backend for Flaky {
+        fn default_name(&self) -> &'static str {
+            "flaky"
+        }
+
+        fn run(
+            &self,
+            task: Task,
+            emitter: Emitter,
+            token: CancellationToken,
+        ) -> BoxFuture<'static, std::result::Result<TaskResult, TaskError>> {
+            // NOTE: the number of remaining failures stops at zero (rather
+            // than wrapping around).
+            let fails = self
+                .0
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+
+            if fails {
+                async move { Err(TaskError::BackendUnavailable(String::from("flaky"))) }.boxed()
+            } else {
+                Immediate.run(task, emitter, token)
+            }
+        }
+    }
+
+    /// A backend whose tasks never complete unless they are cancelled.
+    #[derive(Debug)]
+    struct Pending;
+
+    impl Backend for Pending {
+        fn default_name(&self) -> &'static str {
+            "pending"
+        }
+
+        fn run(
+            &self,
+            _: Task,
+            _: Emitter,
+            token: CancellationToken,
+        ) -> BoxFuture<'static, std::result::Result<TaskResult, TaskError>> {
+            async move {
+                token.cancelled().await;
+                Err(TaskError::Cancelled)
+            }
+            .boxed()
+        }
+    }
+
+    fn task() -> Task {
+        Task::builder()
+            .extend_executions([Execution::builder()
+                .image("ubuntu")
+                .args(["echo", "hello"])
+                .try_build()
+                .unwrap()])
+            .try_build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn cancelled_tasks_resolve_as_cancelled() {
+        let runner = Runner::new(Arc::new(Pending), 1);
+
+        // NOTE: the second task is cancelled while it waits for a permit.
+        let running = runner.submit(task()).unwrap();
+        let queued = runner.submit(task()).unwrap();
+
+        running.cancel();
+        assert!(runner.cancel(queued.id()));
+
+        runner.shutdown();
+        runner.join().await;
+
+        assert!(matches!(
+            running.callback.await.unwrap(),
+            Err(TaskError::Cancelled)
+        ));
+        assert!(matches!(
+            queued.callback.await.unwrap(),
+            Err(TaskError::Cancelled)
+        ));
+    }
+
+    #[tokio::test]
+    async fn all_tasks_can_be_cancelled() {
+        let runner = Runner::new(Arc::new(Pending), 1);
+
+        let handles = [
+            runner.submit(task()).unwrap(),
+            runner.submit(task()).unwrap(),
+        ];
+
+        runner.shutdown();
+        assert_eq!(runner.cancel_all(), 2);
+        runner.join().await;
+
+        for handle in handles {
+            assert!(matches!(
+                handle.callback.await.unwrap(),
+                Err(TaskError::Cancelled)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn tasks_execute_as_they_are_submitted() {
+        let runner = Runner::new(Arc::new(Immediate), 1);
+
+        // NOTE: nothing ever joins the runner here, so the task must be
+        // executing on its own.
+        let handle = runner.submit(task()).unwrap();
+        assert!(handle.callback.await.unwrap().is_ok());
+
+        let handle = runner.clone().submit(task()).unwrap();
+        assert!(handle.callback.await.unwrap().is_ok());
+        assert_eq!(runner.submitted(), 2);
+    }
+
+    #[tokio::test]
+    async fn shut_down_runners_reject_tasks() {
+        let runner = Runner::new(Arc::new(Immediate), 1);
+        runner.shutdown();
+
+        assert!(matches!(runner.submit(task()), Err(SubmitError::ShutDown)));
+
+        runner.join().await;
+        assert_eq!(runner.submitted(), 0);
+    }
+
+    #[test]
+    fn only_requested_resources_count_against_capacity() {
+        let runner = Runner::new(Arc::new(Immediate), 1).with_capacity(Capacity::new(
+            Some(1),
+            Some(1.0),
+            None,
+        ));
+        assert!(runner.accepts(&task()));
+
+        let task = Task::builder()
+            .extend_executions(task().executions().cloned())
+            .resources(crate::task::resources::Builder::default().ram(2.0).build())
+            .try_build()
+            .unwrap();
+        assert!(!runner.accepts(&task));
+    }
+
+    #[tokio::test]
+    async fn failed_attempts_are_retried() {
+        let runner = Runner::new(Arc::new(Flaky(Arc::new(AtomicUsize::new(2)))), 1);
+        let policy = || {
+            crate::task::retry::Policy::builder()
+                .max_attempts(3u32)
+                .initial_backoff(std::time::Duration::ZERO)
+                .build()
         };
 
-        self.tasks.push(Box::pin(fun));
-        TaskHandle { callback: rx }
+        let task = Task::builder()
+            .extend_executions(task().executions().cloned())
+            .retry_policy(policy())
+            .try_build()
+            .unwrap();
+
+        let result = runner
+            .submit(task)
+            .unwrap()
+            .callback
+            .await
+            .unwrap()
+            .unwrap();
+        let attempts = result.attempts();
+        assert_eq!(attempts.len(), 3);
+        assert!(matches!(
+            attempts[0].error(),
+            Some(TaskError::BackendUnavailable(_))
+        ));
+        assert!(attempts[2].error().is_none());
+        assert_eq!(attempts[2].number(), 3);
+    }
+
+    #[tokio::test]
+    async fn statuses_follow_tasks_through_their_attempts() {
+        let runner = Runner::new(Arc::new(Flaky(Arc::new(AtomicUsize::new(1)))), 1);
+
+        let task = Task::builder()
+            .extend_executions(task().executions().cloned())
+            .retry_policy(
+                crate::task::retry::Policy::builder()
+                    .max_attempts(2u32)
+                    .initial_backoff(std::time::Duration::ZERO)
+                    .build(),
+            )
+            .try_build()
+            .unwrap();
+
+        let handle = runner.submit(task).unwrap();
+        let id = handle.id();
+        assert_eq!(runner.status(id).unwrap().backend(), "flaky");
+
+        handle.callback.await.unwrap().unwrap();
+
+        let status = runner.status(id).unwrap();
+        assert!(matches!(status.state(), TaskState::Completed));
+        assert_eq!(status.attempt(), 2);
+        assert!(matches!(
+            status.error(),
+            Some(TaskError::BackendUnavailable(_))
+        ));
+
+        assert_eq!(runner.list(&Filter::default().finished(true)).len(), 1);
+        assert!(runner.list(&Filter::default().backend("docker")).is_empty());
     }
 
-    /// Gets the tasks from the runner.
-    pub fn tasks(self) -> impl Iterator<Item = BoxFuture<'static, TaskResult>> {
-        self.tasks.into_iter()
+    #[tokio::test]
+    async fn tasks_that_exceed_their_timeout_time_out() {
+        let runner = Runner::new(Arc::new(Pending), 1);
+
+        let task = Task::builder()
+            .extend_executions(task().executions().cloned())
+            .timeout(std::time::Duration::from_millis(10))
+            .try_build()
+            .unwrap();
+
+        let handle = runner.submit(task).unwrap();
+        assert!(matches!(
+            handle.callback.await.unwrap(),
+            Err(TaskError::TimedOut)
+        ));
     }
 
-    /// Runs all of the tasks scheduled in the [`Runner`].
-    pub async fn run(self) {
-        join_all(self.tasks).await;
+    #[tokio::test]
+    async fn cached_results_skip_the_backend() {
+        let root = tempfile::tempdir().unwrap();
+
+        // NOTE: this backend only succeeds the first time it is run.
+        let runner = Runner::new(Arc::new(Flaky(Arc::new(AtomicUsize::new(0)))), 1)
+            .with_cache(Arc::new(crate::cache::disk::Store::new(root.path())));
+
+        let result = runner.submit(task()).unwrap().callback.await.unwrap();
+        assert!(!result.unwrap().is_cached());
+
+        let result = runner.submit(task()).unwrap().callback.await.unwrap();
+        assert!(result.unwrap().is_cached());
+    }
+
+    #[tokio::test]
+    async fn task_events_are_emitted() {
+        let runner = Runner::new(Arc::new(Immediate), 1);
+        let mut events = runner.subscribe();
+
+        let handle = runner.submit(task()).unwrap();
+        handle.callback.await.unwrap().unwrap();
+
+        let event = events.recv().await.unwrap();
+        assert_eq!(event.id(), handle.id);
+        assert!(matches!(event.state(), TaskState::Queued));
+
+        let event = events.recv().await.unwrap();
+        assert_eq!(event.id(), handle.id);
+        assert!(matches!(event.state(), TaskState::Completed));
+    }
+
+    #[tokio::test]
+    async fn submissions_are_rate_limited_by_the_runner() {
+        // NOTE: the backend never waits on the rate limit itself, and the
+        // bucket holds ten tokens, so the last four tasks wait for a token
+        // each.
+        let runner =
+            Runner::new(Arc::new(Immediate), 14).with_rate_limit(&RateLimit::new(Some(10.0), None));
+        let started = tokio::time::Instant::now();
+
+        let handles = (0..14)
+            .map(|_| runner.submit(task()).unwrap())
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.callback.await.unwrap().unwrap();
+        }
+
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(300), "{elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn waiting_on_the_rate_limit_is_not_latency() {
+        // NOTE: the last four tasks wait on the rate limit for longer than the
+        // target latency, but the backend accepts each of them right away.
+        let runner = Runner::new(Arc::new(Accepting), 4)
+            .with_rate_limit(&RateLimit::new(Some(10.0), None))
+            .with_adaptive(&Adaptive::new(1, 4, Some(0.05)));
+
+        let handles = (0..14)
+            .map(|_| runner.submit(task()).unwrap())
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.callback.await.unwrap().unwrap();
+        }
+
+        assert_eq!(runner.max_tasks(), 4);
     }
 }
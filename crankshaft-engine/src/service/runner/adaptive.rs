@@ -0,0 +1,231 @@
+//! Adaptive concurrency.
+//!
+//! When a runner is configured with [`Adaptive`] bounds, the number of tasks
+//! it runs at once is adjusted at runtime by additive increase/multiplicative
+//! decrease (AIMD) based on feedback from its backend:
+//!
+//! * Each submission that the backend accepts within the target latency while
+//!   tasks are waiting for a slot (and every slot is taken) adds a fraction of
+//!   a slot, so the limit grows by roughly one slot for each "round" of
+//!   submissions.
+//! * An attempt that fails because the backend was unavailable, or a submission
+//!   that takes longer than the target latency, halves the limit. The limit is
+//!   halved at most once per [`COOLDOWN`] so that a single outage (which
+//!   typically fails many tasks at once) doesn't collapse it.
+//!
+//! The limit always stays within the configured bounds.
+
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crankshaft_config::backend::Adaptive;
+use tokio::time::Instant;
+use tracing::debug;
+
+use crate::service::runner::backend::TaskError;
+use crate::service::runner::queue::Queue;
+
+/// The factor the limit is multiplied by when the backend is overloaded.
+const DECREASE_FACTOR: f64 = 0.5;
+
+/// The minimum amount of time between decreases of the limit.
+pub(crate) const COOLDOWN: Duration = Duration::from_secs(5);
+
+/// The state of a [`Controller`].
+#[derive(Debug)]
+struct State {
+    /// The current limit (fractional so that it can grow gradually).
+    limit: f64,
+
+    /// When the limit was last decreased.
+    decreased: Option<Instant>,
+}
+
+/// Adjusts the number of tasks a runner runs at once.
+#[derive(Debug)]
+pub(crate) struct Controller {
+    /// The runner's queue.
+    queue: Arc<Queue>,
+
+    /// The minimum limit.
+    min: usize,
+
+    /// The maximum limit.
+    max: usize,
+
+    /// The longest a submission may take before the backend is considered
+    /// overloaded (if there is a target).
+    target: Option<Duration>,
+
+    /// The state of the controller.
+    state: Mutex<State>,
+}
+
+impl Controller {
+    /// Creates a new [`Controller`] for the provided queue that starts from the
+    /// queue's current limit (clamped to the bounds).
+    pub(crate) fn new(queue: Arc<Queue>, adaptive: &Adaptive) -> Self {
+        let min = adaptive.min_tasks().max(1);
+        let max = adaptive.max_tasks().max(min);
+        let target = adaptive
+            .target_latency()
+            .filter(|latency| latency.is_finite() && *latency > 0.0)
+            .map(Duration::from_secs_f64);
+
+        let controller = Self {
+            min,
+            max,
+            target,
+            state: Mutex::new(State {
+                limit: queue.max_tasks() as f64,
+                decreased: None,
+            }),
+            queue,
+        };
+
+        let mut state = controller.state.lock().unwrap();
+        controller.apply(&mut state);
+        drop(state);
+
+        controller
+    }
+
+    /// Sets the limit (clamped to the bounds).
+    pub(crate) fn resize(&self, max_tasks: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.limit = max_tasks as f64;
+        self.apply(&mut state);
+    }
+
+    /// Records that the backend accepted a submission after the provided
+    /// amount of time.
+    pub(crate) fn submitted(&self, latency: Duration) {
+        if self.target.is_some_and(|target| latency > target) {
+            debug!("submission took {latency:?}; reducing concurrency");
+            return self.decrease();
+        }
+
+        let mut state = self.state.lock().unwrap();
+
+        // NOTE: there is no point growing the limit unless it is what is
+        // holding tasks back.
+        if self.queue.is_waiting() && self.queue.running() >= self.queue.max_tasks() {
+            state.limit += 1.0 / state.limit.max(1.0);
+            self.apply(&mut state);
+        }
+    }
+
+    /// Records that an attempt failed with the provided error.
+    pub(crate) fn failed(&self, error: &TaskError) {
+        if let TaskError::BackendUnavailable(_) = error {
+            debug!("backend unavailable ({error}); reducing concurrency");
+            self.decrease();
+        }
+    }
+
+    /// Decreases the limit (unless it was decreased recently).
+    fn decrease(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        if state
+            .decreased
+            .is_some_and(|decreased| decreased.elapsed() < COOLDOWN)
+        {
+            return;
+        }
+
+        state.limit *= DECREASE_FACTOR;
+        state.decreased = Some(Instant::now());
+        self.apply(&mut state);
+    }
+
+    /// Clamps the limit to the bounds and applies it to the queue.
+    fn apply(&self, state: &mut State) {
+        state.limit = state.limit.clamp(self.min as f64, self.max as f64);
+
+        let limit = state.limit as usize;
+        if limit != self.queue.max_tasks() {
+            debug!("adjusting concurrency to {limit}");
+            self.queue.set_max_tasks(limit);
+        }
+    }
+}
+
+/// Feeds the outcome of a single task's attempts back to a [`Controller`].
+#[derive(Debug)]
+pub(crate) struct Feedback {
+    /// The controller of the task's runner.
+    controller: Arc<Controller>,
+
+    /// When the current attempt was handed to the backend (until it is
+    /// submitted).
+    dispatched: Mutex<Option<Instant>>,
+}
+
+impl Feedback {
+    /// Creates a new [`Feedback`] for a task submitted to a runner.
+    pub(crate) fn new(controller: Arc<Controller>) -> Self {
+        Self {
+            controller,
+            dispatched: Default::default(),
+        }
+    }
+
+    /// Records that an attempt was handed to the backend (i.e., it was given a
+    /// slot and the rate limit allowed it to be submitted).
+    pub(crate) fn dispatched(&self) {
+        *self.dispatched.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Records that the backend accepted a submission.
+    ///
+    /// Only the first submission of each attempt is fed back (later ones
+    /// belong to later executions of the same task).
+    pub(crate) fn submitted(&self) {
+        if let Some(dispatched) = self.dispatched.lock().unwrap().take() {
+            self.controller.submitted(dispatched.elapsed());
+        }
+    }
+
+    /// Records that an attempt failed.
+    pub(crate) fn failed(&self, error: &TaskError) {
+        self.dispatched.lock().unwrap().take();
+        self.controller.failed(error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::pin;
+
+    use futures::poll;
+
+    use super::*;
+    use crate::service::runner::queue::Usage;
+
+    #[tokio::test]
+    async fn limits_grow_with_demand_and_shrink_when_unavailable() {
+        let queue = Queue::new(1);
+        let controller = Controller::new(queue.clone(), &Adaptive::new(1, 4, Some(60.0)));
+
+        // NOTE: with one task running and another waiting, a quick submission
+        // grows the limit (which lets the waiting task run).
+        let _running = queue.acquire(None, 0, Usage::default()).await;
+        let mut waiting = pin!(queue.acquire(None, 0, Usage::default()));
+        assert!(poll!(waiting.as_mut()).is_pending());
+
+        controller.submitted(Duration::ZERO);
+        assert_eq!(queue.max_tasks(), 2);
+        assert!(poll!(waiting.as_mut()).is_ready());
+
+        controller.resize(100);
+        assert_eq!(queue.max_tasks(), 4);
+
+        // NOTE: only the first failure within the cooldown shrinks the limit.
+        let unavailable = TaskError::BackendUnavailable(String::from("down"));
+        controller.failed(&unavailable);
+        controller.failed(&unavailable);
+        assert_eq!(queue.max_tasks(), 2);
+    }
+}
@@ -2,29 +2,264 @@
 
 use std::fmt::Debug;
 use std::process::Output;
+use std::time::Duration;
+use std::time::SystemTime;
 
 use async_trait::async_trait;
 use futures::future::BoxFuture;
 use nonempty::NonEmpty;
+use tokio_util::sync::CancellationToken;
 
 use crate::Task;
+use crate::service::runner::event::Emitter;
 
+pub mod apptainer;
 pub mod docker;
 pub mod generic;
+pub mod local;
+pub mod mock;
 pub mod tes;
 
+/// A record of a single attempt at running a task.
+#[derive(Clone, Debug)]
+pub struct Attempt {
+    /// The number of the attempt (starting at one).
+    number: u32,
+
+    /// The time at which the attempt was handed to the backend.
+    started: SystemTime,
+
+    /// How long the attempt took.
+    duration: Duration,
+
+    /// The error the attempt failed with (if it failed).
+    error: Option<TaskError>,
+}
+
+impl Attempt {
+    /// Creates a new [`Attempt`] that started at `started` and finished now.
+    pub(crate) fn new(number: u32, started: SystemTime, error: Option<TaskError>) -> Self {
+        Self {
+            number,
+            started,
+            duration: started.elapsed().unwrap_or_default(),
+            error,
+        }
+    }
+
+    /// Gets the number of the attempt (starting at one).
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    /// Gets the time at which the attempt was handed to the backend.
+    pub fn started(&self) -> SystemTime {
+        self.started
+    }
+
+    /// Gets how long the attempt took.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Gets the error the attempt failed with (if it failed).
+    pub fn error(&self) -> Option<&TaskError> {
+        self.error.as_ref()
+    }
+}
+
 /// A reply from a backend when a task is completed.
 #[derive(Clone, Debug)]
 pub struct TaskResult {
     /// The results from each execution.
     pub(crate) executions: NonEmpty<Output>,
+
+    /// Every attempt at running the task (the last of which succeeded).
+    pub(crate) attempts: Vec<Attempt>,
+
+    /// Whether the result was retrieved from the call cache.
+    cached: bool,
 }
 
 impl TaskResult {
+    /// Creates a new [`TaskResult`] from the results of each execution.
+    pub(crate) fn new(executions: NonEmpty<Output>) -> Self {
+        Self {
+            executions,
+            attempts: Vec::new(),
+            cached: false,
+        }
+    }
+
+    /// Marks the result as having been retrieved from the call cache.
+    pub(crate) fn cached(mut self) -> Self {
+        self.cached = true;
+        self
+    }
+
     /// Gets the execution results.
     pub fn executions(&self) -> &NonEmpty<Output> {
         &self.executions
     }
+
+    /// Gets every attempt at running the task.
+    ///
+    /// Attempts that failed and were retried (according to the task's
+    /// [retry policy](crate::task::retry::Policy)) come before the final,
+    /// successful attempt.
+    pub fn attempts(&self) -> &[Attempt] {
+        &self.attempts
+    }
+
+    /// Returns whether the result was retrieved from a previous run (from
+    /// either the [call cache](crate::cache) or a [resumed
+    /// journal](crate::journal::Journal::resume)), in which case the task never
+    /// reached the backend and there are no attempts.
+    pub fn is_cached(&self) -> bool {
+        self.cached
+    }
+}
+
+/// An error returned from a backend when a task does not complete
+/// successfully.
+///
+/// Errors are categorized by their cause so that callers can decide how to
+/// react to them (e.g., retrying transient errors but not failed executions).
+#[derive(Clone, Debug)]
+pub enum TaskError {
+    /// The task could not be submitted to the backend.
+    ///
+    /// This typically indicates a problem with the task or the backend
+    /// configuration, so resubmitting the task as-is is unlikely to succeed.
+    SubmissionFailed(String),
+
+    /// The backend could not be reached or failed while managing the task.
+    ///
+    /// This is typically transient (e.g., a network error or an overloaded
+    /// service).
+    BackendUnavailable(String),
+
+    /// An execution within the task exited unsuccessfully.
+    ExecutionFailed {
+        /// The exit code of the execution, if one was reported.
+        exit: Option<i32>,
+
+        /// The output of the failed execution.
+        output: Output,
+    },
+
+    /// The task was cancelled before it could complete.
+    Cancelled,
+
+    /// The task did not complete within its allotted time.
+    TimedOut,
+
+    /// An execution within the task ran out of memory.
+    OutOfMemory,
+
+    /// The resources running the task were preempted.
+    Preempted,
+}
+
+impl TaskError {
+    /// Creates an [`TaskError::ExecutionFailed`] from the output of an
+    /// execution.
+    pub(crate) fn execution_failed(output: Output) -> Self {
+        Self::ExecutionFailed {
+            exit: output.status.code(),
+            output,
+        }
+    }
+}
+
+impl std::fmt::Display for TaskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskError::SubmissionFailed(reason) => write!(f, "task submission failed: {reason}"),
+            TaskError::BackendUnavailable(reason) => write!(f, "backend unavailable: {reason}"),
+            TaskError::ExecutionFailed {
+                exit: Some(exit), ..
+            } => write!(f, "execution failed with exit code {exit}"),
+            TaskError::ExecutionFailed { exit: None, .. } => {
+                write!(f, "execution failed without an exit code")
+            }
+            TaskError::Cancelled => write!(f, "task was cancelled"),
+            TaskError::TimedOut => write!(f, "task timed out"),
+            TaskError::OutOfMemory => write!(f, "task ran out of memory"),
+            TaskError::Preempted => write!(f, "task was preempted"),
+        }
+    }
+}
+
+impl std::error::Error for TaskError {}
+
+/// The point at which to reattach to work submitted by a previous run.
+#[derive(Clone, Debug)]
+pub struct Reattach {
+    /// The index of the execution the work was submitted for.
+    pub execution: usize,
+
+    /// The identifier the backend reported for the work (e.g., a job id).
+    pub id: String,
+}
+
+/// A rendering of what a backend would submit for a task (see
+/// [`Backend::plan()`]).
+#[derive(Clone, Debug)]
+pub struct Plan {
+    /// The default name of the backend.
+    backend: &'static str,
+
+    /// What would be submitted (in order).
+    submissions: Vec<String>,
+}
+
+impl Plan {
+    /// Creates a new [`Plan`].
+    pub(crate) fn new(backend: &'static str, submissions: Vec<String>) -> Self {
+        Self {
+            backend,
+            submissions,
+        }
+    }
+
+    /// Gets the default name of the backend.
+    pub fn backend(&self) -> &str {
+        self.backend
+    }
+
+    /// Gets what would be submitted (in order).
+    ///
+    /// What each submission is depends on the backend: a command for generic
+    /// backends and a JSON document for the Docker and TES backends.
+    pub fn submissions(&self) -> &[String] {
+        &self.submissions
+    }
+}
+
+impl std::fmt::Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, submission) in self.submissions.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+
+            writeln!(f, "{submission}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Completes once `timeout` has elapsed (or never, if there is no timeout).
+///
+/// Backends use this to enforce [execution
+/// timeouts](crate::task::Execution::timeout).
+pub(crate) async fn expired(timeout: Option<Duration>) {
+    match timeout {
+        Some(timeout) => tokio::time::sleep(timeout).await,
+        None => std::future::pending().await,
+    }
 }
 
 /// An execution backend.
@@ -33,6 +268,63 @@ pub trait Backend: Debug + Send + Sync + 'static {
     /// Gets the default name for the backend.
     fn default_name(&self) -> &'static str;
 
+    /// Returns whether the backend runs executions within their container
+    /// images.
+    ///
+    /// Backends that don't (e.g., ones that run commands directly on a host)
+    /// are never sent [routed](crate::Engine::submit_routed) tasks that
+    /// [require a container](Task::requires_container).
+    fn containers(&self) -> bool {
+        true
+    }
+
     /// Runs a task in a backend.
-    fn run(&self, task: Task) -> BoxFuture<'static, TaskResult>;
+    ///
+    /// The backend reports the transitions it observes (e.g., a job being
+    /// submitted) through the `emitter`.
+    ///
+    /// When `token` is cancelled, the backend is expected to stop any work it
+    /// has started for the task (e.g., killing a submitted job or stopping a
+    /// running container) and return [`TaskError::Cancelled`]. This is also
+    /// how the runner enforces [task timeouts](Task::timeout).
+    ///
+    /// The backend is responsible for enforcing [execution
+    /// timeouts](crate::task::Execution::timeout) by stopping the work in the
+    /// same way and returning [`TaskError::TimedOut`].
+    fn run(
+        &self,
+        task: Task,
+        emitter: Emitter,
+        token: CancellationToken,
+    ) -> BoxFuture<'static, Result<TaskResult, TaskError>>;
+
+    /// Reattaches to a task that was submitted to the backend by a previous
+    /// run (e.g., one that crashed) instead of running it again.
+    ///
+    /// The returned future behaves exactly like the one returned from
+    /// [`run()`](Self::run), picking up from the work identified by
+    /// `reattach`. Backends that cannot reattach return [`None`] (the
+    /// default), in which case the task is run again from scratch.
+    fn reattach(
+        &self,
+        task: Task,
+        reattach: Reattach,
+        emitter: Emitter,
+        token: CancellationToken,
+    ) -> Option<BoxFuture<'static, Result<TaskResult, TaskError>>> {
+        let _ = (task, reattach, emitter, token);
+        None
+    }
+
+    /// Renders what the backend would submit for a task without submitting
+    /// anything (i.e., a dry run).
+    ///
+    /// Any placeholders (e.g., the resources in a generic backend's submit
+    /// command) are resolved exactly as they would be when running the task.
+    /// Backends that cannot render their submissions return [`None`] (the
+    /// default).
+    fn plan(&self, task: &Task) -> Option<Result<Plan, TaskError>> {
+        let _ = task;
+        None
+    }
 }
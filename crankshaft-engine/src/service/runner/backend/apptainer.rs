@@ -0,0 +1,692 @@
+//! An Apptainer (formerly Singularity) backend.
+//!
+//! The Apptainer backend runs each execution within its container image on
+//! the local host using `apptainer exec`. Images are referred to as they are
+//! for Docker: a reference without a scheme (e.g., `ubuntu:22.04`) is pulled
+//! from a Docker registry (i.e., as `docker://ubuntu:22.04`), references with
+//! a scheme (e.g., `docker://`, `library://`, or `oras://`) are pulled as-is,
+//! and paths to SIF files are used directly. Pulled images are cached as SIF
+//! files (see [`Config::cache()`]) so that each image is only pulled once.
+//!
+//! Every task gets a working directory of its own on the host, from which the
+//! following are bind-mounted into the container:
+//!
+//! * Inputs (read-only). Inputs backed by local files are mounted directly, and
+//!   all other inputs are fetched into the working directory first.
+//! * Outputs, which are copied to their URLs once every execution succeeds.
+//! * Shared volumes.
+//!
+//! Containers are run with `--containall`, so nothing from the host (e.g., its
+//! environment, the user's home directory, or `/tmp`) is available within them
+//! other than those mounts. The environment variables of an execution are
+//! passed to the container through `APPTAINERENV_` variables (or
+//! `SINGULARITYENV_` ones when the executable is `singularity`).
+//!
+//! The standard stream files of an execution must be within one of the
+//! mounted paths. The standard output and error are written directly to their
+//! files (when configured), in which case they aren't captured in the
+//! execution's output.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread::available_parallelism;
+
+use crankshaft_config::backend::Capacity;
+use crankshaft_config::backend::apptainer::Config;
+use futures::FutureExt as _;
+use futures::future::BoxFuture;
+use nonempty::NonEmpty;
+use sha2::Digest as _;
+use sha2::Sha256;
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use crate::Task;
+use crate::service::runner::backend::TaskError;
+use crate::service::runner::backend::TaskResult;
+use crate::service::runner::backend::expired;
+use crate::service::runner::backend::local::copy;
+use crate::service::runner::backend::local::create_dir;
+use crate::service::runner::backend::local::kill;
+use crate::service::runner::backend::local::stream;
+use crate::service::runner::backend::local::targets;
+use crate::service::runner::event::Emitter;
+use crate::task::Execution;
+use crate::task::input::Contents;
+use crate::task::output::Type;
+
+/// The name of the directory (within the user's cache directory) that images
+/// are cached in by default.
+pub const DEFAULT_CACHE_DIRECTORY: &str = "crankshaft/sif";
+
+/// The source of an image.
+#[derive(Debug, Eq, PartialEq)]
+enum Source {
+    /// A SIF file on the host.
+    File(PathBuf),
+
+    /// An image to pull (by its URI).
+    Uri(String),
+}
+
+impl Source {
+    /// Gets the source of an image from its reference.
+    fn new(image: &str) -> Self {
+        if image.ends_with(".sif") && !image.contains("://") {
+            Self::File(PathBuf::from(image))
+        } else if image.contains("://") {
+            Self::Uri(image.to_owned())
+        } else {
+            Self::Uri(format!("docker://{image}"))
+        }
+    }
+}
+
+/// A partially pulled image.
+///
+/// The file is removed when this is dropped (including when a pull is
+/// abandoned), unless it has been moved into the cache by then.
+#[derive(Debug)]
+struct Partial(PathBuf);
+
+impl Drop for Partial {
+    fn drop(&mut self) {
+        // NOTE: once the image has been moved into the cache, this fails (as
+        // the file no longer exists), which is expected.
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// A cache of pulled images.
+#[derive(Debug)]
+struct Images {
+    /// The executable used to pull images.
+    executable: String,
+
+    /// The directory images are cached in.
+    root: PathBuf,
+
+    /// A lock for each image (so that concurrent tasks don't pull the same
+    /// image more than once).
+    pulls: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl Images {
+    /// Gets the path to the SIF file for an image, pulling it if it isn't
+    /// cached yet.
+    async fn get(&self, image: &str) -> Result<PathBuf, TaskError> {
+        let uri = match Source::new(image) {
+            Source::File(path) => return Ok(path),
+            Source::Uri(uri) => uri,
+        };
+
+        let path = self
+            .root
+            .join(format!("{:x}.sif", Sha256::digest(uri.as_bytes())));
+
+        let lock = self
+            .pulls
+            .lock()
+            .unwrap()
+            .entry(uri.clone())
+            .or_default()
+            .clone();
+        let _guard = lock.lock().await;
+
+        if tokio::fs::try_exists(&path).await.unwrap_or_default() {
+            return Ok(path);
+        }
+
+        debug!("pulling `{uri}` to `{}`", path.display());
+        tokio::fs::create_dir_all(&self.root).await.map_err(|err| {
+            TaskError::BackendUnavailable(format!(
+                "creating image cache `{}`: {err}",
+                self.root.display()
+            ))
+        })?;
+
+        // NOTE: images are pulled to a temporary file first so that an
+        // interrupted pull never leaves a partial image in the cache.
+        let partial = Partial(path.with_extension(format!("{}.partial.sif", uuid::Uuid::new_v4())));
+        let output = Command::new(&self.executable)
+            .arg("pull")
+            .arg(&partial.0)
+            .arg(&uri)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|err| {
+                TaskError::BackendUnavailable(format!("running `{}`: {err}", self.executable))
+            })?;
+
+        if !output.status.success() {
+            return Err(TaskError::SubmissionFailed(format!(
+                "pulling `{uri}`: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        tokio::fs::rename(&partial.0, &path)
+            .await
+            .map_err(|err| TaskError::BackendUnavailable(format!("caching `{uri}`: {err}")))?;
+
+        Ok(path)
+    }
+}
+
+/// A bind mount of a path on the host into a container.
+#[derive(Debug)]
+struct Bind {
+    /// The path on the host.
+    source: PathBuf,
+
+    /// The path within the container.
+    target: String,
+
+    /// Whether the mount is read-only.
+    read_only: bool,
+}
+
+impl Bind {
+    /// Creates a new [`Bind`].
+    ///
+    /// Fails if either path contains a character that is reserved by the
+    /// syntax of bind mounts.
+    fn new(source: PathBuf, target: &str, read_only: bool) -> Result<Self, TaskError> {
+        let reserved = |path: &str| path.contains([':', ',']);
+
+        if reserved(&source.to_string_lossy()) || reserved(target) {
+            return Err(TaskError::SubmissionFailed(format!(
+                "cannot mount `{}` at `{target}`: paths must not contain `:` or `,`",
+                source.display()
+            )));
+        }
+
+        Ok(Self {
+            source,
+            target: target.to_owned(),
+            read_only,
+        })
+    }
+}
+
+impl fmt::Display for Bind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.source.display(), self.target)?;
+
+        if self.read_only {
+            write!(f, ":ro")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Maps a path within a container to the path on the host it is mounted from
+/// (if it is within one of the mounts).
+///
+/// When mounts are nested, the innermost one is used.
+fn host_path(binds: &[Bind], path: &str) -> Option<PathBuf> {
+    binds
+        .iter()
+        .filter_map(|bind| {
+            let rest = Path::new(path).strip_prefix(&bind.target).ok()?;
+            Some((Path::new(&bind.target).components().count(), bind, rest))
+        })
+        .max_by_key(|(depth, ..)| *depth)
+        .map(|(_, bind, rest)| {
+            if rest.as_os_str().is_empty() {
+                bind.source.clone()
+            } else {
+                bind.source.join(rest)
+            }
+        })
+}
+
+/// Maps a standard stream file within a container to the path on the host it
+/// is mounted from, failing the task if it isn't mounted.
+fn stream_path(binds: &[Bind], path: Option<&String>) -> Result<Option<PathBuf>, TaskError> {
+    path.map(|path| {
+        host_path(binds, path).ok_or_else(|| {
+            TaskError::SubmissionFailed(format!(
+                "`{path}` is not within an input, output, or shared volume"
+            ))
+        })
+    })
+    .transpose()
+}
+
+/// An Apptainer backend.
+#[derive(Debug)]
+pub struct Backend {
+    /// Configuration for the backend.
+    config: Config,
+
+    /// The cache of pulled images.
+    images: Arc<Images>,
+}
+
+impl Backend {
+    /// Creates a new Apptainer [`Backend`].
+    ///
+    /// Unless configured otherwise, images are cached within the [default
+    /// directory](DEFAULT_CACHE_DIRECTORY) of the user's cache directory (or
+    /// of the temporary directory of the system, if the user's cache directory
+    /// cannot be determined).
+    pub fn new(config: Config) -> Self {
+        let root = config.cache().map(ToOwned::to_owned).unwrap_or_else(|| {
+            dirs::cache_dir()
+                .unwrap_or_else(std::env::temp_dir)
+                .join(DEFAULT_CACHE_DIRECTORY)
+        });
+
+        Self {
+            images: Arc::new(Images {
+                executable: config.executable().to_owned(),
+                root,
+                pulls: Default::default(),
+            }),
+            config,
+        }
+    }
+
+    /// Detects the total resources of the host.
+    ///
+    /// Only the number of CPUs is detected (the RAM and disk are left
+    /// unlimited).
+    pub fn capacity(&self) -> Capacity {
+        let cpu = available_parallelism().ok().map(usize::from);
+        Capacity::new(cpu, None, None)
+    }
+}
+
+impl crate::Backend for Backend {
+    fn default_name(&self) -> &'static str {
+        "apptainer"
+    }
+
+    fn run(
+        &self,
+        task: Task,
+        emitter: Emitter,
+        token: CancellationToken,
+    ) -> BoxFuture<'static, Result<TaskResult, TaskError>> {
+        let config = self.config.clone();
+        let images = self.images.clone();
+
+        async move {
+            let root = config
+                .root()
+                .map(ToOwned::to_owned)
+                .unwrap_or_else(std::env::temp_dir);
+            let dir = create_dir(&root).await.map_err(|err| {
+                TaskError::BackendUnavailable(format!(
+                    "creating working directory within `{}`: {err}",
+                    root.display()
+                ))
+            })?;
+
+            // NOTE: `into_path()` causes the directory to no longer be removed
+            // when the [`TempDir`](tempfile::TempDir) is dropped.
+            let (path, _guard) = if config.cleanup() {
+                (dir.path().to_owned(), Some(dir))
+            } else {
+                (dir.into_path(), None)
+            };
+
+            debug!("running task within `{}`", path.display());
+            let result = run(&task, &path, &config, &images, &emitter, &token).await;
+
+            // NOTE: the result is bound so that it is computed before the
+            // working directory is removed.
+            result
+        }
+        .boxed()
+    }
+}
+
+/// Prepares the mounts of a task within its working directory.
+async fn mounts(task: &Task, dir: &Path) -> Result<Vec<Bind>, TaskError> {
+    let mut binds = Vec::new();
+
+    for (i, input) in task.inputs().into_iter().flatten().enumerate() {
+        let source = match input.contents() {
+            Contents::URL(url) if url.scheme() == "file" => url
+                .to_file_path()
+                .map_err(|_| TaskError::SubmissionFailed(format!("invalid file URL: {url}")))?,
+            _ => {
+                let source = dir.join("inputs").join(i.to_string());
+                let result = match input.fetch().await {
+                    Ok(contents) => tokio::fs::create_dir_all(dir.join("inputs"))
+                        .await
+                        .and(tokio::fs::write(&source, contents).await),
+                    Err(err) => Err(err),
+                };
+
+                result.map_err(|err| {
+                    TaskError::SubmissionFailed(format!(
+                        "localizing input `{}`: {err}",
+                        input.path()
+                    ))
+                })?;
+
+                source
+            }
+        };
+
+        binds.push(Bind::new(source, input.path(), true)?);
+    }
+
+    for (i, output) in task.outputs().into_iter().flatten().enumerate() {
+        let source = dir.join("outputs").join(i.to_string());
+
+        // NOTE: the source of a mount must exist, so an empty file (or
+        // directory) is created for the output to be written to.
+        let result = match output.r#type() {
+            Type::File => match tokio::fs::create_dir_all(dir.join("outputs")).await {
+                Ok(()) => tokio::fs::write(&source, b"").await,
+                Err(err) => Err(err),
+            },
+            Type::Directory => tokio::fs::create_dir_all(&source).await,
+        };
+
+        result.map_err(|err| {
+            TaskError::SubmissionFailed(format!("creating output `{}`: {err}", output.path()))
+        })?;
+
+        binds.push(Bind::new(source, output.path(), false)?);
+    }
+
+    for (i, volume) in task.shared_volumes().into_iter().flatten().enumerate() {
+        let source = dir.join("volumes").join(i.to_string());
+
+        tokio::fs::create_dir_all(&source).await.map_err(|err| {
+            TaskError::SubmissionFailed(format!("creating shared volume `{volume}`: {err}"))
+        })?;
+
+        binds.push(Bind::new(source, volume, false)?);
+    }
+
+    Ok(binds)
+}
+
+/// Builds the command for an execution of a task.
+fn command(
+    config: &Config,
+    image: &Path,
+    execution: &Execution,
+    binds: &[Bind],
+) -> Result<Command, TaskError> {
+    let stdin = match stream_path(binds, execution.stdin())? {
+        Some(path) => std::fs::File::open(&path).map(Stdio::from).map_err(|err| {
+            TaskError::SubmissionFailed(format!("opening `{}`: {err}", path.display()))
+        })?,
+        None => Stdio::null(),
+    };
+
+    let stdout = match stream_path(binds, execution.stdout())? {
+        Some(path) => stream(&path)?,
+        None => Stdio::piped(),
+    };
+
+    let stderr = match stream_path(binds, execution.stderr())? {
+        Some(path) => stream(&path)?,
+        None => Stdio::piped(),
+    };
+
+    let mut command = Command::new(config.executable());
+    command.arg("exec").arg("--containall");
+
+    if let Some(workdir) = execution.workdir() {
+        command.arg("--pwd").arg(workdir);
+    }
+
+    for bind in binds {
+        command.arg("--bind").arg(bind.to_string());
+    }
+
+    command
+        .arg(image)
+        .args(execution.args())
+        .stdin(stdin)
+        .stdout(stdout)
+        .stderr(stderr)
+        .kill_on_drop(true);
+
+    #[cfg(unix)]
+    command.process_group(0);
+
+    // NOTE: passing environment variables this way (rather than with `--env`)
+    // means that their values may contain commas.
+    let singularity = Path::new(config.executable())
+        .file_name()
+        .is_some_and(|name| name == "singularity");
+    let prefix = if singularity {
+        "SINGULARITYENV_"
+    } else {
+        "APPTAINERENV_"
+    };
+
+    for (name, value) in execution.env().into_iter().flatten() {
+        command.env(format!("{prefix}{name}"), value);
+    }
+
+    Ok(command)
+}
+
+/// Runs a task within its working directory.
+async fn run(
+    task: &Task,
+    dir: &Path,
+    config: &Config,
+    images: &Images,
+    emitter: &Emitter,
+    token: &CancellationToken,
+) -> Result<TaskResult, TaskError> {
+    // NOTE: the outputs are checked up front so that a task whose outputs
+    // can't be collected fails before anything runs.
+    let targets = targets(task)?;
+
+    // (1) Prepare the mounts.
+    let binds = mounts(task, dir).await?;
+    let mut outputs = Vec::new();
+
+    for execution in task.executions() {
+        if token.is_cancelled() {
+            return Err(TaskError::Cancelled);
+        }
+
+        // (2) Pull the image (if it isn't cached).
+        let image = tokio::select! {
+            image = images.get(execution.image()) => image?,
+            _ = token.cancelled() => return Err(TaskError::Cancelled),
+        };
+
+        // (3) Start the container.
+        emitter.submitting().await;
+        let child = command(config, &image, execution, &binds)?
+            .spawn()
+            .map_err(|err| {
+                TaskError::BackendUnavailable(format!("running `{}`: {err}", config.executable()))
+            })?;
+
+        let group = child.id();
+        emitter.submitted(group.map(|id| id.to_string()));
+        emitter.running();
+
+        // NOTE: the container's process is killed if it is abandoned (i.e.,
+        // when the future waiting on it is dropped), and the rest of its
+        // process group is killed explicitly.
+        let output = tokio::select! {
+            output = child.wait_with_output() => output.map_err(|err| {
+                TaskError::BackendUnavailable(format!("waiting on container: {err}"))
+            })?,
+            _ = token.cancelled() => {
+                kill(group);
+                return Err(TaskError::Cancelled);
+            }
+            _ = expired(execution.timeout()) => {
+                kill(group);
+                return Err(TaskError::TimedOut);
+            }
+        };
+
+        if !output.status.success() {
+            return Err(TaskError::execution_failed(output));
+        }
+
+        outputs.push(output);
+    }
+
+    // (4) Collect the outputs.
+    let outputs_and_targets = task.outputs().into_iter().flatten().zip(targets);
+    for (i, (output, target)) in outputs_and_targets.enumerate() {
+        let source = dir.join("outputs").join(i.to_string());
+
+        // SAFETY: the closure never panics, so this will always unwrap.
+        tokio::task::spawn_blocking(move || copy(&source, &target))
+            .await
+            .unwrap()
+            .map_err(|err| {
+                TaskError::SubmissionFailed(format!("collecting output `{}`: {err}", output.path()))
+            })?;
+    }
+
+    let mut outputs = outputs.into_iter();
+
+    // SAFETY: each task _must_ have at least one execution, so at least one
+    // output _must_ exist at this stage. Thus, this will always unwrap.
+    let mut executions = NonEmpty::new(outputs.next().unwrap());
+    executions.extend(outputs);
+
+    Ok(TaskResult::new(executions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn images_are_translated() {
+        assert_eq!(
+            Source::new("ubuntu:22.04"),
+            Source::Uri(String::from("docker://ubuntu:22.04"))
+        );
+        assert_eq!(
+            Source::new("library://alpine"),
+            Source::Uri(String::from("library://alpine"))
+        );
+        assert_eq!(
+            Source::new("/images/alpine.sif"),
+            Source::File(PathBuf::from("/images/alpine.sif"))
+        );
+    }
+
+    #[test]
+    fn abandoned_pulls_are_removed() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("image.partial.sif");
+        std::fs::write(&path, b"").unwrap();
+
+        drop(Partial(path.clone()));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn paths_map_to_the_innermost_mount() {
+        let binds = [
+            Bind::new(PathBuf::from("/host/volume"), "/data", false).unwrap(),
+            Bind::new(PathBuf::from("/host/output"), "/data/out", false).unwrap(),
+        ];
+
+        assert_eq!(
+            host_path(&binds, "/data/out/log.txt").unwrap(),
+            Path::new("/host/output/log.txt")
+        );
+        assert_eq!(
+            host_path(&binds, "/data/in.txt").unwrap(),
+            Path::new("/host/volume/in.txt")
+        );
+        assert!(host_path(&binds, "/other/in.txt").is_none());
+        assert!(Bind::new(PathBuf::from("/host/a,b"), "/data", false).is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn images_are_pulled_once() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        use crate::service::Runner;
+
+        // NOTE: a stand-in for Apptainer that records each pull and the
+        // arguments of each execution (and runs the command of each execution
+        // directly on the host).
+        let dir = tempfile::TempDir::new().unwrap();
+        let executable = dir.path().join("apptainer");
+        std::fs::write(
+            &executable,
+            format!(
+                r#"#!/bin/sh
+if [ "$1" = pull ]; then echo "$3" >> {dir}/pulls; touch "$2"; exit; fi
+shift
+echo "$@" >> {dir}/execs
+while :; do
+    case "$1" in
+        --containall) shift ;;
+        --*) shift 2 ;;
+        *) break ;;
+    esac
+done
+shift
+exec "$@"
+"#,
+                dir = dir.path().display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&executable, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config = Config::builder()
+            .executable(executable.to_string_lossy())
+            .cache(dir.path().join("cache"))
+            .build();
+        let runner = Runner::new(Arc::new(Backend::new(config)), 2);
+
+        let task = || {
+            Task::builder()
+                .extend_executions([Execution::builder()
+                    .image("alpine")
+                    .args(["echo", "hello"])
+                    .try_build()
+                    .unwrap()])
+                .try_build()
+                .unwrap()
+        };
+
+        let handles = [
+            runner.submit(task()).unwrap(),
+            runner.submit(task()).unwrap(),
+        ];
+        for handle in handles {
+            let result = handle.callback.await.unwrap().unwrap();
+            assert_eq!(result.executions().first().stdout, b"hello\n");
+        }
+
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("pulls")).unwrap(),
+            "docker://alpine\n"
+        );
+
+        // NOTE: nothing from the host is passed into the container other than
+        // the explicit mounts.
+        let execs = std::fs::read_to_string(dir.path().join("execs")).unwrap();
+        assert!(execs.lines().all(|exec| exec.starts_with("--containall ")));
+    }
+}
@@ -4,8 +4,11 @@ use async_trait::async_trait;
 use bollard::secret::HostConfig;
 use bollard::secret::Mount;
 use bollard::secret::MountTypeEnum;
+use crankshaft_config::backend::Capacity;
 use crankshaft_config::backend::docker::Config;
+use crankshaft_docker::Container;
 use crankshaft_docker::Docker;
+use crankshaft_docker::container::Builder;
 use eyre::Context;
 use futures::FutureExt;
 use futures::StreamExt;
@@ -13,10 +16,17 @@ use futures::future::BoxFuture;
 use futures::stream::FuturesUnordered;
 use nonempty::NonEmpty;
 use tempfile::TempDir;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
 
 use crate::Result;
 use crate::Task;
+use crate::service::runner::backend::Plan;
+use crate::service::runner::backend::TaskError;
 use crate::service::runner::backend::TaskResult;
+use crate::service::runner::backend::expired;
+use crate::service::runner::event::Emitter;
+use crate::task::Execution;
 
 /// The working dir name inside the docker container
 pub const WORKDIR: &str = "/workdir";
@@ -53,6 +63,25 @@ impl Backend {
     pub fn initialize_default() -> Result<Self> {
         Self::initialize_default_with(Config::default())
     }
+
+    /// Detects the total resources of the host the Docker daemon runs on.
+    ///
+    /// Only the number of CPUs and the amount of RAM are detected (the disk
+    /// is left unlimited).
+    pub async fn capacity(&self) -> Result<Capacity> {
+        let info = self
+            .client
+            .info()
+            .await
+            .context("error getting information about the Docker daemon")?;
+
+        let cpu = info.ncpu.and_then(|cpu| usize::try_from(cpu).ok());
+        let ram = info
+            .mem_total
+            .map(|bytes| bytes as f64 / (1024. * 1024. * 1024.));
+
+        Ok(Capacity::new(cpu, ram, None))
+    }
 }
 
 #[async_trait]
@@ -61,121 +90,232 @@ impl crate::Backend for Backend {
         "docker"
     }
 
-    fn run(&self, task: Task) -> BoxFuture<'static, TaskResult> {
-        run(self, task)
+    fn run(
+        &self,
+        task: Task,
+        emitter: Emitter,
+        token: CancellationToken,
+    ) -> BoxFuture<'static, std::result::Result<TaskResult, TaskError>> {
+        run(self, task, emitter, token)
+    }
+
+    /// Renders the configuration of the container created for each execution
+    /// (as JSON).
+    ///
+    /// The shared volumes of the task are mounted from temporary directories
+    /// that are only created when the task runs, so their sources are left as
+    /// placeholders.
+    fn plan(&self, task: &Task) -> Option<std::result::Result<Plan, TaskError>> {
+        let mounts = task.shared_volumes().map(|volumes| {
+            volumes
+                .map(|target| shared_mount(target, String::from("<temporary directory>")))
+                .collect::<Vec<_>>()
+        });
+
+        let submissions = task
+            .executions()
+            .map(|execution| {
+                let config = builder(&self.client, task, execution, mounts.clone()).to_config();
+
+                serde_json::to_string_pretty(&serde_json::json!({
+                    "name": task.name(),
+                    "config": config,
+                }))
+                .map_err(|err| TaskError::SubmissionFailed(format!("rendering container: {err}")))
+            })
+            .collect::<std::result::Result<Vec<_>, _>>();
+
+        Some(submissions.map(|submissions| Plan::new(self.default_name(), submissions)))
+    }
+}
+
+/// Creates a bind mount of a shared volume.
+fn shared_mount(target: &str, source: String) -> Mount {
+    Mount {
+        target: Some(target.to_owned()),
+        source: Some(source),
+        typ: Some(MountTypeEnum::BIND),
+        read_only: Some(false),
+        ..Default::default()
+    }
+}
+
+/// Builds the container for an execution of a task.
+fn builder(
+    client: &Docker,
+    task: &Task,
+    execution: &Execution,
+    mounts: Option<Vec<Mount>>,
+) -> Builder {
+    let builder = client
+        .container_builder()
+        .image(execution.image())
+        .command(
+            execution
+                .args()
+                .into_iter()
+                .map(|s| s.to_owned())
+                .collect::<Vec<_>>(),
+        )
+        .attached(true)
+        .host_config(HostConfig {
+            mounts,
+            ..task.resources().map(HostConfig::from).unwrap_or_default()
+        });
+
+    match execution.workdir() {
+        Some(workdir) => builder.workdir(workdir.to_owned()),
+        None => builder,
     }
 }
 
 /// Gets the shared mounts (if any exist) from the shared volumes in a [`Task`]
 /// (via [`Task::shared_volumes()`]).
-fn get_shared_mounts<'a>(volumes: Option<impl Iterator<Item = &'a str>>) -> Option<Vec<Mount>> {
-    volumes.map(|iter| {
-        iter.map(|inner_path| {
-            Mount {
-                target: Some(inner_path.to_owned()),
-                source: Some(
-                    TempDir::new()
-                        // SAFETY: for now, this is essentially a workaround to the fact
-                        // that we do not return a [`Result`] in the `run()` method. It's
-                        // certainly possible for this to fail, but I feel it's unlikely
-                        // enough to occur in early development that handling this properly
-                        // can be elided for now.
-                        //
-                        // TODO(clay): more properly handle this later.
-                        .expect("could not initialize tempdir")
-                        // NOTE: this is *required* because it causes the
-                        // temporary directory to no longer be dropped when the
-                        // [`TempDir`] goes out of scope. In other words, simply
-                        // referring to [`path()`] isn't sufficient (even though
-                        // it would suit our purposes from the perspective of
-                        // getting a [`str`] representation).
-                        .into_path()
-                        .to_str()
-                        // SAFETY: essentially the above reasoning—it's unlikely
-                        // that this will fail in early testing, but we should
-                        // come back to more properly handling this later.
-                        //
-                        // TODO(clay): more properly handle this later.
-                        .unwrap()
-                        .to_owned(),
-                ),
-                typ: Some(MountTypeEnum::BIND),
-                read_only: Some(false),
-                ..Default::default()
-            }
+fn get_shared_mounts<'a>(
+    volumes: Option<impl Iterator<Item = &'a str>>,
+) -> std::io::Result<Option<Vec<Mount>>> {
+    volumes
+        .map(|iter| {
+            iter.map(|inner_path| {
+                // NOTE: `into_path()` is *required* because it causes the
+                // temporary directory to no longer be dropped when the
+                // [`TempDir`] goes out of scope. In other words, simply
+                // referring to [`path()`] isn't sufficient (even though it
+                // would suit our purposes from the perspective of getting a
+                // [`str`] representation).
+                let source = TempDir::new()?.into_path();
+                Ok(shared_mount(
+                    inner_path,
+                    source.to_string_lossy().into_owned(),
+                ))
+            })
+            .collect::<std::io::Result<Vec<_>>>()
         })
-        .collect::<Vec<_>>()
-    })
+        .transpose()
+}
+
+/// Categorizes an error returned from the Docker daemon.
+///
+/// Errors the daemon reports as the fault of the request (e.g., a missing
+/// image) are considered submission failures. Everything else (e.g., the
+/// daemon not being reachable) is considered the backend being unavailable.
+fn categorize(err: crankshaft_docker::Error, context: &str) -> TaskError {
+    let crankshaft_docker::Error::Docker(inner) = &err;
+
+    match inner {
+        bollard::errors::Error::DockerResponseServerError { status_code, .. }
+            if *status_code < 500 =>
+        {
+            TaskError::SubmissionFailed(format!("{context}: {err}"))
+        }
+        _ => TaskError::BackendUnavailable(format!("{context}: {err}")),
+    }
 }
 
 /// Runs a task using the Docker backend.
-fn run(backend: &Backend, task: Task) -> BoxFuture<'static, TaskResult> {
+fn run(
+    backend: &Backend,
+    task: Task,
+    emitter: Emitter,
+    token: CancellationToken,
+) -> BoxFuture<'static, std::result::Result<TaskResult, TaskError>> {
     let client = backend.client.clone();
     let cleanup = backend.config.cleanup();
-    let mounts = get_shared_mounts(task.shared_volumes());
 
     async move {
+        let mounts = get_shared_mounts(task.shared_volumes()).map_err(|err| {
+            TaskError::SubmissionFailed(format!("creating shared volumes: {err}"))
+        })?;
+
+        // SAFETY: the runner always assigns a name to tasks destined for the
+        // Docker backend, so this will always unwrap.
+        let name = task.name().unwrap().to_owned();
         let mut outputs = Vec::new();
 
         for execution in task.executions() {
-            // (1) Create the container.
-            let mut builder = client
-                .container_builder()
-                .image(execution.image())
-                .command(
-                    execution
-                        .args()
-                        .into_iter()
-                        .map(|s| s.to_owned())
-                        .collect::<Vec<_>>(),
-                )
-                .attached(true)
-                .host_config(HostConfig {
-                    mounts: mounts.clone(),
-                    ..task.resources().map(HostConfig::from).unwrap_or_default()
-                });
-
-            if let Some(workdir) = execution.workdir() {
-                builder = builder.workdir(workdir.to_owned());
+            if token.is_cancelled() {
+                return Err(TaskError::Cancelled);
             }
 
-            let container = builder.try_create(&task.name().unwrap()).await.unwrap();
+            // (1) Create the container.
+            emitter.submitting().await;
+            let container = builder(&client, &task, execution, mounts.clone())
+                .try_create(&name)
+                .await
+                .map_err(|err| categorize(err, "creating container"))?;
+
+            emitter.submitted(Some(container.name().to_owned()));
 
             // (2) Upload inputs to the container.
             //
             // TODO(clay): these could be cached.
             if let Some(inputs) = task.inputs() {
-                let futures = inputs
+                let mut futures = inputs
                     .map(|input| async {
-                        let contents = input.fetch().await;
-                        container.upload_file(input.path(), contents).await
+                        let contents = input.fetch().await.map_err(|err| {
+                            TaskError::SubmissionFailed(format!(
+                                "fetching input `{}`: {err}",
+                                input.path()
+                            ))
+                        })?;
+
+                        container
+                            .upload_file(input.path(), contents)
+                            .await
+                            .map_err(|err| categorize(err, "uploading input"))
                     })
                     .collect::<FuturesUnordered<_>>();
 
-                // NOTE: this is just an unfancy way to evaluate all of the
-                // above futures.
-                //
-                // TODO(clay): make this more elegant.
-                futures.for_each(|_| async {}).await;
+                while let Some(result) = futures.next().await {
+                    if let Err(err) = result {
+                        drop(futures);
+                        remove(&container).await;
+                        return Err(err);
+                    }
+                }
             };
 
             // (3) Start the container.
-            let output = container.run().await.unwrap();
+            emitter.running();
+
+            let result = tokio::select! {
+                result = container.run() => result,
+                _ = token.cancelled() => {
+                    stop(&container).await;
+                    return Err(TaskError::Cancelled);
+                }
+                _ = expired(execution.timeout()) => {
+                    stop(&container).await;
+                    return Err(TaskError::TimedOut);
+                }
+            };
+
+            let result = match result {
+                Ok(output) if output.status.success() => Ok(output),
+                Ok(output) => {
+                    let oom_killed = container
+                        .inspect()
+                        .await
+                        .ok()
+                        .and_then(|inspect| inspect.state)
+                        .and_then(|state| state.oom_killed)
+                        .unwrap_or_default();
+
+                    if oom_killed {
+                        Err(TaskError::OutOfMemory)
+                    } else {
+                        Err(TaskError::execution_failed(output))
+                    }
+                }
+                Err(err) => Err(categorize(err, "running container")),
+            };
 
             // (4) Cleanup the container (if desired).
             if cleanup {
-                container
-                    .remove()
-                    .await
-                    // SAFETY: this should always unwrap for now, but we should
-                    // revisit this in the future to more elegantly handle the
-                    // situation.
-                    //
-                    // TODO(clay): more elegantly handle this situation.
-                    .unwrap();
+                remove(&container).await;
             }
 
-            outputs.push(output);
+            outputs.push(result?);
         }
 
         let mut outputs = outputs.into_iter();
@@ -185,7 +325,30 @@ fn run(backend: &Backend, task: Task) -> BoxFuture<'static, TaskResult> {
         let mut executions = NonEmpty::new(outputs.next().unwrap());
         executions.extend(outputs);
 
-        TaskResult { executions }
+        Ok(TaskResult::new(executions))
     }
     .boxed()
 }
+
+/// Stops and removes a container whose execution is being abandoned (e.g.,
+/// because the task was cancelled or timed out).
+///
+/// Such a container is always removed, as there is no completed execution to
+/// inspect.
+async fn stop(container: &Container) {
+    if let Err(err) = container.stop().await {
+        warn!("failed to stop container: {err}");
+    }
+
+    remove(container).await;
+}
+
+/// Forcibly removes a container.
+///
+/// Failures are logged rather than returned, as a container that cannot be
+/// removed does not affect the outcome of the task.
+async fn remove(container: &Container) {
+    if let Err(err) = container.force_remove().await {
+        warn!("failed to remove container: {err}");
+    }
+}
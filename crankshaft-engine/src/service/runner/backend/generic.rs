@@ -3,6 +3,9 @@
 //! Generic backends are intended to be relatively maleable and configurable by
 //! the end user without requiring the need to write Rust code.
 
+use std::collections::HashMap;
+use std::process::ExitStatus;
+use std::process::Output;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -13,12 +16,20 @@ use futures::FutureExt;
 use futures::future::BoxFuture;
 use nonempty::NonEmpty;
 use regex::Regex;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
 use tracing::warn;
 
 use crate::Result;
 use crate::Task;
+use crate::service::runner::backend::Plan;
+use crate::service::runner::backend::Reattach;
+use crate::service::runner::backend::TaskError;
 use crate::service::runner::backend::TaskResult;
+use crate::service::runner::backend::expired;
 use crate::service::runner::backend::generic::driver::Driver;
+use crate::service::runner::event::Emitter;
+use crate::task::Execution;
 use crate::task::Resources;
 
 pub mod driver;
@@ -37,6 +48,9 @@ pub struct Backend {
 
     /// The execution defaults.
     defaults: Option<Defaults>,
+
+    /// The compiled job id regex (if one is configured).
+    job_id_regex: Option<Regex>,
 }
 
 impl Backend {
@@ -48,10 +62,17 @@ impl Backend {
             .await
             .map(Arc::new)?;
 
+        let job_id_regex = config
+            .job_id_regex()
+            .map(Regex::new)
+            .transpose()
+            .context("compiling job id regex")?;
+
         Ok(Self {
             driver,
             config,
             defaults,
+            job_id_regex,
         })
     }
 
@@ -66,24 +87,18 @@ impl Backend {
     }
 
     /// Resolves the resources for a particular task.
-    // NOTE: first, the default resources from the code are assumed. Then, the
-    // default resources from the configuration are applied (if they are
-    // provided). Last, the resources from the execution itself are applied.
-    // This is the relative level of priority resource resolution should have,
-    // and the order is important to preserve.
+    // NOTE: the default resources from the code are assumed before the
+    // configured defaults and the task's resources are applied.
     fn resolve_resources(&self, task: Option<&Resources>) -> Option<Resources> {
-        let mut resources: Option<Resources> = None;
-
-        if let Some(defaults) = &self.defaults {
-            let defaults = Resources::from(defaults);
-            resources = Some(resources.unwrap_or_default().apply(&defaults));
-        }
-
-        if let Some(task) = task {
-            resources = Some(resources.unwrap_or_default().apply(task));
-        }
+        Resources::resolve(Resources::default(), self.defaults.as_ref(), task)
+    }
 
-        resources
+    /// Gets the substitutions shared by every execution of a task (i.e., its
+    /// resolved resources).
+    fn default_substitutions(&self, task: &Task) -> HashMap<String, String> {
+        self.resolve_resources(task.resources())
+            .and_then(|resources| resources.to_hashmap())
+            .unwrap_or_default()
     }
 }
 
@@ -93,112 +108,484 @@ impl crate::Backend for Backend {
         "generic"
     }
 
+    /// Returns whether the backend runs executions within their container
+    /// images (it doesn't).
+    fn containers(&self) -> bool {
+        false
+    }
+
     /// Runs a task in a backend.
-    fn run(&self, task: Task) -> BoxFuture<'static, TaskResult> {
-        let driver = self.driver.clone();
-        let config = self.config.clone();
+    fn run(
+        &self,
+        task: Task,
+        emitter: Emitter,
+        token: CancellationToken,
+    ) -> BoxFuture<'static, std::result::Result<TaskResult, TaskError>> {
+        run(self, task, emitter, token, None)
+    }
 
-        let default_substitutions = self
-            .resolve_resources(task.resources())
-            .and_then(|resources| resources.to_hashmap())
-            .unwrap_or_default();
-
-        async move {
-            let mut outputs = Vec::new();
-            let job_id_regex = config.job_id_regex().map(|pattern| {
-                Regex::new(pattern)
-                    .context("compiling job id regex")
-                    .unwrap()
-            });
-
-            for execution in task.executions() {
-                // TODO(clay): this will warn every time for now. We need to
-                // change the model of how tasks are done internally to remove
-                // this need.
-                warn!(
-                    "generic backends do not support images; as such, the directive to use a `{}` \
-                     image will be ignored",
-                    execution.image()
-                );
-
-                // TODO(clay): surely we can do better than a reallocation here.
-                let shell = execution
-                    .args()
-                    .into_iter()
-                    .map(String::from)
-                    .collect::<Vec<String>>()
-                    .join(" ");
-
-                let mut subtitutions = default_substitutions.clone();
-
-                if subtitutions.insert(String::from("shell"), shell).is_some() {
-                    unreachable!("the `shell` key should not be present here");
-                };
-
-                if let Some(cwd) = execution.workdir() {
-                    if subtitutions
-                        .insert(String::from("cwd"), cwd.into())
-                        .is_some()
-                    {
-                        unreachable!("the `cwd` key should not be present here");
+    /// Reattaches to a job submitted by a previous run.
+    ///
+    /// This is only possible when a job id regex is configured (as, otherwise,
+    /// the submitted command dies along with the previous run). The executions
+    /// before the one the job was submitted for are reported as having
+    /// succeeded without any output, and the executions after it are run as
+    /// usual.
+    fn reattach(
+        &self,
+        task: Task,
+        reattach: Reattach,
+        emitter: Emitter,
+        token: CancellationToken,
+    ) -> Option<BoxFuture<'static, std::result::Result<TaskResult, TaskError>>> {
+        self.job_id_regex
+            .as_ref()
+            .map(|_| run(self, task, emitter, token, Some(reattach)))
+    }
+
+    /// Renders the submit command for each execution.
+    fn plan(&self, task: &Task) -> Option<std::result::Result<Plan, TaskError>> {
+        let defaults = self.default_substitutions(task);
+
+        let submissions = task
+            .executions()
+            .map(|execution| {
+                self.config
+                    .resolve_submit(&substitutions(&defaults, execution))
+                    .map_err(|err| TaskError::SubmissionFailed(err.to_string()))
+            })
+            .collect::<std::result::Result<Vec<_>, _>>();
+
+        Some(submissions.map(|submissions| Plan::new(self.default_name(), submissions)))
+    }
+}
+
+/// Builds the substitutions for an execution from the default substitutions
+/// of its task.
+fn substitutions(
+    defaults: &HashMap<String, String>,
+    execution: &Execution,
+) -> HashMap<String, String> {
+    // TODO(clay): surely we can do better than a reallocation here.
+    let shell = execution
+        .args()
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    let mut substitutions = defaults.clone();
+
+    if substitutions.insert(String::from("shell"), shell).is_some() {
+        unreachable!("the `shell` key should not be present here");
+    };
+
+    if let Some(cwd) = execution.workdir() {
+        if substitutions
+            .insert(String::from("cwd"), cwd.into())
+            .is_some()
+        {
+            unreachable!("the `cwd` key should not be present here");
+        };
+    }
+
+    substitutions
+}
+
+/// Runs a task in a generic backend (optionally reattaching to a job submitted
+/// by a previous run).
+fn run(
+    backend: &Backend,
+    task: Task,
+    emitter: Emitter,
+    token: CancellationToken,
+    reattach: Option<Reattach>,
+) -> BoxFuture<'static, std::result::Result<TaskResult, TaskError>> {
+    let driver = backend.driver.clone();
+    let config = backend.config.clone();
+    let job_id_regex = backend.job_id_regex.clone();
+
+    let default_substitutions = backend.default_substitutions(&task);
+
+    async move {
+        let mut outputs = Vec::new();
+
+        for (index, execution) in task.executions().enumerate() {
+            if token.is_cancelled() {
+                return Err(TaskError::Cancelled);
+            }
+
+            let reattached = reattach
+                .as_ref()
+                .filter(|reattach| index <= reattach.execution);
+
+            if reattached.is_some_and(|reattach| index < reattach.execution) {
+                outputs.push(Output {
+                    status: ExitStatus::default(),
+                    stdout: Vec::new(),
+                    stderr: Vec::new(),
+                });
+                continue;
+            }
+
+            // TODO(clay): this will warn every time for now. We need to
+            // change the model of how tasks are done internally to remove
+            // this need.
+            warn!(
+                "generic backends do not support images; as such, the directive to use a `{}` \
+                 image will be ignored",
+                execution.image()
+            );
+
+            let mut subtitutions = substitutions(&default_substitutions, execution);
+
+            // (1) Submitting the initial job.
+            let submit = config
+                .resolve_submit(&subtitutions)
+                .map_err(|err| TaskError::SubmissionFailed(err.to_string()))?;
+
+            // NOTE: the timeout begins once the job is submitted.
+            let expired = expired(execution.timeout());
+            tokio::pin!(expired);
+
+            // (2) Monitoring the output.
+            match job_id_regex {
+                Some(ref regex) => {
+                    let id = match reattached {
+                        Some(reattach) => reattach.id.clone(),
+                        None => {
+                            emitter.submitting().await;
+                            submit_job(&driver, regex, submit).await?
+                        }
                     };
-                }
 
-                // (1) Submitting the initial job.
-                // TODO(clay): we should probably handle this more gracefully.
-                let submit = config.resolve_submit(&subtitutions).unwrap();
-
-                // TODO(clay): we should probably handle this more gracefully.
-                let output = driver.run(submit).await.unwrap();
-
-                // (2) Monitoring the output.
-                match job_id_regex {
-                    Some(ref regex) => {
-                        let stdout = String::from_utf8_lossy(&output.stdout);
-                        let captures = regex.captures_iter(&stdout).next().unwrap_or_else(|| {
-                            panic!(
-                                "could not match the job id regex within stdout: `{}`",
-                                stdout
-                            )
-                        });
-
-                        // SAFETY: this will always unwrap, as the group is
-                        // _required_ for the pattern to match.
-                        let id = captures.get(1).map(|c| String::from(c.as_str())).unwrap();
-                        subtitutions.insert(String::from("job_id"), id);
-
-                        loop {
-                            let monitor = config.resolve_monitor(&subtitutions).unwrap();
-                            let output = driver.run(monitor).await.unwrap();
-
-                            if !output.status.success() {
-                                outputs.push(output);
-                                break;
-                            }
+                    emitter.submitted(Some(id.clone()));
+                    subtitutions.insert(String::from("job_id"), id);
+
+                    let monitor = match config.resolve_monitor(&subtitutions) {
+                        Ok(monitor) => monitor,
+                        Err(err) => {
+                            kill(&driver, &config, subtitutions).await;
+                            return Err(TaskError::SubmissionFailed(err.to_string()));
+                        }
+                    };
+
+                    // NOTE: a generic backend cannot tell a queued job from a
+                    // running one, so the job is reported as running the first
+                    // time the monitor command sees it alive.
+                    let mut running = false;
 
-                            tokio::time::sleep(Duration::from_secs(
+                    loop {
+                        if token.is_cancelled() {
+                            kill(&driver, &config, subtitutions).await;
+                            return Err(TaskError::Cancelled);
+                        }
+
+                        let output =
+                            emitter
+                                .poll(driver.run(monitor.clone()))
+                                .await
+                                .map_err(|err| {
+                                    TaskError::BackendUnavailable(format!(
+                                        "monitoring job: {err:#}"
+                                    ))
+                                })?;
+
+                        if !output.status.success() {
+                            outputs.push(output);
+                            break;
+                        }
+
+                        if !running {
+                            emitter.running();
+                            running = true;
+                        }
+
+                        tokio::select! {
+                            _ = tokio::time::sleep(Duration::from_secs(
                                 config
                                     .monitor_frequency()
                                     .unwrap_or(DEFAULT_MONITOR_FREQUENCY),
-                            ))
-                            .await;
+                            )) => {}
+                            _ = token.cancelled() => {}
+                            _ = &mut expired => {
+                                kill(&driver, &config, subtitutions).await;
+                                return Err(TaskError::TimedOut);
+                            }
                         }
                     }
-                    _ => {
-                        outputs.push(output);
+                }
+                _ => {
+                    // NOTE: without a job id, the submission _is_ the
+                    // execution, so cancelling (or timing out) drops (and
+                    // thereby kills) the running command. The `kill` command is
+                    // not run, as it has no job id to target.
+                    emitter.submitting().await;
+                    emitter.submitted(None);
+                    emitter.running();
+
+                    let output = tokio::select! {
+                        output = driver.run(submit) => output.map_err(|err| {
+                            TaskError::BackendUnavailable(format!("running command: {err:#}"))
+                        })?,
+                        _ = token.cancelled() => return Err(TaskError::Cancelled),
+                        _ = &mut expired => return Err(TaskError::TimedOut),
+                    };
+
+                    if !output.status.success() {
+                        return Err(TaskError::execution_failed(output));
                     }
+
+                    outputs.push(output);
                 }
             }
+        }
 
-            let mut outputs = outputs.into_iter();
+        let mut outputs = outputs.into_iter();
 
-            // SAFETY: each task _must_ have at least one execution, so at least one
-            // execution result _must_ exist at this stage. Thus, this will always unwrap.
-            let mut executions = NonEmpty::new(outputs.next().unwrap());
-            executions.extend(outputs);
+        // SAFETY: each task _must_ have at least one execution, so at least one
+        // execution result _must_ exist at this stage. Thus, this will always unwrap.
+        let mut executions = NonEmpty::new(outputs.next().unwrap());
+        executions.extend(outputs);
 
-            TaskResult { executions }
+        Ok(TaskResult::new(executions))
+    }
+    .boxed()
+}
+
+/// Submits a job and returns the job id reported by the submit command.
+async fn submit_job(
+    driver: &Driver,
+    regex: &Regex,
+    submit: String,
+) -> std::result::Result<String, TaskError> {
+    // NOTE: the submission itself is not interrupted by cancellation, as we
+    // need the job id it reports to be able to kill the job.
+    let output = driver
+        .run(submit)
+        .await
+        .map_err(|err| TaskError::BackendUnavailable(format!("submitting job: {err:#}")))?;
+
+    if !output.status.success() {
+        return Err(TaskError::SubmissionFailed(format!(
+            "submit command exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    regex
+        .captures(&stdout)
+        .and_then(|captures| captures.get(1))
+        .map(|id| String::from(id.as_str()))
+        .ok_or_else(|| {
+            TaskError::SubmissionFailed(format!(
+                "could not match the job id regex within stdout: `{}`",
+                stdout.trim()
+            ))
+        })
+}
+
+/// Kills a submitted job by running the configured `kill` command.
+///
+/// Nothing is run unless the job id was captured (i.e., the `job_id`
+/// substitution is present), as there is no job to target otherwise. Failures
+/// are logged rather than returned, as there is nothing more that can be done
+/// for a job that could not be killed.
+async fn kill(driver: &Driver, config: &Config, substitutions: HashMap<String, String>) {
+    if !substitutions.contains_key("job_id") {
+        return;
+    }
+
+    let command = match config.resolve_kill(substitutions) {
+        Ok(command) => command,
+        Err(err) => {
+            warn!("unable to kill job: {err}");
+            return;
+        }
+    };
+
+    debug!("killing job: `{command}`");
+
+    match driver.run(command).await {
+        Ok(output) if !output.status.success() => warn!(
+            "kill command for job exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ),
+        Ok(_) => {}
+        Err(err) => warn!("unable to kill job: {err:#}"),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::Backend as _;
+    use crate::service::runner::TaskId;
+    use crate::service::runner::event::TaskState;
+    use crate::task::Execution;
+
+    fn task(command: &str) -> Task {
+        Task::builder()
+            .extend_executions([Execution::builder()
+                .image("ubuntu")
+                .args([command])
+                .try_build()
+                .unwrap()])
+            .try_build()
+            .unwrap()
+    }
+
+    fn emitter() -> Emitter {
+        Emitter::new(TaskId::generate(), tokio::sync::broadcast::Sender::new(1))
+    }
+
+    async fn backend(job_id_regex: Option<&str>) -> Backend {
+        let mut config = Config::builder()
+            .default_driver()
+            .submit("~{shell}")
+            .monitor("false")
+            .kill("true");
+
+        if let Some(regex) = job_id_regex {
+            config = config.job_id_regex(regex);
+        }
+
+        Backend::initialize(config.try_build().unwrap(), None)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn nonzero_exits_are_execution_failures() {
+        let backend = backend(None).await;
+
+        let err = backend
+            .run(task("exit 3"), emitter(), CancellationToken::new())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            TaskError::ExecutionFailed { exit: Some(3), .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn plans_render_the_submit_command() {
+        let backend = backend(None).await;
+
+        let plan = backend.plan(&task("echo hello")).unwrap().unwrap();
+        assert_eq!(plan.backend(), "generic");
+        assert_eq!(plan.submissions(), ["echo hello"]);
+    }
+
+    #[tokio::test]
+    async fn unmatched_job_ids_are_submission_failures() {
+        let backend = backend(Some(r"Job <(\d+)>")).await;
+
+        let err = backend
+            .run(
+                task("echo 'no job here'"),
+                emitter(),
+                CancellationToken::new(),
+            )
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, TaskError::SubmissionFailed(_)));
+    }
+
+    #[tokio::test]
+    async fn executions_that_exceed_their_timeout_time_out() {
+        let backend = backend(None).await;
+
+        let task = Task::builder()
+            .extend_executions([Execution::builder()
+                .image("ubuntu")
+                .args(["sleep 10"])
+                .timeout(Duration::from_millis(50))
+                .try_build()
+                .unwrap()])
+            .try_build()
+            .unwrap();
+
+        let err = backend
+            .run(task, emitter(), CancellationToken::new())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, TaskError::TimedOut));
+    }
+
+    #[tokio::test]
+    async fn monitored_jobs_are_reported_as_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let seen = dir.path().join("seen");
+
+        // NOTE: the monitor reports the job alive on its first poll and
+        // finished on the second.
+        let config = Config::builder()
+            .default_driver()
+            .submit("echo 'Job <1>'")
+            .job_id_regex(r"Job <(\d+)>")
+            .monitor(format!("[ ! -e {0} ] && touch {0}", seen.display()))
+            .monitor_frequency(0u64)
+            .kill("true")
+            .try_build()
+            .unwrap();
+        let backend = Backend::initialize(config, None).await.unwrap();
+
+        let sender = tokio::sync::broadcast::Sender::new(16);
+        let mut events = sender.subscribe();
+        backend
+            .run(
+                task("true"),
+                Emitter::new(TaskId::generate(), sender),
+                CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+
+        let mut running = false;
+        while let Ok(event) = events.try_recv() {
+            running |= matches!(event.state(), TaskState::Running);
         }
-        .boxed()
+
+        assert!(running);
+    }
+
+    #[tokio::test]
+    async fn cancelled_commands_without_job_ids_are_stopped() {
+        let dir = tempfile::tempdir().unwrap();
+        let finished = dir.path().join("finished");
+        let killed = dir.path().join("killed");
+
+        let config = Config::builder()
+            .default_driver()
+            .submit("~{shell}")
+            .monitor("false")
+            .kill(format!("touch {}", killed.display()))
+            .try_build()
+            .unwrap();
+        let backend = Backend::initialize(config, None).await.unwrap();
+
+        let token = CancellationToken::new();
+        let run = tokio::spawn(backend.run(
+            task(&format!("sleep 0.2; touch {}", finished.display())),
+            emitter(),
+            token.clone(),
+        ));
+
+        // NOTE: the command is cancelled while it is running.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        token.cancel();
+        assert!(matches!(run.await.unwrap(), Err(TaskError::Cancelled)));
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert!(!finished.exists());
+        assert!(!killed.exists());
     }
 }
@@ -116,6 +116,9 @@ impl Driver {
 
     /// Runs a shell commmand within the configuration locale.
     ///
+    /// If the returned future is dropped before completion, commands running
+    /// locally are killed. Commands running over SSH run to completion.
+    ///
     /// **NOTE:** this method returns an [`eyre::Result`] because any errors
     /// are intended to be returned directly to the user in the calling binary
     /// (i.e., the errors are typically unrecoverable).
@@ -156,11 +159,13 @@ async fn run_local_command(command: String, config: &Config) -> Result<Output> {
             .args(["bash", "-c", &command])
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
             .spawn(),
         Shell::Sh => Command::new("/usr/bin/env")
             .args(["sh", "-c", &command])
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
             .spawn(),
     }
     .context("spawning the local command")?;
@@ -394,9 +399,11 @@ async fn run_ssh_command(
             .map_err(Error::SSH2)
             .context("waiting for the SSH channel to be closed from the client's end")?;
 
+        // NOTE: on Unix, the raw status is a wait status, which holds the exit
+        // code in its second byte.
         #[cfg(unix)]
         let output = Output {
-            status: ExitStatus::from_raw(status),
+            status: ExitStatus::from_raw(status << 8),
             stdout,
             stderr,
         };
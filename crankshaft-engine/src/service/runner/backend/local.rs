@@ -0,0 +1,623 @@
+//! A local backend.
+//!
+//! The local backend runs each execution directly as a child process on the
+//! host (without a container). Every task gets a working directory of its own
+//! (created within [`Config::root()`]) that stands in for the root of the
+//! container's filesystem: the paths of inputs, outputs, shared volumes,
+//! working directories, and standard stream files are all resolved within it
+//! (e.g., an input at `/data/in.txt` is placed at `data/in.txt` within the
+//! task's working directory). The arguments of an execution are passed to the
+//! process as-is, so tasks meant for this backend should refer to files by
+//! relative paths. The image of an execution is ignored.
+//!
+//! The standard output and error of an execution are written directly to
+//! their files (when configured), in which case they aren't captured in the
+//! execution's output.
+//!
+//! Resources (resolved against the configured defaults) are enforced where the
+//! operating system allows it: on Unix, the requested RAM limits the address
+//! space of each process and the requested disk limits the size of any file it
+//! writes (see `setrlimit(2)`). The requested CPUs cannot be enforced this way,
+//! so they are only used to schedule tasks. Each execution runs in a process
+//! group of its own, so cancelling it (or it timing out) kills every process it
+//! started.
+
+use std::io;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::thread::available_parallelism;
+
+use crankshaft_config::backend::Capacity;
+use crankshaft_config::backend::Defaults;
+use crankshaft_config::backend::local::Config;
+use crankshaft_config::backend::local::Localization;
+use futures::FutureExt as _;
+use futures::future::BoxFuture;
+use nonempty::NonEmpty;
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+use url::Url;
+
+use crate::Task;
+use crate::service::runner::backend::TaskError;
+use crate::service::runner::backend::TaskResult;
+use crate::service::runner::backend::expired;
+use crate::service::runner::event::Emitter;
+use crate::task::Execution;
+use crate::task::Input;
+use crate::task::Resources;
+use crate::task::input::Contents;
+
+/// A local backend.
+#[derive(Debug)]
+pub struct Backend {
+    /// Configuration for the backend.
+    config: Config,
+
+    /// The execution defaults.
+    defaults: Option<Defaults>,
+}
+
+impl Backend {
+    /// Creates a new local [`Backend`].
+    pub fn new(config: Config, defaults: Option<Defaults>) -> Self {
+        Self { config, defaults }
+    }
+
+    /// Resolves the resources for a particular task.
+    // NOTE: unlike the generic backend, nothing is assumed beyond what the
+    // configuration and the task request, as every RAM or disk size resolved
+    // here becomes a hard limit on the task's processes.
+    fn resolve_resources(&self, task: Option<&Resources>) -> Option<Resources> {
+        Resources::resolve(Resources::empty(), self.defaults.as_ref(), task)
+    }
+
+    /// Detects the total resources of the host.
+    ///
+    /// Only the number of CPUs is detected (the RAM and disk are left
+    /// unlimited).
+    pub fn capacity(&self) -> Capacity {
+        let cpu = available_parallelism().ok().map(usize::from);
+        Capacity::new(cpu, None, None)
+    }
+}
+
+impl crate::Backend for Backend {
+    fn default_name(&self) -> &'static str {
+        "local"
+    }
+
+    /// Returns whether the backend runs executions within their container
+    /// images (it doesn't).
+    fn containers(&self) -> bool {
+        false
+    }
+
+    fn run(
+        &self,
+        task: Task,
+        emitter: Emitter,
+        token: CancellationToken,
+    ) -> BoxFuture<'static, Result<TaskResult, TaskError>> {
+        let config = self.config.clone();
+        let resources = self.resolve_resources(task.resources());
+
+        async move {
+            let root = config
+                .root()
+                .map(ToOwned::to_owned)
+                .unwrap_or_else(std::env::temp_dir);
+            let dir = create_dir(&root).await.map_err(|err| {
+                TaskError::BackendUnavailable(format!(
+                    "creating working directory within `{}`: {err}",
+                    root.display()
+                ))
+            })?;
+
+            // NOTE: `into_path()` causes the directory to no longer be removed
+            // when the [`TempDir`](tempfile::TempDir) is dropped.
+            let (path, _guard) = if config.cleanup() {
+                (dir.path().to_owned(), Some(dir))
+            } else {
+                (dir.into_path(), None)
+            };
+
+            debug!("running task within `{}`", path.display());
+            let result = run(
+                &task,
+                &path,
+                config.localization(),
+                resources.as_ref(),
+                &emitter,
+                &token,
+            )
+            .await;
+
+            // NOTE: the result is bound so that it is computed before the
+            // working directory is removed.
+            result
+        }
+        .boxed()
+    }
+}
+
+/// Creates a working directory for a task within the provided root.
+pub(super) async fn create_dir(root: &Path) -> io::Result<tempfile::TempDir> {
+    let root = root.to_owned();
+
+    // SAFETY: the closure never panics, so this will always unwrap.
+    tokio::task::spawn_blocking(move || {
+        std::fs::create_dir_all(&root)?;
+        tempfile::Builder::new()
+            .prefix("crankshaft-")
+            .tempdir_in(&root)
+    })
+    .await
+    .unwrap()
+}
+
+/// Resolves a path within a task to a path within the task's working
+/// directory.
+///
+/// Returns `None` if the path would escape the working directory.
+fn resolve(dir: &Path, path: &str) -> Option<PathBuf> {
+    let mut resolved = dir.to_owned();
+
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(component) => resolved.push(component),
+            Component::RootDir | Component::CurDir => {}
+            Component::Prefix(_) | Component::ParentDir => return None,
+        }
+    }
+
+    Some(resolved)
+}
+
+/// Resolves a path within a task (see [`resolve()`]), failing the task if the
+/// path escapes its working directory.
+fn resolve_or_fail(dir: &Path, path: &str) -> Result<PathBuf, TaskError> {
+    resolve(dir, path).ok_or_else(|| {
+        TaskError::SubmissionFailed(format!(
+            "path `{path}` is outside of the task's working directory"
+        ))
+    })
+}
+
+/// Recursively copies a file or directory.
+pub(super) fn copy(source: &Path, target: &Path) -> io::Result<()> {
+    if source.is_dir() {
+        std::fs::create_dir_all(target)?;
+
+        for entry in std::fs::read_dir(source)? {
+            let entry = entry?;
+            copy(&entry.path(), &target.join(entry.file_name()))?;
+        }
+
+        Ok(())
+    } else {
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::copy(source, target).map(|_| ())
+    }
+}
+
+/// Creates a symlink to a file or directory.
+fn symlink(source: &Path, target: &Path) -> io::Result<()> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(source, target);
+
+    #[cfg(windows)]
+    return if source.is_dir() {
+        std::os::windows::fs::symlink_dir(source, target)
+    } else {
+        std::os::windows::fs::symlink_file(source, target)
+    };
+}
+
+/// Places an input within a task's working directory.
+///
+/// Inputs backed by local files are copied or symlinked (depending on the
+/// localization), and all other inputs are fetched.
+async fn localize(input: &Input, target: PathBuf, localization: Localization) -> io::Result<()> {
+    if let Contents::URL(url) = input.contents() {
+        if url.scheme() == "file" {
+            let source = url.to_file_path().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid file URL: {url}"),
+                )
+            })?;
+
+            // SAFETY: the closure never panics, so this will always unwrap.
+            return tokio::task::spawn_blocking(move || match localization {
+                Localization::Copy => copy(&source, &target),
+                Localization::Symlink => symlink(&source, &target),
+            })
+            .await
+            .unwrap();
+        }
+    }
+
+    let contents = input.fetch().await?;
+
+    if let Some(parent) = target.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    tokio::fs::write(target, contents).await
+}
+
+/// Limits the resources a command's process may use.
+#[cfg(unix)]
+fn limit(command: &mut Command, resources: Option<&Resources>) {
+    /// The number of bytes in a gigabyte.
+    const GIGABYTE: f64 = 1024. * 1024. * 1024.;
+
+    let memory = resources
+        .and_then(|resources| resources.ram())
+        .map(|ram| (ram * GIGABYTE) as libc::rlim_t);
+    let disk = resources
+        .and_then(|resources| resources.disk())
+        .map(|disk| (disk * GIGABYTE) as libc::rlim_t);
+
+    if memory.is_none() && disk.is_none() {
+        return;
+    }
+
+    // SAFETY: the closure runs in the child process between `fork()` and
+    // `exec()`, so it may only call async-signal-safe functions (which
+    // `setrlimit()` is) and must not allocate.
+    unsafe {
+        command.pre_exec(move || {
+            for (resource, limit) in [(libc::RLIMIT_AS, memory), (libc::RLIMIT_FSIZE, disk)] {
+                let Some(limit) = limit else {
+                    continue;
+                };
+
+                let limit = libc::rlimit {
+                    rlim_cur: limit,
+                    rlim_max: limit,
+                };
+
+                if libc::setrlimit(resource, &limit) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+
+            Ok(())
+        });
+    }
+}
+
+/// Kills every process in the process group of an execution (i.e., the
+/// process started for the execution and any processes it started).
+#[cfg(unix)]
+pub(super) fn kill(group: Option<u32>) {
+    if let Some(group) = group.and_then(|group| libc::pid_t::try_from(group).ok()) {
+        // SAFETY: `killpg()` doesn't access any memory. A group that no longer
+        // exists is reported as an error, which is ignored.
+        unsafe { libc::killpg(group, libc::SIGKILL) };
+    }
+}
+
+/// Kills every process started for an execution.
+///
+/// Process groups are only supported on Unix, so elsewhere only the process
+/// itself is killed (when it is dropped).
+#[cfg(not(unix))]
+pub(super) fn kill(_: Option<u32>) {}
+
+/// Creates a file for a standard stream of an execution.
+pub(super) fn stream(path: &Path) -> Result<Stdio, TaskError> {
+    let result = match path.parent() {
+        Some(parent) => std::fs::create_dir_all(parent),
+        None => Ok(()),
+    };
+
+    result
+        .and_then(|_| std::fs::File::create(path))
+        .map(Stdio::from)
+        .map_err(|err| TaskError::SubmissionFailed(format!("creating `{}`: {err}", path.display())))
+}
+
+/// Gets the paths on the host that the outputs of a task are copied to.
+///
+/// Only `file://` URLs are supported.
+pub(super) fn targets(task: &Task) -> Result<Vec<PathBuf>, TaskError> {
+    task.outputs()
+        .into_iter()
+        .flatten()
+        .map(|output| {
+            Url::parse(output.url())
+                .ok()
+                .and_then(|url| url.to_file_path().ok())
+                .ok_or_else(|| {
+                    TaskError::SubmissionFailed(format!(
+                        "unsupported URL for output `{}`: {}",
+                        output.path(),
+                        output.url()
+                    ))
+                })
+        })
+        .collect()
+}
+
+/// Builds the command for an execution of a task.
+fn command(
+    execution: &Execution,
+    dir: &Path,
+    resources: Option<&Resources>,
+) -> Result<Command, TaskError> {
+    let workdir = match execution.workdir() {
+        Some(workdir) => resolve_or_fail(dir, workdir)?,
+        None => dir.to_owned(),
+    };
+
+    std::fs::create_dir_all(&workdir)
+        .map_err(|err| TaskError::SubmissionFailed(format!("creating working directory: {err}")))?;
+
+    let stdin = match execution.stdin() {
+        Some(stdin) => std::fs::File::open(resolve_or_fail(dir, stdin)?)
+            .map(Stdio::from)
+            .map_err(|err| {
+                TaskError::SubmissionFailed(format!("opening standard input `{stdin}`: {err}"))
+            })?,
+        None => Stdio::null(),
+    };
+
+    let stdout = match execution.stdout() {
+        Some(stdout) => stream(&resolve_or_fail(dir, stdout)?)?,
+        None => Stdio::piped(),
+    };
+
+    let stderr = match execution.stderr() {
+        Some(stderr) => stream(&resolve_or_fail(dir, stderr)?)?,
+        None => Stdio::piped(),
+    };
+
+    let mut command = Command::new(execution.args().first());
+    command
+        .args(execution.args().tail())
+        .current_dir(workdir)
+        .stdin(stdin)
+        .stdout(stdout)
+        .stderr(stderr)
+        .kill_on_drop(true);
+
+    if let Some(env) = execution.env() {
+        command.envs(env);
+    }
+
+    #[cfg(unix)]
+    {
+        command.process_group(0);
+        limit(&mut command, resources);
+    }
+
+    Ok(command)
+}
+
+/// Runs a task within its working directory.
+async fn run(
+    task: &Task,
+    dir: &Path,
+    localization: Localization,
+    resources: Option<&Resources>,
+    emitter: &Emitter,
+    token: &CancellationToken,
+) -> Result<TaskResult, TaskError> {
+    // NOTE: the outputs are checked up front so that a task whose outputs
+    // can't be collected fails before anything runs.
+    let targets = targets(task)?;
+
+    // (1) Create the shared volumes and localize the inputs.
+    for volume in task.shared_volumes().into_iter().flatten() {
+        tokio::fs::create_dir_all(resolve_or_fail(dir, volume)?)
+            .await
+            .map_err(|err| {
+                TaskError::SubmissionFailed(format!("creating shared volume `{volume}`: {err}"))
+            })?;
+    }
+
+    for input in task.inputs().into_iter().flatten() {
+        let target = resolve_or_fail(dir, input.path())?;
+        localize(input, target, localization).await.map_err(|err| {
+            TaskError::SubmissionFailed(format!("localizing input `{}`: {err}", input.path()))
+        })?;
+    }
+
+    let mut outputs = Vec::new();
+
+    for execution in task.executions() {
+        if token.is_cancelled() {
+            return Err(TaskError::Cancelled);
+        }
+
+        // (2) Start the process.
+        emitter.submitting().await;
+        let child = command(execution, dir, resources)?.spawn().map_err(|err| {
+            TaskError::SubmissionFailed(format!("spawning `{}`: {err}", execution.args().first()))
+        })?;
+
+        let group = child.id();
+        emitter.submitted(group.map(|id| id.to_string()));
+        emitter.running();
+
+        // NOTE: the process itself is killed if it is abandoned (i.e., when
+        // the future waiting on it is dropped), and the rest of its process
+        // group is killed explicitly.
+        let output = tokio::select! {
+            output = child.wait_with_output() => output.map_err(|err| {
+                TaskError::BackendUnavailable(format!("waiting on process: {err}"))
+            })?,
+            _ = token.cancelled() => {
+                kill(group);
+                return Err(TaskError::Cancelled);
+            }
+            _ = expired(execution.timeout()) => {
+                kill(group);
+                return Err(TaskError::TimedOut);
+            }
+        };
+
+        if !output.status.success() {
+            return Err(TaskError::execution_failed(output));
+        }
+
+        outputs.push(output);
+    }
+
+    // (3) Collect the outputs.
+    for (output, target) in task.outputs().into_iter().flatten().zip(targets) {
+        let source = resolve_or_fail(dir, output.path())?;
+
+        // SAFETY: the closure never panics, so this will always unwrap.
+        tokio::task::spawn_blocking(move || copy(&source, &target))
+            .await
+            .unwrap()
+            .map_err(|err| {
+                TaskError::SubmissionFailed(format!("collecting output `{}`: {err}", output.path()))
+            })?;
+    }
+
+    let mut outputs = outputs.into_iter();
+
+    // SAFETY: each task _must_ have at least one execution, so at least one
+    // output _must_ exist at this stage. Thus, this will always unwrap.
+    let mut executions = NonEmpty::new(outputs.next().unwrap());
+    executions.extend(outputs);
+
+    Ok(TaskResult::new(executions))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::service::Runner;
+    use crate::task::Output;
+
+    #[tokio::test]
+    async fn tasks_run_within_their_working_directory() {
+        let outputs = TempDir::new().unwrap();
+        let url = Url::from_file_path(outputs.path().join("out.txt")).unwrap();
+
+        let task = Task::builder()
+            .extend_inputs([Input::builder()
+                .contents(Contents::Literal(String::from("hello")))
+                .path("/data/in.txt")
+                .r#type(crate::task::input::Type::File)
+                .try_build()
+                .unwrap()])
+            .extend_outputs([Output::builder()
+                .url(url)
+                .path("/data/out.txt")
+                .r#type(crate::task::output::Type::File)
+                .try_build()
+                .unwrap()])
+            .extend_executions([Execution::builder()
+                .image("ignored")
+                .args(["sh", "-c", "tr a-z A-Z; echo \"$GREETING\" >&2"])
+                .working_directory("/data")
+                .stdin("/data/in.txt")
+                .stdout("/data/out.txt")
+                .env("GREETING", "hi")
+                .try_build()
+                .unwrap()])
+            .try_build()
+            .unwrap();
+
+        let runner = Runner::new(Arc::new(Backend::new(Config::default(), None)), 1);
+        let result = runner
+            .submit(task)
+            .unwrap()
+            .callback
+            .await
+            .unwrap()
+            .unwrap();
+
+        // NOTE: the standard output was written to its file (rather than
+        // captured), while the standard error was captured.
+        let output = result.executions().first();
+        assert!(output.stdout.is_empty());
+        assert_eq!(output.stderr, b"hi\n");
+        assert_eq!(
+            std::fs::read_to_string(outputs.path().join("out.txt")).unwrap(),
+            "HELLO"
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn timeouts_kill_every_process_of_an_execution() {
+        let dir = TempDir::new().unwrap();
+        let pid = dir.path().join("pid");
+
+        let task = Task::builder()
+            .extend_executions([Execution::builder()
+                .image("ignored")
+                .args([
+                    "sh",
+                    "-c",
+                    &format!("sleep 30 & echo $! > {}; wait", pid.display()),
+                ])
+                .timeout(Duration::from_millis(500))
+                .try_build()
+                .unwrap()])
+            .try_build()
+            .unwrap();
+
+        let runner = Runner::new(Arc::new(Backend::new(Config::default(), None)), 1);
+        let result = runner.submit(task).unwrap().callback.await.unwrap();
+        assert!(matches!(result, Err(TaskError::TimedOut)));
+
+        // NOTE: the orphaned `sleep` may linger as a zombie until it is reaped,
+        // but it must not still be running.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let pid = std::fs::read_to_string(pid).unwrap();
+        let running = std::fs::read_to_string(format!("/proc/{}/stat", pid.trim()))
+            .is_ok_and(|stat| stat.split_whitespace().nth(2) != Some("Z"));
+        assert!(!running);
+    }
+
+    #[test]
+    fn only_requested_resources_become_limits() {
+        let backend = Backend::new(Config::default(), None);
+        assert!(backend.resolve_resources(None).is_none());
+
+        let resources = backend
+            .resolve_resources(Some(
+                &crate::task::resources::Builder::default()
+                    .cpu(2usize)
+                    .build(),
+            ))
+            .unwrap();
+        assert_eq!(resources.cpu(), Some(2));
+        assert!(resources.ram().is_none());
+        assert!(resources.disk().is_none());
+    }
+
+    #[test]
+    fn paths_cannot_escape_the_working_directory() {
+        let dir = Path::new("/tmp/task");
+        assert_eq!(
+            resolve(dir, "/data/./in.txt").unwrap(),
+            Path::new("/tmp/task/data/in.txt")
+        );
+        assert!(resolve(dir, "../in.txt").is_none());
+    }
+}
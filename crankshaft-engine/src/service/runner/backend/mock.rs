@@ -0,0 +1,320 @@
+//! A mock backend.
+//!
+//! The mock backend doesn't run anything. Each execution "completes" after a
+//! simulated duration (see [`Config`]) with scripted output, which makes it
+//! useful for testing code built on top of the engine without Docker, SSH, or
+//! a TES server. Because durations are simulated with [`tokio::time`],
+//! combining the backend with [paused time] simulates long
+//! runs of many tasks in very little real time.
+//!
+//! The configured output of an execution can be overridden by setting the
+//! following environment variables on the execution:
+//!
+//! * `MOCK_DURATION`: the duration of the execution (in seconds).
+//! * `MOCK_STDOUT`: the standard output of the execution.
+//! * `MOCK_EXIT_CODE`: the exit code of the execution.
+//!
+//! Exit codes must be between zero and 255; an execution with any other
+//! `MOCK_EXIT_CODE` fails the attempt with [`TaskError::SubmissionFailed`].
+//!
+//! Injected failures fail the whole attempt as if the backend were unavailable
+//! (i.e., with [`TaskError::BackendUnavailable`]), so they can be retried.
+//!
+//! [paused time]: https://docs.rs/tokio/latest/tokio/time/fn.pause.html
+
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+#[cfg(windows)]
+use std::os::windows::process::ExitStatusExt;
+use std::process::ExitStatus;
+use std::process::Output;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use crankshaft_config::backend::mock::Config;
+use futures::FutureExt as _;
+use futures::future::BoxFuture;
+use nonempty::NonEmpty;
+use rand::Rng as _;
+use rand::SeedableRng as _;
+use rand::rngs::StdRng;
+use tokio_util::sync::CancellationToken;
+
+use crate::Task;
+use crate::service::runner::backend::TaskError;
+use crate::service::runner::backend::TaskResult;
+use crate::service::runner::backend::expired;
+use crate::service::runner::event::Emitter;
+use crate::task::Execution;
+
+/// The environment variable that overrides the duration of an execution.
+pub const DURATION_VAR: &str = "MOCK_DURATION";
+
+/// The environment variable that overrides the standard output of an
+/// execution.
+pub const STDOUT_VAR: &str = "MOCK_STDOUT";
+
+/// The environment variable that overrides the exit code of an execution.
+pub const EXIT_CODE_VAR: &str = "MOCK_EXIT_CODE";
+
+/// A simulated execution.
+#[derive(Debug)]
+struct Simulated {
+    /// How long the execution takes.
+    duration: Duration,
+
+    /// The output of the execution.
+    output: Output,
+}
+
+/// A mock backend.
+#[derive(Debug)]
+pub struct Backend {
+    /// The configuration of the backend.
+    config: Config,
+
+    /// The random number generator for durations and failures.
+    rng: Mutex<StdRng>,
+
+    /// The number of jobs submitted (used to give each one an identifier).
+    jobs: AtomicU64,
+}
+
+impl Backend {
+    /// Creates a new mock [`Backend`].
+    pub fn new(config: Config) -> Self {
+        let rng = match config.seed() {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        Self {
+            config,
+            rng: Mutex::new(rng),
+            jobs: Default::default(),
+        }
+    }
+
+    /// Returns whether an attempt should fail (according to the failure rate).
+    fn fails(&self) -> bool {
+        let rate = self.config.failure_rate();
+
+        // NOTE: rates outside of zero and one (including NaN) are clamped here
+        // because `gen_bool()` panics on them.
+        if rate.is_nan() || rate <= 0.0 {
+            return false;
+        }
+
+        rate >= 1.0 || self.rng.lock().unwrap().gen_bool(rate)
+    }
+
+    /// Picks the duration of an execution (in seconds).
+    fn duration(&self) -> f64 {
+        // NOTE: durations that aren't finite or are negative (including NaN)
+        // are treated as zero, and a maximum that isn't finite is ignored,
+        // because `gen_range()` panics on such bounds.
+        let min = self.config.min_duration();
+        let min = if min.is_finite() { min.max(0.0) } else { 0.0 };
+
+        match self.config.max_duration() {
+            Some(max) if max.is_finite() && max > min => {
+                self.rng.lock().unwrap().gen_range(min..=max)
+            }
+            _ => min,
+        }
+    }
+
+    /// Simulates an execution.
+    ///
+    /// An error is returned if the exit code set on the execution is not a
+    /// valid exit code.
+    fn simulate(&self, execution: &Execution) -> Result<Simulated, TaskError> {
+        let var = |name: &str| execution.env().and_then(|env| env.get(name));
+
+        let seconds = var(DURATION_VAR)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_else(|| self.duration());
+        let stdout = var(STDOUT_VAR)
+            .cloned()
+            .unwrap_or_else(|| self.config.stdout().to_owned());
+        let code = match var(EXIT_CODE_VAR) {
+            Some(value) => value.parse::<u8>().map_err(|_| {
+                TaskError::SubmissionFailed(format!(
+                    "`{EXIT_CODE_VAR}` must be an exit code between 0 and 255 (found `{value}`)"
+                ))
+            })?,
+            None => self.config.exit_code(),
+        };
+
+        #[cfg(unix)]
+        let status = ExitStatus::from_raw(i32::from(code) << 8);
+
+        #[cfg(windows)]
+        let status = ExitStatus::from_raw(u32::from(code));
+
+        Ok(Simulated {
+            duration: Duration::try_from_secs_f64(seconds).unwrap_or_default(),
+            output: Output {
+                status,
+                stdout: stdout.into_bytes(),
+                stderr: Vec::new(),
+            },
+        })
+    }
+}
+
+impl crate::Backend for Backend {
+    fn default_name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn run(
+        &self,
+        task: Task,
+        emitter: Emitter,
+        token: CancellationToken,
+    ) -> BoxFuture<'static, Result<TaskResult, TaskError>> {
+        // NOTE: everything random is decided up front so that the future
+        // doesn't need to hold onto the backend.
+        let fails = self.fails();
+        let executions = task
+            .executions()
+            .map(|execution| Ok((self.simulate(execution)?, execution.timeout())))
+            .collect::<Result<Vec<_>, TaskError>>();
+        let job = self.jobs.fetch_add(1, Ordering::Relaxed);
+
+        async move {
+            let executions = executions?;
+
+            emitter.submitting().await;
+            emitter.submitted(Some(format!("mock-{job}")));
+
+            if fails {
+                return Err(TaskError::BackendUnavailable(String::from(
+                    "injected failure",
+                )));
+            }
+
+            emitter.running();
+
+            let mut outputs = Vec::new();
+
+            for (simulated, timeout) in executions {
+                tokio::select! {
+                    _ = tokio::time::sleep(simulated.duration) => {}
+                    _ = token.cancelled() => return Err(TaskError::Cancelled),
+                    _ = expired(timeout) => return Err(TaskError::TimedOut),
+                }
+
+                if !simulated.output.status.success() {
+                    return Err(TaskError::execution_failed(simulated.output));
+                }
+
+                outputs.push(simulated.output);
+            }
+
+            let mut outputs = outputs.into_iter();
+
+            // SAFETY: each task _must_ have at least one execution, so at least
+            // one output _must_ exist at this stage. Thus, this will always
+            // unwrap.
+            let mut executions = NonEmpty::new(outputs.next().unwrap());
+            executions.extend(outputs);
+
+            Ok(TaskResult::new(executions))
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::service::Runner;
+
+    fn task() -> Task {
+        Task::builder()
+            .extend_executions([Execution::builder()
+                .image("ubuntu")
+                .args(["echo", "hello"])
+                .try_build()
+                .unwrap()])
+            .try_build()
+            .unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn many_tasks_run_in_simulated_time() {
+        let config = Config::builder()
+            .duration_between(60.0, 3600.0)
+            .stdout("hello")
+            .failure_rate(0.1)
+            .seed(42)
+            .build();
+        let runner = Runner::new(Arc::new(Backend::new(config)), 1000);
+
+        let started = std::time::Instant::now();
+        let handles = (0..20_000)
+            .map(|_| runner.submit(task()).unwrap())
+            .collect::<Vec<_>>();
+
+        let mut failed = 0;
+        for handle in handles {
+            match handle.callback.await.unwrap() {
+                Ok(result) => assert_eq!(result.executions().first().stdout, b"hello"),
+                Err(TaskError::BackendUnavailable(_)) => failed += 1,
+                Err(err) => panic!("unexpected error: {err}"),
+            }
+        }
+
+        assert!((1_000..3_000).contains(&failed), "{failed}");
+        assert!(started.elapsed() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn durations_never_panic() {
+        for (min, max) in [
+            (1.0, f64::INFINITY),
+            (f64::NAN, 2.0),
+            (f64::NEG_INFINITY, f64::NAN),
+            (-5.0, -1.0),
+        ] {
+            let backend = Backend::new(Config::builder().duration_between(min, max).build());
+            let duration = backend.duration();
+            assert!(duration.is_finite() && duration >= 0.0, "{duration}");
+        }
+    }
+
+    #[tokio::test]
+    async fn executions_can_be_scripted() {
+        let backend = Backend::new(Config::builder().stdout("configured").build());
+        let execution = Execution::builder()
+            .image("ubuntu")
+            .args(["false"])
+            .env(EXIT_CODE_VAR, "3")
+            .try_build()
+            .unwrap();
+
+        let simulated = backend.simulate(&execution).unwrap();
+        assert_eq!(simulated.output.status.code(), Some(3));
+        assert_eq!(simulated.output.stdout, b"configured");
+
+        for code in ["256", "-1", "nope"] {
+            let execution = Execution::builder()
+                .image("ubuntu")
+                .args(["false"])
+                .env(EXIT_CODE_VAR, code)
+                .try_build()
+                .unwrap();
+
+            assert!(matches!(
+                backend.simulate(&execution),
+                Err(TaskError::SubmissionFailed(_))
+            ));
+        }
+    }
+}
@@ -20,11 +20,20 @@ use futures::future::BoxFuture;
 use nonempty::NonEmpty;
 use tes::v1::Client;
 use tes::v1::client::tasks::View;
+use tes::v1::types::task::State;
+use tes::v1::types::task::executor::Log;
+use tokio_util::sync::CancellationToken;
 use tracing::debug;
 use tracing::error;
+use tracing::warn;
 
 use crate::Task;
+use crate::service::runner::backend::Plan;
+use crate::service::runner::backend::Reattach;
+use crate::service::runner::backend::TaskError;
 use crate::service::runner::backend::TaskResult;
+use crate::service::runner::backend::expired;
+use crate::service::runner::event::Emitter;
 
 /// A backend driven by the Task Execution Service (TES) schema.
 #[derive(Debug)]
@@ -56,8 +65,42 @@ impl crate::Backend for Backend {
     }
 
     /// Runs a task in a backend.
-    fn run(&self, task: Task) -> BoxFuture<'static, TaskResult> {
-        run(self, task)
+    fn run(
+        &self,
+        task: Task,
+        emitter: Emitter,
+        token: CancellationToken,
+    ) -> BoxFuture<'static, Result<TaskResult, TaskError>> {
+        run(self, task, emitter, token)
+    }
+
+    /// Reattaches to a TES task created by a previous run.
+    fn reattach(
+        &self,
+        task: Task,
+        reattach: Reattach,
+        emitter: Emitter,
+        token: CancellationToken,
+    ) -> Option<BoxFuture<'static, Result<TaskResult, TaskError>>> {
+        let client = self.client.clone();
+        let timeout = timeout(&task);
+
+        Some(
+            async move {
+                emitter.submitted(Some(reattach.id.clone()));
+                poll(&client, reattach.id, timeout, &emitter, &token).await
+            }
+            .boxed(),
+        )
+    }
+
+    /// Renders the TES task that would be created (as JSON).
+    fn plan(&self, task: &Task) -> Option<Result<Plan, TaskError>> {
+        Some(
+            serde_json::to_string_pretty(&to_tes_task(task.clone()))
+                .map(|task| Plan::new(self.default_name(), vec![task]))
+                .map_err(|err| TaskError::SubmissionFailed(format!("rendering task: {err}"))),
+        )
     }
 }
 
@@ -85,77 +128,217 @@ fn to_tes_task(task: Task) -> tes::v1::types::Task {
     }
 }
 
+/// The maximum number of consecutive failures to retrieve a task's status
+/// before the backend is considered unavailable.
+const MAX_CONSECUTIVE_POLL_FAILURES: usize = 10;
+
+/// Categorizes an error returned from the TES client.
+///
+/// Errors the server reports as the fault of the request (i.e., a `4xx`
+/// status) are considered submission failures. Everything else (e.g.,
+/// connection errors or `5xx` statuses) is considered the backend being
+/// unavailable.
+fn categorize(err: tes::v1::client::Error, context: &str) -> TaskError {
+    match &err {
+        tes::v1::client::Error::Reqwest(inner)
+            if inner
+                .status()
+                .is_some_and(|status| status.is_client_error()) =>
+        {
+            TaskError::SubmissionFailed(format!("{context}: {err}"))
+        }
+        tes::v1::client::Error::SerdeJSON(_) => {
+            TaskError::SubmissionFailed(format!("{context}: {err}"))
+        }
+        _ => TaskError::BackendUnavailable(format!("{context}: {err}")),
+    }
+}
+
+/// Converts a [TES executor log](Log) into an [`Output`].
+fn to_output(log: Log) -> Output {
+    let status = log.exit_code.unwrap_or_default();
+
+    #[cfg(unix)]
+    let status = ExitStatus::from_raw((status as i32) << 8);
+
+    #[cfg(windows)]
+    let status = ExitStatus::from_raw(status);
+
+    Output {
+        status,
+        stdout: log.stdout.unwrap_or_default().into_bytes(),
+        stderr: log.stderr.unwrap_or_default().into_bytes(),
+    }
+}
+
+/// Gets the executor outputs from the most recent attempt within a TES task.
+fn outputs(task: &mut tes::v1::types::Task) -> Vec<Output> {
+    task.logs
+        .take()
+        .and_then(|logs| logs.into_iter().last())
+        .map(|log| log.logs.into_iter().map(to_output).collect())
+        .unwrap_or_default()
+}
+
+/// Gets the limit on how long a TES task may run for.
+///
+/// TES runs all of a task's executors as a single unit, so the execution
+/// timeouts can only be enforced together (and only when every execution has
+/// one).
+fn timeout(task: &Task) -> Option<Duration> {
+    task.executions()
+        .map(|execution| execution.timeout())
+        .sum::<Option<Duration>>()
+}
+
 /// Runs a [`Task`] in the backend.
-fn run(backend: &Backend, task: Task) -> BoxFuture<'static, TaskResult> {
+fn run(
+    backend: &Backend,
+    task: Task,
+    emitter: Emitter,
+    token: CancellationToken,
+) -> BoxFuture<'static, Result<TaskResult, TaskError>> {
     let client = backend.client.clone();
+    let timeout = timeout(&task);
     let task = to_tes_task(task);
 
     async move {
-        let task_id = client.create_task(task).await.unwrap().id;
-
-        loop {
-            debug!("looping on {task_id}");
-            match client.get_task(&task_id, View::Full).await {
-                Ok(task) => {
-                    debug!("Got response for {task_id}: {task:?}");
-                    // SAFETY: `get_task` called with `View::Full` will always
-                    // return a full [`Task`], so this will always unwrap.
-                    let task = task.into_task().unwrap();
-
-                    if let Some(ref state) = task.state {
-                        debug!("State was found for {task_id}");
-                        if !state.is_executing() {
-                            debug!("Task is completed for {task_id}");
-                            // let mut results = task
-                            //     .logs
-                            //     .unwrap()
-                            //     .into_iter()
-                            //     .flat_map(|task| task.logs)
-                            //     .map(|log| {
-                            //         let status = log.exit_code.expect("exit code to be present");
-
-                            //         #[cfg(unix)]
-                            //         let output = Output {
-                            //             status: ExitStatus::from_raw(status as i32),
-                            //             stdout:
-                            // log.stdout.unwrap_or_default().as_bytes().to_vec(),
-                            //             stderr:
-                            // log.stderr.unwrap_or_default().as_bytes().to_vec(),
-                            //         };
-
-                            //         #[cfg(windows)]
-                            //         let output = Output {
-                            //             status: ExitStatus::from_raw(status),
-                            //             stdout:
-                            // log.stdout.unwrap_or_default().as_bytes().to_vec(),
-                            //             stderr:
-                            // log.stderr.unwrap_or_default().as_bytes().to_vec(),
-                            //         };
-
-                            //         output
-                            //     });
-
-                            // let mut executions = NonEmpty::new(results.next().unwrap());
-                            // executions.extend(results);
-                            let executions = NonEmpty::new(Output {
+        if token.is_cancelled() {
+            return Err(TaskError::Cancelled);
+        }
+
+        emitter.submitting().await;
+        let task_id = client
+            .create_task(task)
+            .await
+            .map_err(|err| categorize(err, "creating task"))?
+            .id;
+
+        emitter.submitted(Some(task_id.clone()));
+        poll(&client, task_id, timeout, &emitter, &token).await
+    }
+    .boxed()
+}
+
+/// Polls a created TES task until it completes.
+async fn poll(
+    client: &Client,
+    task_id: String,
+    timeout: Option<Duration>,
+    emitter: &Emitter,
+    token: &CancellationToken,
+) -> Result<TaskResult, TaskError> {
+    let expired = expired(timeout);
+    tokio::pin!(expired);
+
+    let mut failures = 0;
+    let mut running = false;
+    let mut timed_out = false;
+
+    loop {
+        if token.is_cancelled() || timed_out {
+            debug!("cancelling {task_id}");
+
+            if let Err(err) = client.cancel_task(&task_id).await {
+                warn!("failed to cancel TES task `{task_id}`: {err}");
+            }
+
+            return Err(if timed_out {
+                TaskError::TimedOut
+            } else {
+                TaskError::Cancelled
+            });
+        }
+
+        debug!("looping on {task_id}");
+        match emitter.poll(client.get_task(&task_id, View::Full)).await {
+            Ok(task) => {
+                failures = 0;
+                debug!("Got response for {task_id}: {task:?}");
+                // SAFETY: `get_task` called with `View::Full` will always
+                // return a full [`Task`], so this will always unwrap.
+                let mut task = task.into_task().unwrap();
+
+                match task.state {
+                    Some(State::Complete) => {
+                        debug!("Task is completed for {task_id}");
+                        let mut outputs = outputs(&mut task).into_iter();
+
+                        // NOTE: some servers do not report executor logs.
+                        // In that case, a completed task is reported as
+                        // having exited successfully with no output.
+                        let mut executions =
+                            NonEmpty::new(outputs.next().unwrap_or_else(|| Output {
                                 status: ExitStatus::from_raw(0),
                                 stdout: Vec::new(),
                                 stderr: Vec::new(),
-                            });
+                            }));
+                        executions.extend(outputs);
 
-                            return TaskResult { executions };
-                        } else {
-                            debug!("Task was NOT completed for {task_id}. Looping...");
+                        return Ok(TaskResult::new(executions));
+                    }
+                    Some(State::ExecutorError) => {
+                        debug!("Task failed for {task_id}");
+                        let output = outputs(&mut task)
+                            .into_iter()
+                            .find(|output| !output.status.success());
+
+                        return Err(match output {
+                            Some(output) => TaskError::execution_failed(output),
+                            // NOTE: the server did not report which
+                            // executor failed (or how).
+                            None => TaskError::ExecutionFailed {
+                                exit: None,
+                                output: to_output(Log {
+                                    exit_code: Some(1),
+                                    ..Default::default()
+                                }),
+                            },
+                        });
+                    }
+                    Some(State::SystemError) => {
+                        debug!("Task encountered a system error for {task_id}");
+                        let logs = task
+                            .logs
+                            .into_iter()
+                            .flatten()
+                            .flat_map(|log| log.system_logs.unwrap_or_default())
+                            .collect::<Vec<_>>()
+                            .join("; ");
+
+                        return Err(TaskError::BackendUnavailable(format!(
+                            "system error for TES task `{task_id}`: {logs}"
+                        )));
+                    }
+                    Some(State::Canceled) => {
+                        debug!("Task was cancelled externally for {task_id}");
+                        return Err(TaskError::Cancelled);
+                    }
+                    Some(state) => {
+                        if state == State::Running && !running {
+                            running = true;
+                            emitter.running();
                         }
-                    } else {
-                        debug!("State was NOT set for {task_id}. Looping...");
+
+                        debug!("Task was NOT completed for {task_id}. Looping...")
                     }
+                    None => debug!("State was NOT set for {task_id}. Looping..."),
+                }
+            }
+            Err(err) => {
+                failures += 1;
+                error!("error: {err}");
 
-                    tokio::time::sleep(Duration::from_millis(200)).await;
+                if failures >= MAX_CONSECUTIVE_POLL_FAILURES {
+                    return Err(categorize(err, "getting task status"));
                 }
-                Err(err) => error!("error: {err}"),
             }
         }
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(200)) => {}
+            _ = token.cancelled() => {}
+            _ = &mut expired => timed_out = true,
+        }
     }
-    .boxed()
 }
@@ -0,0 +1,279 @@
+//! Task lifecycle events.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use tokio::sync::broadcast;
+use tracing::Span;
+
+use crate::journal::Journal;
+#[cfg(feature = "metrics")]
+use crate::metrics::Recorder;
+use crate::service::runner::TaskId;
+use crate::service::runner::adaptive::Feedback;
+use crate::service::runner::backend::TaskError;
+use crate::service::runner::limit::Limits;
+use crate::status::Registry;
+
+/// The number of events that are buffered for each subscriber.
+///
+/// Subscribers that fall further behind than this miss the oldest events (see
+/// [`broadcast::error::RecvError::Lagged`]).
+pub const DEFAULT_CAPACITY: usize = 4096;
+
+/// A state that a task transitions through.
+#[derive(Clone, Debug)]
+pub enum TaskState {
+    /// The task is waiting for the runner to have capacity to run it.
+    Queued,
+
+    /// The task has been submitted to the backend.
+    ///
+    /// For backends that run a task in multiple units (e.g., one container per
+    /// execution), this state is entered once for each of them.
+    Submitted {
+        /// The identifier the backend uses for the submitted work (e.g., a job
+        /// id or a container name), if it has one.
+        id: Option<String>,
+    },
+
+    /// The task is running.
+    ///
+    /// Note that not every backend can report exactly when a task begins
+    /// running (e.g., the generic backend only knows whether a submitted job is
+    /// still alive, so it reports this state once the job is first seen
+    /// alive).
+    Running,
+
+    /// An attempt at running the task failed, and the task will be retried
+    /// (according to its [retry policy](crate::task::retry::Policy)).
+    Retrying {
+        /// The number of the attempt that failed (starting at one).
+        attempt: u32,
+
+        /// The error the attempt failed with.
+        error: TaskError,
+
+        /// How long the runner waits before the next attempt.
+        delay: Duration,
+    },
+
+    /// The task completed successfully.
+    Completed,
+
+    /// The task failed.
+    Failed(TaskError),
+}
+
+/// An event emitted when a task transitions to a new state.
+#[derive(Clone, Debug)]
+pub struct TaskEvent {
+    /// The id of the task.
+    id: TaskId,
+
+    /// The time at which the transition occurred.
+    timestamp: SystemTime,
+
+    /// The state the task transitioned to.
+    state: TaskState,
+}
+
+impl TaskEvent {
+    /// Gets the id of the task.
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    /// Gets the time at which the transition occurred.
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    /// Gets the state the task transitioned to.
+    pub fn state(&self) -> &TaskState {
+        &self.state
+    }
+}
+
+/// Emits the events for a single task.
+///
+/// An emitter is handed to a [`Backend`](crate::Backend) alongside each task
+/// it runs so that the backend can report the transitions only it can observe.
+#[derive(Clone, Debug)]
+pub struct Emitter {
+    /// The id of the task.
+    id: TaskId,
+
+    /// The channel to send events over.
+    sender: broadcast::Sender<TaskEvent>,
+
+    /// The journal to record events in (if one is configured).
+    journal: Option<Arc<Journal>>,
+
+    /// The statuses to keep up to date as events are emitted.
+    statuses: Arc<Registry>,
+
+    /// The span the task runs in.
+    span: Span,
+
+    /// The rate limits of the calls made to the backend.
+    limits: Arc<Limits>,
+
+    /// Whether the runner has already taken the submission rate limit's token
+    /// for the next submission of the current attempt.
+    granted: Arc<AtomicBool>,
+
+    /// The feedback for the runner's concurrency (if it is adaptive).
+    feedback: Option<Arc<Feedback>>,
+
+    /// The recorder of the task's metrics (if metrics are enabled).
+    #[cfg(feature = "metrics")]
+    recorder: Option<Arc<Recorder>>,
+}
+
+impl Emitter {
+    /// Creates a new [`Emitter`].
+    pub(crate) fn new(id: TaskId, sender: broadcast::Sender<TaskEvent>) -> Self {
+        Self {
+            id,
+            sender,
+            journal: None,
+            statuses: Default::default(),
+            span: Span::none(),
+            limits: Default::default(),
+            granted: Default::default(),
+            feedback: None,
+            #[cfg(feature = "metrics")]
+            recorder: None,
+        }
+    }
+
+    /// Records the events in the provided journal as they are emitted.
+    pub(crate) fn with_journal(mut self, journal: Option<Arc<Journal>>) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    /// Records the metrics of the task as the events are emitted.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn with_recorder(mut self, recorder: Option<Arc<Recorder>>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Keeps the status of the task in the provided registry up to date as the
+    /// events are emitted.
+    pub(crate) fn with_statuses(mut self, statuses: Arc<Registry>) -> Self {
+        self.statuses = statuses;
+        self
+    }
+
+    /// Records the backend's identifier for the task in the provided span as
+    /// the task is submitted.
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+
+    /// Shares the provided rate limits with the backend.
+    pub(crate) fn with_limits(mut self, limits: Arc<Limits>) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Feeds the outcome of the task's attempts back to the runner's
+    /// concurrency controller.
+    pub(crate) fn with_feedback(mut self, feedback: Option<Arc<Feedback>>) -> Self {
+        self.feedback = feedback;
+        self
+    }
+
+    /// Waits until the runner's rate limit allows an attempt that was given a
+    /// slot to be handed to the backend.
+    ///
+    /// The token taken here covers the first submission of the attempt. The
+    /// latency fed back to the concurrency controller is measured from once
+    /// the token is taken so that waiting on the rate limit is never mistaken
+    /// for a slow backend.
+    pub(crate) async fn dispatched(&self) {
+        self.limits.submission().await;
+        self.granted.store(true, Ordering::SeqCst);
+
+        if let Some(feedback) = &self.feedback {
+            feedback.dispatched();
+        }
+    }
+
+    /// Emits an event for the task transitioning to the provided state.
+    pub(crate) fn emit(&self, state: TaskState) {
+        let timestamp = SystemTime::now();
+        self.statuses.transition(self.id, &state, timestamp);
+
+        if let Some(journal) = &self.journal {
+            journal.state(self.id, &state);
+        }
+
+        if let Some(feedback) = &self.feedback {
+            match &state {
+                TaskState::Submitted { .. } => feedback.submitted(),
+                TaskState::Retrying { error, .. } | TaskState::Failed(error) => {
+                    feedback.failed(error)
+                }
+                _ => {}
+            }
+        }
+
+        #[cfg(feature = "metrics")]
+        if let Some(recorder) = &self.recorder {
+            recorder.record(&state);
+        }
+
+        // NOTE: sending only fails when there are no subscribers, in which case
+        // nobody is interested in the event.
+        let _ = self.sender.send(TaskEvent {
+            id: self.id,
+            timestamp,
+            state,
+        });
+    }
+
+    /// Reports that the task was submitted to the backend.
+    pub fn submitted(&self, id: Option<String>) {
+        if let Some(id) = &id {
+            self.span.record("job", id.as_str());
+        }
+
+        self.emit(TaskState::Submitted { id });
+    }
+
+    /// Waits until the runner's rate limit allows another submission.
+    ///
+    /// The runner takes the token for the first submission of each attempt
+    /// before handing the task to the backend, so this only waits for later
+    /// submissions (e.g., one for each execution). Backends should call this
+    /// before each call that submits work (e.g., before each `bsub`).
+    pub async fn submitting(&self) {
+        if !self.granted.swap(false, Ordering::SeqCst) {
+            self.limits.submission().await;
+        }
+    }
+
+    /// Polls the state of submitted work once the runner's rate limit allows
+    /// it.
+    ///
+    /// Backends must make each call that polls the state of submitted work
+    /// (e.g., each `bjobs`) through this method.
+    pub async fn poll<F: Future>(&self, poll: F) -> F::Output {
+        self.limits.monitor().await;
+        poll.await
+    }
+
+    /// Reports that the task began running.
+    pub fn running(&self) {
+        self.emit(TaskState::Running);
+    }
+}
@@ -0,0 +1,168 @@
+//! Rate limiting of the calls made to a backend.
+//!
+//! Each rate is enforced by a token bucket that refills continuously at the
+//! rate and holds up to a second's worth of tokens (and at least one). A call
+//! takes a token from the bucket, waiting until one is available if the bucket
+//! is empty. Waiting calls reserve their tokens in the order they arrive, so
+//! calls are never starved. A call that stops waiting (e.g., because it was
+//! cancelled) returns its reserved token to the bucket.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crankshaft_config::backend::RateLimit;
+use tokio::time::Instant;
+
+/// A token bucket.
+#[derive(Debug)]
+pub(crate) struct Bucket {
+    /// The number of tokens added per second.
+    rate: f64,
+
+    /// The maximum number of tokens the bucket holds.
+    burst: f64,
+
+    /// The number of tokens in the bucket and when it was last refilled.
+    ///
+    /// The number of tokens goes negative when calls are waiting for tokens
+    /// that they have reserved.
+    state: Mutex<(f64, Instant)>,
+}
+
+impl Bucket {
+    /// Creates a new, full [`Bucket`] that refills at the provided rate (per
+    /// second).
+    ///
+    /// Returns `None` if the rate is not positive (i.e., unlimited).
+    pub(crate) fn new(rate: f64) -> Option<Self> {
+        if !(rate.is_finite() && rate > 0.0) {
+            return None;
+        }
+
+        let burst = rate.max(1.0);
+
+        Some(Self {
+            rate,
+            burst,
+            state: Mutex::new((burst, Instant::now())),
+        })
+    }
+
+    /// Refills the bucket for the time elapsed since it was last refilled.
+    fn refill(&self, tokens: &mut f64, refilled: &mut Instant) {
+        let now = Instant::now();
+        *tokens = (*tokens + (now - *refilled).as_secs_f64() * self.rate).min(self.burst);
+        *refilled = now;
+    }
+
+    /// Takes a token from the bucket, waiting until one is available.
+    pub(crate) async fn acquire(&self) {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let (tokens, refilled) = &mut *state;
+
+            self.refill(tokens, refilled);
+            *tokens -= 1.0;
+
+            match *tokens < 0.0 {
+                true => Duration::from_secs_f64(-*tokens / self.rate),
+                false => Duration::ZERO,
+            }
+        };
+
+        if !wait.is_zero() {
+            // NOTE: the token was reserved above, so it is refunded if this
+            // future is dropped before the wait completes.
+            let reservation = Reservation(self);
+            tokio::time::sleep(wait).await;
+            std::mem::forget(reservation);
+        }
+    }
+}
+
+/// A token reserved from a [`Bucket`] by a call that is still waiting for it.
+///
+/// Dropping the reservation returns the token to the bucket.
+struct Reservation<'a>(&'a Bucket);
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        let (tokens, refilled) = &mut *state;
+
+        self.0.refill(tokens, refilled);
+        *tokens = (*tokens + 1.0).min(self.0.burst);
+    }
+}
+
+/// The rate limits of the calls a runner makes to its backend.
+#[derive(Debug, Default)]
+pub(crate) struct Limits {
+    /// The limit on submissions (if there is one).
+    submissions: Option<Bucket>,
+
+    /// The limit on calls to monitor submitted tasks (if there is one).
+    monitors: Option<Bucket>,
+}
+
+impl Limits {
+    /// Creates the limits described by a [`RateLimit`].
+    pub(crate) fn new(limit: &RateLimit) -> Self {
+        Self {
+            submissions: limit.submissions().and_then(Bucket::new),
+            monitors: limit.monitors().and_then(Bucket::new),
+        }
+    }
+
+    /// Waits until another submission is allowed.
+    pub(crate) async fn submission(&self) {
+        if let Some(bucket) = &self.submissions {
+            bucket.acquire().await;
+        }
+    }
+
+    /// Waits until another call to monitor a submitted task is allowed.
+    pub(crate) async fn monitor(&self) {
+        if let Some(bucket) = &self.monitors {
+            bucket.acquire().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn calls_beyond_the_burst_wait_for_tokens() {
+        let bucket = Bucket::new(20.0).unwrap();
+        let started = Instant::now();
+
+        // NOTE: the first twenty calls drain the bucket, and the next two wait
+        // for a token each.
+        for _ in 0..22 {
+            bucket.acquire().await;
+        }
+
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(95), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(1), "{elapsed:?}");
+
+        assert!(Bucket::new(0.0).is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn abandoned_waits_refund_their_tokens() {
+        let bucket = Bucket::new(1.0).unwrap();
+        bucket.acquire().await;
+
+        // NOTE: the second call reserves the next token and gives up halfway
+        // through its wait.
+        let abandoned = tokio::time::timeout(Duration::from_millis(500), bucket.acquire()).await;
+        assert!(abandoned.is_err());
+
+        let started = Instant::now();
+        bucket.acquire().await;
+        assert_eq!(started.elapsed(), Duration::from_millis(500));
+    }
+}
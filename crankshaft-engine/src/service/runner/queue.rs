@@ -0,0 +1,699 @@
+//! A priority queue for the slots in which tasks run.
+//!
+//! A runner can only run so many tasks at once. When every slot is taken,
+//! tasks wait in a queue and, as slots free up, the waiting task with the
+//! highest [priority](crate::Task::priority) is started first (ties are broken
+//! in the order the tasks started waiting).
+//!
+//! Slots are limited both by the number of tasks running and, when the
+//! backend declares its [`Capacity`], by the resources those tasks use: a task
+//! is only started while the sum of the [resources](crate::task::Resources) of
+//! the running tasks (including its own) fits within the capacity. A task that
+//! requests more than the whole capacity is started once it would be the only
+//! task running.
+//!
+//! Tasks can be placed in [groups](crate::task::Builder::group) (e.g., by
+//! project or owner). When tasks from multiple groups are waiting, slots are
+//! shared between the groups by weighted fair share: the next task is taken
+//! from the group running the fewest tasks relative to its [weight](Group),
+//! and a group may also be capped at a maximum number of running tasks. Tasks
+//! without a group belong to a group of their own with the
+//! [default weight](DEFAULT_WEIGHT). Priorities order the tasks within a
+//! group.
+//!
+//! So that a steady stream of urgent tasks can never starve less urgent ones
+//! forever, waiting tasks _age_: every [aging interval](DEFAULT_AGING_INTERVAL)
+//! a task spends waiting raises its priority by one.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crankshaft_config::backend::Capacity;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+use crate::task::Resources;
+
+/// The amount of time a task must wait to have its priority raised by one.
+pub const DEFAULT_AGING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// An amount of resources.
+///
+/// RAM and disk are accounted in whole megabytes so that summing them never
+/// accumulates floating point error.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Usage {
+    /// The number of CPUs.
+    cpu: u64,
+
+    /// The amount of RAM (in MB).
+    ram: u64,
+
+    /// The amount of disk (in MB).
+    disk: u64,
+}
+
+impl Usage {
+    /// An unlimited amount of resources.
+    const UNLIMITED: Self = Self {
+        cpu: u64::MAX,
+        ram: u64::MAX,
+        disk: u64::MAX,
+    };
+
+    /// Gets the amount of resources a task with the provided (resolved)
+    /// resources uses.
+    ///
+    /// Resources that are not specified are not accounted for.
+    pub(crate) fn of(resources: &Resources) -> Self {
+        Self {
+            cpu: resources.cpu().unwrap_or(0) as u64,
+            ram: resources.ram().map(megabytes).unwrap_or(0),
+            disk: resources.disk().map(megabytes).unwrap_or(0),
+        }
+    }
+
+    /// Gets the limits imposed by a capacity.
+    ///
+    /// Resources that are not specified are unlimited.
+    fn limits(capacity: &Capacity) -> Self {
+        Self {
+            cpu: capacity.cpu().map(|cpu| cpu as u64).unwrap_or(u64::MAX),
+            ram: capacity.ram().map(megabytes).unwrap_or(u64::MAX),
+            disk: capacity.disk().map(megabytes).unwrap_or(u64::MAX),
+        }
+    }
+
+    /// Clamps each resource to at most the provided limits.
+    fn clamp(self, limits: Self) -> Self {
+        Self {
+            cpu: self.cpu.min(limits.cpu),
+            ram: self.ram.min(limits.ram),
+            disk: self.disk.min(limits.disk),
+        }
+    }
+
+    /// Returns whether adding `self` to `used` stays within `limits`.
+    fn fits(self, used: Self, limits: Self) -> bool {
+        used.cpu.saturating_add(self.cpu) <= limits.cpu
+            && used.ram.saturating_add(self.ram) <= limits.ram
+            && used.disk.saturating_add(self.disk) <= limits.disk
+    }
+
+    /// Adds the resources in `other`.
+    fn add(&mut self, other: Self) {
+        self.cpu += other.cpu;
+        self.ram += other.ram;
+        self.disk += other.disk;
+    }
+
+    /// Subtracts the resources in `other`.
+    fn sub(&mut self, other: Self) {
+        self.cpu -= other.cpu;
+        self.ram -= other.ram;
+        self.disk -= other.disk;
+    }
+}
+
+/// Converts an amount in gigabytes to whole megabytes (rounding up).
+fn megabytes(gigabytes: f64) -> u64 {
+    (gigabytes * 1024.).ceil() as u64
+}
+
+/// The weight of groups that are not configured.
+pub const DEFAULT_WEIGHT: u32 = 1;
+
+/// A task waiting for a slot.
+#[derive(Debug)]
+struct Waiter {
+    /// The score the waiter is ordered by.
+    score: i128,
+
+    /// The priority of the task.
+    priority: i32,
+
+    /// When the waiter started waiting (relative to the queue's origin).
+    since: Duration,
+
+    /// The order in which the waiter started waiting.
+    sequence: u64,
+
+    /// The resources the task uses.
+    usage: Usage,
+
+    /// The channel over which the waiter is handed its permit.
+    sender: oneshot::Sender<Permit>,
+}
+
+impl Waiter {
+    /// Computes the score of a waiter with the provided priority that started
+    /// waiting at the provided time.
+    ///
+    /// As every waiter ages at the same rate, the order of two waiters never
+    /// changes while they wait. Thus, rather than recomputing the aged
+    /// priority of every waiter whenever a slot frees up, each waiter is scored
+    /// once by its priority (scaled by the aging interval) less the time at
+    /// which it started waiting.
+    fn score(priority: i32, since: Duration, aging: Duration) -> i128 {
+        i128::from(priority) * aging.as_nanos() as i128 - since.as_nanos() as i128
+    }
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // NOTE: the heap pops the greatest waiter first, so waiters that
+        // started waiting earlier must compare as greater.
+        self.score
+            .cmp(&other.score)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+/// The share of a runner's slots configured for a group of tasks.
+#[derive(Clone, Debug)]
+pub struct Group {
+    /// The weight of the group.
+    weight: u32,
+
+    /// The maximum number of the group's tasks that may run at once.
+    max_tasks: Option<usize>,
+}
+
+impl Group {
+    /// Creates a new [`Group`] with the provided weight and (optionally) a
+    /// maximum number of its tasks that may run at once.
+    ///
+    /// A weight of zero is treated as a weight of one.
+    pub fn new(weight: u32, max_tasks: Option<usize>) -> Self {
+        Self {
+            weight: weight.max(1),
+            max_tasks,
+        }
+    }
+
+    /// Gets the weight of the group.
+    pub fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    /// Gets the maximum number of the group's tasks that may run at once (if
+    /// specified).
+    pub fn max_tasks(&self) -> Option<usize> {
+        self.max_tasks
+    }
+}
+
+impl Default for Group {
+    fn default() -> Self {
+        Self::new(DEFAULT_WEIGHT, None)
+    }
+}
+
+/// The tasks of a group that are running or waiting for a slot.
+#[derive(Debug, Default)]
+struct Members {
+    /// The number of the group's tasks holding a permit.
+    running: usize,
+
+    /// The group's tasks waiting for a slot.
+    waiting: BinaryHeap<Waiter>,
+}
+
+/// The state of a queue.
+#[derive(Debug)]
+struct State {
+    /// The number of tasks that may run at once.
+    max_tasks: usize,
+
+    /// The number of tasks holding a permit.
+    running: usize,
+
+    /// The resources that may be used at once.
+    limits: Usage,
+
+    /// The resources used by the tasks holding a permit.
+    used: Usage,
+
+    /// The amount of time a task must wait to have its priority raised by one.
+    aging: Duration,
+
+    /// The configured groups.
+    groups: HashMap<String, Group>,
+
+    /// The tasks running or waiting for a slot (by group).
+    members: HashMap<Option<String>, Members>,
+
+    /// The sequence number of the next waiter.
+    sequence: u64,
+}
+
+impl State {
+    /// Gets the configuration of a group.
+    fn group(&self, name: Option<&String>) -> Group {
+        name.and_then(|name| self.groups.get(name))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Forgets a group once it has neither running nor waiting tasks.
+    fn prune(&mut self, group: &Option<String>) {
+        if self
+            .members
+            .get(group)
+            .is_some_and(|members| members.running == 0 && members.waiting.is_empty())
+        {
+            self.members.remove(group);
+        }
+    }
+
+    /// Picks the group whose task should be started next (if any).
+    ///
+    /// Of the groups with a waiting task that are below their own maximum,
+    /// the one running the fewest tasks relative to its weight is picked (ties
+    /// are broken by the next waiting task of each group).
+    fn next(&self) -> Option<Option<String>> {
+        self.members
+            .iter()
+            .filter_map(|(name, members)| {
+                let group = self.group(name.as_ref());
+                let below = group
+                    .max_tasks
+                    .map(|max| members.running < max)
+                    .unwrap_or(true);
+
+                match (below, members.waiting.peek()) {
+                    (true, Some(waiter)) => Some((name, members.running, group.weight, waiter)),
+                    _ => None,
+                }
+            })
+            .max_by(|(_, a_running, a_weight, a), (_, b_running, b_weight, b)| {
+                // NOTE: `a_running / a_weight < b_running / b_weight` is
+                // compared without dividing, and a lower share is better.
+                let a_share = *a_running as u128 * u128::from(*b_weight);
+                let b_share = *b_running as u128 * u128::from(*a_weight);
+                b_share.cmp(&a_share).then_with(|| a.cmp(b))
+            })
+            .map(|(name, ..)| name.clone())
+    }
+}
+
+/// A priority queue for the slots in which tasks run.
+#[derive(Debug)]
+pub(crate) struct Queue {
+    /// The state of the queue.
+    state: Mutex<State>,
+
+    /// The instant against which waiting times are measured.
+    origin: Instant,
+}
+
+impl Queue {
+    /// Creates a new [`Queue`] that allows `max_tasks` tasks to run at once.
+    pub(crate) fn new(max_tasks: usize) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(State {
+                max_tasks,
+                running: 0,
+                limits: Usage::UNLIMITED,
+                used: Default::default(),
+                aging: DEFAULT_AGING_INTERVAL,
+                groups: Default::default(),
+                members: Default::default(),
+                sequence: 0,
+            }),
+            origin: Instant::now(),
+        })
+    }
+
+    /// Sets the amount of time a task must wait to have its priority raised
+    /// by one.
+    ///
+    /// Tasks that are already waiting are rescored as if they had been aging
+    /// at the new interval since they started waiting.
+    pub(crate) fn set_aging(self: &Arc<Self>, interval: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.aging = interval;
+
+        for members in state.members.values_mut() {
+            members.waiting = std::mem::take(&mut members.waiting)
+                .into_iter()
+                .map(|mut waiter| {
+                    waiter.score = Waiter::score(waiter.priority, waiter.since, interval);
+                    waiter
+                })
+                .collect();
+        }
+
+        self.dispatch(&mut state);
+    }
+
+    /// Limits the resources that may be used at once to the provided capacity.
+    pub(crate) fn set_capacity(self: &Arc<Self>, capacity: &Capacity) {
+        let mut state = self.state.lock().unwrap();
+        state.limits = Usage::limits(capacity);
+        self.dispatch(&mut state);
+    }
+
+    /// Sets the number of tasks that may run at once.
+    ///
+    /// Lowering the number never interrupts running tasks; it only holds back
+    /// waiting tasks until enough running ones release their permits.
+    pub(crate) fn set_max_tasks(self: &Arc<Self>, max_tasks: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.max_tasks = max_tasks;
+        self.dispatch(&mut state);
+    }
+
+    /// Configures the share of the slots for a group of tasks.
+    pub(crate) fn set_group(self: &Arc<Self>, name: String, group: Group) {
+        let mut state = self.state.lock().unwrap();
+        state.groups.insert(name, group);
+        self.dispatch(&mut state);
+    }
+
+    /// Gets the number of tasks that may run at once.
+    pub(crate) fn max_tasks(&self) -> usize {
+        self.state.lock().unwrap().max_tasks
+    }
+
+    /// Returns whether a task that uses the provided resources fits within the
+    /// capacity at all (when nothing else is running).
+    pub(crate) fn fits(&self, usage: Usage) -> bool {
+        usage.fits(Usage::default(), self.state.lock().unwrap().limits)
+    }
+
+    /// Gets the number of tasks holding a permit.
+    pub(crate) fn running(&self) -> usize {
+        self.state.lock().unwrap().running
+    }
+
+    /// Gets the number of tasks waiting for a slot.
+    pub(crate) fn waiting(&self) -> usize {
+        self.state
+            .lock()
+            .unwrap()
+            .members
+            .values()
+            .flat_map(|members| members.waiting.iter())
+            .filter(|waiter| !waiter.sender.is_closed())
+            .count()
+    }
+
+    /// Gets whether any task is waiting for a slot.
+    pub(crate) fn is_waiting(&self) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .members
+            .values()
+            .flat_map(|members| members.waiting.iter())
+            .any(|waiter| !waiter.sender.is_closed())
+    }
+
+    /// Waits for a slot for a task in the provided group with the provided
+    /// priority that uses the provided resources.
+    ///
+    /// The slot is held until the returned [`Permit`] is dropped. Dropping the
+    /// returned future gives up the task's place in the queue.
+    pub(crate) async fn acquire(
+        self: &Arc<Self>,
+        group: Option<String>,
+        priority: i32,
+        usage: Usage,
+    ) -> Permit {
+        let receiver = {
+            let mut state = self.state.lock().unwrap();
+
+            let since = Instant::now().duration_since(self.origin);
+            let score = Waiter::score(priority, since, state.aging);
+
+            let (sender, receiver) = oneshot::channel();
+            let sequence = state.sequence;
+            state.sequence += 1;
+            state
+                .members
+                .entry(group)
+                .or_default()
+                .waiting
+                .push(Waiter {
+                    score,
+                    priority,
+                    since,
+                    sequence,
+                    usage,
+                    sender,
+                });
+
+            // NOTE: every task goes through the queue (even when a slot is
+            // free) so that a task never jumps ahead of a waiting task that
+            // should be started before it but doesn't yet fit.
+            self.dispatch(&mut state);
+            receiver
+        };
+
+        // SAFETY: a waiter is only ever removed from the queue by sending it a
+        // permit (or once it has given up waiting), and the queue outlives this
+        // future (as it holds a reference to it).
+        receiver.await.unwrap()
+    }
+
+    /// Releases a slot and hands it to the next waiting task (if any).
+    fn release(self: &Arc<Self>, group: Option<String>, usage: Usage) {
+        let mut state = self.state.lock().unwrap();
+        state.running -= 1;
+        state.used.sub(usage);
+
+        if let Some(members) = state.members.get_mut(&group) {
+            members.running -= 1;
+            state.prune(&group);
+        }
+
+        self.dispatch(&mut state);
+    }
+
+    /// Hands out permits to waiting tasks while the next task fits.
+    fn dispatch(self: &Arc<Self>, state: &mut State) {
+        while state.running < state.max_tasks {
+            let Some(group) = state.next() else {
+                break;
+            };
+
+            // SAFETY: the group was just picked because it has a waiter.
+            let members = state.members.get_mut(&group).unwrap();
+            let waiter = members.waiting.peek().unwrap();
+
+            // NOTE: waiters that have given up their place in the queue are
+            // only removed once they reach the front of it (rather than
+            // searching the whole queue on every dispatch, which is quadratic
+            // in the number of waiting tasks).
+            if waiter.sender.is_closed() {
+                members.waiting.pop();
+                state.prune(&group);
+                continue;
+            }
+
+            // NOTE: a task that requests more than the whole capacity would
+            // never fit, so it only waits for every other task to finish.
+            let usage = waiter.usage.clamp(state.limits);
+
+            if !usage.fits(state.used, state.limits) {
+                break;
+            }
+
+            // SAFETY: the waiter was just peeked.
+            let waiter = members.waiting.pop().unwrap();
+            members.running += 1;
+            state.running += 1;
+            state.used.add(usage);
+
+            // NOTE: if the waiter has given up its place in the queue since it
+            // was checked above, the permit is returned. It is defused (rather
+            // than dropped) so that it doesn't try to take the lock that is
+            // already held here.
+            let permit = Permit::new(self.clone(), group.clone(), usage);
+            let result = waiter.sender.send(permit);
+            if let Err(mut permit) = result {
+                permit.queue = None;
+                state.running -= 1;
+                state.used.sub(usage);
+
+                // SAFETY: the group was just looked up above.
+                state.members.get_mut(&group).unwrap().running -= 1;
+                state.prune(&group);
+            }
+        }
+    }
+}
+
+/// A held slot in a [`Queue`].
+///
+/// The slot is released when the permit is dropped.
+#[derive(Debug)]
+pub(crate) struct Permit {
+    /// The queue the slot belongs to.
+    queue: Option<Arc<Queue>>,
+
+    /// The group the slot belongs to.
+    group: Option<String>,
+
+    /// The resources the slot accounts for.
+    usage: Usage,
+}
+
+impl Permit {
+    /// Creates a new [`Permit`] for a slot in the provided queue.
+    fn new(queue: Arc<Queue>, group: Option<String>, usage: Usage) -> Self {
+        Self {
+            queue: Some(queue),
+            group,
+            usage,
+        }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(queue) = self.queue.take() {
+            queue.release(self.group.take(), self.usage);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::pin;
+
+    use futures::poll;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn higher_priorities_acquire_first() {
+        let queue = Queue::new(1);
+        let held = queue.acquire(None, 0, Usage::default()).await;
+
+        let mut low = pin!(queue.acquire(None, -1, Usage::default()));
+        let mut high = pin!(queue.acquire(None, 1, Usage::default()));
+        assert!(poll!(&mut low).is_pending());
+        assert!(poll!(&mut high).is_pending());
+        assert_eq!(queue.waiting(), 2);
+
+        drop(held);
+        assert!(poll!(&mut low).is_pending());
+        let held = high.await;
+
+        drop(held);
+        low.await;
+    }
+
+    #[tokio::test]
+    async fn waiting_tasks_age() {
+        let queue = Queue::new(1);
+        queue.set_aging(Duration::from_millis(1));
+        let held = queue.acquire(None, 0, Usage::default()).await;
+
+        let mut old = pin!(queue.acquire(None, 0, Usage::default()));
+        assert!(poll!(&mut old).is_pending());
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let mut new = pin!(queue.acquire(None, 10, Usage::default()));
+        assert!(poll!(&mut new).is_pending());
+
+        drop(held);
+        assert!(poll!(&mut new).is_pending());
+        old.await;
+    }
+
+    #[tokio::test]
+    async fn changing_the_aging_rescores_waiting_tasks() {
+        let queue = Queue::new(1);
+        let held = queue.acquire(None, 0, Usage::default()).await;
+
+        let mut old = pin!(queue.acquire(None, 0, Usage::default()));
+        assert!(poll!(&mut old).is_pending());
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let mut new = pin!(queue.acquire(None, 10, Usage::default()));
+        assert!(poll!(&mut new).is_pending());
+
+        // NOTE: with the default interval, the new task's priority wins, but
+        // the old task has waited long enough to win at the new interval.
+        queue.set_aging(Duration::from_millis(1));
+
+        drop(held);
+        assert!(poll!(&mut new).is_pending());
+        old.await;
+    }
+
+    #[tokio::test]
+    async fn tasks_are_admitted_while_their_resources_fit() {
+        let queue = Queue::new(10);
+        queue.set_capacity(&Capacity::new(Some(4), None, None));
+
+        let cpu = |cpu| Usage {
+            cpu,
+            ..Default::default()
+        };
+
+        let large = queue.acquire(None, 0, cpu(3)).await;
+        let small = queue.acquire(None, 0, cpu(1)).await;
+
+        let mut medium = pin!(queue.acquire(None, 0, cpu(2)));
+        let mut huge = pin!(queue.acquire(None, 0, cpu(8)));
+        assert!(poll!(&mut medium).is_pending());
+        assert!(poll!(&mut huge).is_pending());
+
+        drop(large);
+        let medium = medium.await;
+        assert!(poll!(&mut huge).is_pending());
+
+        drop(small);
+        drop(medium);
+        huge.await;
+    }
+
+    #[tokio::test]
+    async fn groups_share_slots_fairly() {
+        let queue = Queue::new(3);
+        queue.set_group(String::from("capped"), Group::new(1, Some(1)));
+
+        let a = || Some(String::from("a"));
+        let first = queue.acquire(a(), 0, Usage::default()).await;
+        let _second = queue.acquire(a(), 0, Usage::default()).await;
+        let _capped = queue
+            .acquire(Some(String::from("capped")), 0, Usage::default())
+            .await;
+
+        let mut third = pin!(queue.acquire(a(), 0, Usage::default()));
+        let mut b = pin!(queue.acquire(Some(String::from("b")), 0, Usage::default()));
+        let mut over = pin!(queue.acquire(Some(String::from("capped")), 0, Usage::default()));
+        assert!(poll!(&mut third).is_pending());
+        assert!(poll!(&mut b).is_pending());
+        assert!(poll!(&mut over).is_pending());
+
+        drop(first);
+        assert!(poll!(&mut third).is_pending());
+        assert!(poll!(&mut over).is_pending());
+        b.await;
+    }
+}
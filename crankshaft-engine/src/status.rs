@@ -0,0 +1,229 @@
+//! Task statuses.
+//!
+//! Every task submitted to the engine has a [`Status`] that is kept up to date
+//! as the task transitions between states. The status of a single task can be
+//! looked up by its id ([`Engine::status()`]), and the statuses of many tasks
+//! can be listed at once ([`Engine::list()`]) to get a snapshot of the engine.
+//!
+//! Statuses are kept for as long as the engine is alive (including those of
+//! tasks that have completed).
+//!
+//! [`Engine::status()`]: crate::Engine::status
+//! [`Engine::list()`]: crate::Engine::list
+
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use indexmap::IndexMap;
+
+use crate::service::runner::TaskId;
+use crate::service::runner::backend::TaskError;
+use crate::service::runner::event::TaskState;
+
+/// A snapshot of the status of a task.
+#[derive(Clone, Debug)]
+pub struct Status {
+    /// The id of the task.
+    id: TaskId,
+
+    /// The name of the task (if it has one).
+    name: Option<String>,
+
+    /// The name of the backend the task was submitted to.
+    backend: &'static str,
+
+    /// The state the task is in.
+    state: TaskState,
+
+    /// The identifier the backend uses for the task (if it has reported one).
+    job: Option<String>,
+
+    /// The number of the current attempt (starting at one).
+    attempt: u32,
+
+    /// When the task was submitted.
+    submitted: SystemTime,
+
+    /// When the current attempt began running (if it has).
+    started: Option<SystemTime>,
+
+    /// The last error the task failed with (if any).
+    error: Option<TaskError>,
+}
+
+impl Status {
+    /// Gets the id of the task.
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    /// Gets the name of the task (if it has one).
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Gets the name of the backend the task was submitted to.
+    ///
+    /// If the task [failed over](crate::routing) to another runner, this is
+    /// the backend of the runner it is on now.
+    pub fn backend(&self) -> &str {
+        self.backend
+    }
+
+    /// Gets the state the task is in.
+    pub fn state(&self) -> &TaskState {
+        &self.state
+    }
+
+    /// Gets the identifier the backend uses for the task (e.g., a job id or a
+    /// container name), if the backend has reported one.
+    ///
+    /// For tasks that run in multiple units, this is the identifier of the
+    /// latest one.
+    pub fn job(&self) -> Option<&str> {
+        self.job.as_deref()
+    }
+
+    /// Gets the number of the current attempt (starting at one).
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Gets when the task was submitted.
+    pub fn submitted(&self) -> SystemTime {
+        self.submitted
+    }
+
+    /// Gets when the current attempt began running (if it has, and the backend
+    /// reports it).
+    pub fn started(&self) -> Option<SystemTime> {
+        self.started
+    }
+
+    /// Gets the last error the task failed with (including errors of attempts
+    /// that were retried).
+    pub fn error(&self) -> Option<&TaskError> {
+        self.error.as_ref()
+    }
+
+    /// Gets whether the task has finished (successfully or not).
+    pub fn finished(&self) -> bool {
+        matches!(self.state, TaskState::Completed | TaskState::Failed(_))
+    }
+}
+
+/// A filter for [listing](crate::Engine::list) the statuses of tasks.
+///
+/// The default filter matches every task.
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    /// The name of the backend tasks must have been submitted to.
+    backend: Option<String>,
+
+    /// Whether tasks must have finished (or must not have).
+    finished: Option<bool>,
+}
+
+impl Filter {
+    /// Only matches tasks submitted to the backend with the provided name.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous backend provided to the
+    /// filter.
+    pub fn backend(mut self, name: impl Into<String>) -> Self {
+        self.backend = Some(name.into());
+        self
+    }
+
+    /// Only matches tasks that have finished (if `true`) or that are still in
+    /// flight (if `false`).
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous value provided to the filter.
+    pub fn finished(mut self, finished: bool) -> Self {
+        self.finished = Some(finished);
+        self
+    }
+
+    /// Gets whether the filter matches a status.
+    pub fn matches(&self, status: &Status) -> bool {
+        self.backend
+            .as_ref()
+            .is_none_or(|backend| backend == status.backend)
+            && self
+                .finished
+                .is_none_or(|finished| finished == status.finished())
+    }
+}
+
+/// The statuses of submitted tasks.
+#[derive(Debug, Default)]
+pub(crate) struct Registry {
+    /// The status of each task (in the order they were submitted).
+    statuses: Mutex<IndexMap<TaskId, Status>>,
+}
+
+impl Registry {
+    /// Records a task being submitted to a backend.
+    ///
+    /// A task that moves to another runner (e.g., when failing over) starts
+    /// over with a new status.
+    pub(crate) fn submitted(&self, id: TaskId, name: Option<&str>, backend: &'static str) {
+        self.statuses.lock().unwrap().insert(
+            id,
+            Status {
+                id,
+                name: name.map(ToOwned::to_owned),
+                backend,
+                state: TaskState::Queued,
+                job: None,
+                attempt: 1,
+                submitted: SystemTime::now(),
+                started: None,
+                error: None,
+            },
+        );
+    }
+
+    /// Records a task transitioning to a new state.
+    pub(crate) fn transition(&self, id: TaskId, state: &TaskState, timestamp: SystemTime) {
+        let mut statuses = self.statuses.lock().unwrap();
+        let Some(status) = statuses.get_mut(&id) else {
+            return;
+        };
+
+        match state {
+            TaskState::Submitted { id: Some(job) } => status.job = Some(job.clone()),
+            TaskState::Running => {
+                status.started.get_or_insert(timestamp);
+            }
+            TaskState::Retrying { attempt, error, .. } => {
+                status.attempt = attempt + 1;
+                status.started = None;
+                status.error = Some(error.clone());
+            }
+            TaskState::Failed(error) => status.error = Some(error.clone()),
+            _ => {}
+        }
+
+        status.state = state.clone();
+    }
+
+    /// Gets the status of a task.
+    pub(crate) fn get(&self, id: TaskId) -> Option<Status> {
+        self.statuses.lock().unwrap().get(&id).cloned()
+    }
+
+    /// Lists the statuses of the tasks that match a filter.
+    pub(crate) fn list(&self, filter: &Filter) -> Vec<Status> {
+        self.statuses
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|status| filter.matches(status))
+            .cloned()
+            .collect()
+    }
+}
@@ -1,5 +1,7 @@
 //! Tasks that can be run by execution runners.
 
+use std::time::Duration;
+
 use nonempty::NonEmpty;
 
 mod builder;
@@ -7,6 +9,7 @@ pub mod execution;
 pub mod input;
 pub mod output;
 pub mod resources;
+pub mod retry;
 
 pub use builder::Builder;
 pub use execution::Execution;
@@ -14,6 +17,9 @@ pub use input::Input;
 pub use output::Output;
 pub use resources::Resources;
 
+/// The priority of tasks that are not given one.
+pub const DEFAULT_PRIORITY: i32 = 0;
+
 /// A task intended for execution.
 #[derive(Clone, Debug)]
 
@@ -38,6 +44,24 @@ pub struct Task {
 
     /// The list of volumes shared across executions in the task.
     shared_volumes: Option<NonEmpty<String>>,
+
+    /// An optional retry [`Policy`](retry::Policy).
+    retry_policy: Option<retry::Policy>,
+
+    /// An optional maximum amount of time each attempt may run for.
+    timeout: Option<Duration>,
+
+    /// The priority.
+    priority: i32,
+
+    /// An optional group (e.g., the project or owner submitting the task).
+    group: Option<String>,
+
+    /// An optional list of labels a runner must have to run the task.
+    labels: Option<NonEmpty<String>>,
+
+    /// Whether the executions must run within their container images.
+    requires_container: bool,
 }
 
 impl Task {
@@ -66,6 +90,23 @@ impl Task {
         self.inputs.as_ref().map(|inputs| inputs.iter())
     }
 
+    /// Adds inputs to the task.
+    pub(crate) fn extend_inputs(&mut self, inputs: impl IntoIterator<Item = Input>) {
+        let mut new = inputs.into_iter();
+
+        self.inputs = match self.inputs.take() {
+            Some(mut inputs) => {
+                inputs.extend(new);
+                Some(inputs)
+            }
+            None => new.next().map(|input| {
+                let mut inputs = NonEmpty::new(input);
+                inputs.extend(new);
+                inputs
+            }),
+        };
+    }
+
     /// Gets the outputs for the task (if any exist).
     pub fn outputs(&self) -> Option<impl Iterator<Item = &Output>> {
         self.outputs.as_ref().map(|outputs| outputs.iter())
@@ -87,4 +128,57 @@ impl Task {
             .as_ref()
             .map(|volumes| volumes.iter().map(|a| a.as_str()))
     }
+
+    /// Gets the retry policy for the task (if one is specified).
+    ///
+    /// Tasks without a retry policy are attempted once.
+    pub fn retry_policy(&self) -> Option<&retry::Policy> {
+        self.retry_policy.as_ref()
+    }
+
+    /// Gets the maximum amount of time each attempt at running the task may
+    /// run for (if one is specified).
+    ///
+    /// This bounds all of the task's executions together (in addition to any
+    /// [timeouts on the executions](Execution::timeout) themselves).
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Gets the priority of the task.
+    ///
+    /// When a runner has more tasks than it can run at once, tasks with a
+    /// higher priority are started first. Tasks that are not given a priority
+    /// have the [default priority](DEFAULT_PRIORITY).
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Gets the group of the task (if it exists).
+    ///
+    /// Runners share their slots between groups by weighted fair share (see
+    /// the [`queue`](crate::service::runner::queue) module).
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    /// Gets the labels a runner must have to run the task (if any exist).
+    ///
+    /// Labels are only considered when the task is
+    /// [routed](crate::Engine::submit_routed).
+    pub fn labels(&self) -> Option<impl Iterator<Item = &str>> {
+        self.labels
+            .as_ref()
+            .map(|labels| labels.iter().map(String::as_str))
+    }
+
+    /// Gets whether the task's executions must run within their container
+    /// images.
+    ///
+    /// This is only considered when the task is
+    /// [routed](crate::Engine::submit_routed). Tasks require a container
+    /// unless told otherwise.
+    pub fn requires_container(&self) -> bool {
+        self.requires_container
+    }
 }
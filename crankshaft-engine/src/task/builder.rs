@@ -1,12 +1,16 @@
 //! A builder for a [`Task`].
 
+use std::time::Duration;
+
 use nonempty::NonEmpty;
 
 use crate::Task;
+use crate::task::DEFAULT_PRIORITY;
 use crate::task::Execution;
 use crate::task::Input;
 use crate::task::Output;
 use crate::task::Resources;
+use crate::task::retry::Policy;
 
 /// An error related to a [`Builder`].
 #[derive(Debug)]
@@ -59,6 +63,24 @@ pub struct Builder {
 
     /// The list of volumes shared across executions in the task.
     shared_volumes: Option<NonEmpty<String>>,
+
+    /// An optional retry policy.
+    retry_policy: Option<Policy>,
+
+    /// An optional maximum amount of time each attempt may run for.
+    timeout: Option<Duration>,
+
+    /// An optional priority.
+    priority: Option<i32>,
+
+    /// An optional group.
+    group: Option<String>,
+
+    /// An optional list of labels a runner must have to run the task.
+    labels: Option<NonEmpty<String>>,
+
+    /// Whether the executions must run within their container images.
+    requires_container: Option<bool>,
 }
 
 impl Builder {
@@ -195,6 +217,92 @@ impl Builder {
         self
     }
 
+    /// Adds a retry policy to the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous retry policy provided to the
+    /// builder.
+    pub fn retry_policy(mut self, policy: Policy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Adds a maximum amount of time each attempt at running the task may run
+    /// for to the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous timeout provided to the
+    /// builder.
+    pub fn timeout(mut self, value: Duration) -> Self {
+        self.timeout = Some(value);
+        self
+    }
+
+    /// Adds a priority to the [`Builder`].
+    ///
+    /// Higher values are more urgent.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous priority provided to the
+    /// builder.
+    pub fn priority(mut self, value: i32) -> Self {
+        self.priority = Some(value);
+        self
+    }
+
+    /// Adds a group (e.g., the project or owner submitting the task) to the
+    /// [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous group provided to the
+    /// builder.
+    pub fn group<S: Into<String>>(mut self, group: S) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+
+    /// Extends the set of labels a runner must have to run the task within
+    /// the [`Builder`].
+    pub fn extend_labels<Iter>(mut self, labels: Iter) -> Self
+    where
+        Iter: IntoIterator<Item = String>,
+    {
+        let mut new = labels.into_iter();
+
+        self.labels = match self.labels {
+            Some(mut labels) => {
+                labels.extend(new);
+                Some(labels)
+            }
+            None => match new.next() {
+                Some(label) => {
+                    let mut labels: NonEmpty<_> = NonEmpty::new(label);
+                    labels.extend(new);
+                    Some(labels)
+                }
+                _ => None,
+            },
+        };
+
+        self
+    }
+
+    /// Sets whether the task's executions must run within their container
+    /// images for the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous value provided to the
+    /// builder.
+    pub fn requires_container(mut self, value: bool) -> Self {
+        self.requires_container = Some(value);
+        self
+    }
+
     /// Consumes `self` and attempts to return a built [`Task`].
     pub fn try_build(self) -> Result<Task> {
         let executors = self
@@ -210,6 +318,12 @@ impl Builder {
             resources: self.resources,
             executions: executors,
             shared_volumes: self.shared_volumes,
+            retry_policy: self.retry_policy,
+            timeout: self.timeout,
+            priority: self.priority.unwrap_or(DEFAULT_PRIORITY),
+            group: self.group,
+            labels: self.labels,
+            requires_container: self.requires_container.unwrap_or(true),
         })
     }
 }
@@ -3,6 +3,7 @@
 mod builder;
 
 use std::hash::RandomState;
+use std::time::Duration;
 
 pub use builder::Builder;
 use indexmap::IndexMap;
@@ -34,6 +35,9 @@ pub struct Execution {
 
     /// A map of environment variables, if configured.
     env: Option<IndexMap<String, String>>,
+
+    /// The maximum amount of time the execution may run for, if configured.
+    timeout: Option<Duration>,
 }
 
 impl Execution {
@@ -76,4 +80,9 @@ impl Execution {
     pub fn env(&self) -> Option<&IndexMap<String, String, RandomState>> {
         self.env.as_ref()
     }
+
+    /// The maximum amount of time the execution may run for.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
 }
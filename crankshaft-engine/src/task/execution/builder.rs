@@ -1,5 +1,7 @@
 //! Builders for an [`Execution`].
 
+use std::time::Duration;
+
 use indexmap::IndexMap;
 use nonempty::NonEmpty;
 
@@ -54,6 +56,9 @@ pub struct Builder {
 
     /// A map of environment variables, if configured.
     env: Option<IndexMap<String, String>>,
+
+    /// The maximum amount of time the execution may run for, if configured.
+    timeout: Option<Duration>,
 }
 
 impl Builder {
@@ -159,6 +164,18 @@ impl Builder {
         self
     }
 
+    /// Adds a maximum amount of time the execution may run for to the
+    /// [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous timeout provided to the
+    /// builder.
+    pub fn timeout(mut self, value: Duration) -> Self {
+        self.timeout = Some(value);
+        self
+    }
+
     /// Consumes `self` and attempts to return a built [`Execution`].
     pub fn try_build(self) -> Result<Execution> {
         let image = self.image.map(Ok).unwrap_or(Err(Error::Missing("image")))?;
@@ -172,6 +189,7 @@ impl Builder {
             stdout: self.stdout,
             stderr: self.stderr,
             env: self.env,
+            timeout: self.timeout,
         })
     }
 }
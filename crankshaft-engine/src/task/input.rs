@@ -2,6 +2,8 @@
 
 mod builder;
 
+use std::io::Error;
+use std::io::ErrorKind;
 use std::path::PathBuf;
 
 pub use builder::Builder;
@@ -87,22 +89,27 @@ impl Input {
     }
 
     /// Fetches the file contents via an [`AsyncRead`]er.
-    pub async fn fetch(&self) -> Vec<u8> {
+    ///
+    /// An error is returned if the contents cannot be read or if the URL
+    /// scheme is not (yet) supported.
+    pub async fn fetch(&self) -> std::io::Result<Vec<u8>> {
         match &self.contents {
-            Contents::Literal(content) => content.as_bytes().to_vec(),
+            Contents::Literal(content) => Ok(content.as_bytes().to_vec()),
             Contents::URL(url) => match url.scheme() {
                 "file" => {
-                    // SAFETY: we just checked to ensure this is a file, so
-                    // getting the file path should always unwrap.
-                    let path = url.to_file_path().unwrap();
-                    let mut file = File::open(path).await.unwrap();
+                    let path = url.to_file_path().map_err(|_| {
+                        Error::new(ErrorKind::InvalidInput, format!("invalid file URL: {url}"))
+                    })?;
+                    let mut file = File::open(path).await?;
                     let mut buffer = Vec::with_capacity(4096);
-                    file.read_to_end(&mut buffer).await.unwrap();
-                    buffer
+                    file.read_to_end(&mut buffer).await?;
+                    Ok(buffer)
                 }
-                "http" | "https" => unimplemented!("http(s) URL support not implemented"),
-                "s3" => unimplemented!("s3 URL support not implemented"),
-                v => unreachable!("unsupported URL scheme: {v}"),
+                // TODO(clay): support fetching remote contents.
+                v => Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("unsupported URL scheme: {v}"),
+                )),
             },
         }
     }
@@ -54,6 +54,47 @@ impl Resources {
         self.zones.as_ref()
     }
 
+    /// Creates a set of [`Resources`] with nothing requested.
+    pub(crate) fn empty() -> Self {
+        Self {
+            cpu: None,
+            preemptible: None,
+            ram: None,
+            disk: None,
+            zones: None,
+        }
+    }
+
+    /// Resolves the resources for a task.
+    ///
+    /// The default resources from the configuration (if provided) are applied
+    /// over `base`, and the resources from the task itself (if provided) are
+    /// applied over those. This is the relative level of priority resource
+    /// resolution should have, and the order is important to preserve.
+    ///
+    /// Returns `None` if neither defaults nor task resources are provided.
+    pub(crate) fn resolve(
+        base: Self,
+        defaults: Option<&Defaults>,
+        task: Option<&Self>,
+    ) -> Option<Self> {
+        if defaults.is_none() && task.is_none() {
+            return None;
+        }
+
+        let mut resources = base;
+
+        if let Some(defaults) = defaults {
+            resources = resources.apply(&Self::from(defaults));
+        }
+
+        if let Some(task) = task {
+            resources = resources.apply(task);
+        }
+
+        Some(resources)
+    }
+
     /// Applies any provided options in `other` to the [`Resources`].
     pub fn apply(mut self, other: &Self) -> Self {
         if let Some(cores) = other.cpu {
@@ -0,0 +1,195 @@
+//! Task retry policies.
+
+mod builder;
+
+use std::time::Duration;
+
+pub use builder::Builder;
+use rand::Rng as _;
+
+use crate::service::runner::backend::TaskError;
+
+/// The default maximum number of attempts.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 1;
+
+/// The default delay before the first retry.
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The default maximum delay between attempts.
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// The default factor the delay is multiplied by after each attempt.
+pub const DEFAULT_MULTIPLIER: f64 = 2.0;
+
+/// The default fraction of each delay that is randomized.
+pub const DEFAULT_JITTER: f64 = 0.5;
+
+/// A class of failure that may be retried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Class {
+    /// The backend could not be reached or failed while managing the task
+    /// (see [`TaskError::BackendUnavailable`]).
+    Transient,
+
+    /// The resources running the task were preempted (see
+    /// [`TaskError::Preempted`]).
+    Preempted,
+
+    /// An execution exited unsuccessfully (see
+    /// [`TaskError::ExecutionFailed`]).
+    NonZeroExit,
+
+    /// An execution ran out of memory (see [`TaskError::OutOfMemory`]).
+    OutOfMemory,
+}
+
+impl Class {
+    /// Gets the class of a [`TaskError`].
+    ///
+    /// Returns [`None`] for errors that are never retried (e.g., a task that
+    /// was cancelled).
+    pub fn of(error: &TaskError) -> Option<Self> {
+        match error {
+            TaskError::BackendUnavailable(_) => Some(Self::Transient),
+            TaskError::Preempted => Some(Self::Preempted),
+            TaskError::ExecutionFailed { .. } => Some(Self::NonZeroExit),
+            TaskError::OutOfMemory => Some(Self::OutOfMemory),
+            _ => None,
+        }
+    }
+}
+
+/// A policy for retrying a task that fails.
+///
+/// The delay before each retry grows exponentially from the initial backoff
+/// (up to the maximum backoff), and a random portion of each delay (the
+/// jitter) is subtracted so that many failing tasks do not retry in lockstep.
+#[derive(Clone, Debug)]
+pub struct Policy {
+    /// The maximum number of attempts (including the first).
+    max_attempts: u32,
+
+    /// The delay before the first retry.
+    initial_backoff: Duration,
+
+    /// The maximum delay between attempts.
+    max_backoff: Duration,
+
+    /// The factor the delay is multiplied by after each attempt.
+    multiplier: f64,
+
+    /// The fraction of each delay that is randomized.
+    jitter: f64,
+
+    /// The classes of failure that are retried.
+    classes: Vec<Class>,
+}
+
+impl Policy {
+    /// Gets a new retry policy builder.
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// Gets the maximum number of attempts (including the first).
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Gets the delay before the first retry.
+    pub fn initial_backoff(&self) -> Duration {
+        self.initial_backoff
+    }
+
+    /// Gets the maximum delay between attempts.
+    pub fn max_backoff(&self) -> Duration {
+        self.max_backoff
+    }
+
+    /// Gets the factor the delay is multiplied by after each attempt.
+    pub fn multiplier(&self) -> f64 {
+        self.multiplier
+    }
+
+    /// Gets the fraction of each delay that is randomized.
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    /// Gets the classes of failure that are retried.
+    pub fn classes(&self) -> impl Iterator<Item = Class> + '_ {
+        self.classes.iter().copied()
+    }
+
+    /// Determines whether a task should be retried after `attempt` (starting
+    /// at one) failed with `error`.
+    pub fn should_retry(&self, attempt: u32, error: &TaskError) -> bool {
+        attempt < self.max_attempts
+            && Class::of(error).is_some_and(|class| self.classes.contains(&class))
+    }
+
+    /// Gets the delay before retrying after `attempt` (starting at one).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = (self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent))
+            .min(self.max_backoff.as_secs_f64());
+
+        let jitter = if self.jitter > 0.0 {
+            rand::thread_rng().gen_range(0.0..=self.jitter)
+        } else {
+            0.0
+        };
+
+        Duration::from_secs_f64(delay * (1.0 - jitter))
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Builder::default().build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_the_maximum() {
+        let policy = Policy::builder()
+            .initial_backoff(Duration::from_secs(1))
+            .max_backoff(Duration::from_secs(5))
+            .jitter(0.0)
+            .build();
+
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(4), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn only_configured_classes_are_retried() {
+        let policy = Policy::builder()
+            .max_attempts(2u32)
+            .classes([Class::Transient])
+            .build();
+
+        let transient = TaskError::BackendUnavailable(String::from("unreachable"));
+        assert!(policy.should_retry(1, &transient));
+        assert!(!policy.should_retry(2, &transient));
+        assert!(!policy.should_retry(1, &TaskError::OutOfMemory));
+        assert!(!policy.should_retry(1, &TaskError::Cancelled));
+    }
+
+    #[test]
+    fn empty_classes_retry_all_classes() {
+        let policy = Policy::builder().max_attempts(2u32).classes([]).build();
+
+        assert!(policy.should_retry(1, &TaskError::OutOfMemory));
+        assert!(policy.should_retry(
+            1,
+            &TaskError::BackendUnavailable(String::from("unreachable"))
+        ));
+    }
+}
@@ -0,0 +1,135 @@
+//! Builders for a [`Policy`].
+
+use std::time::Duration;
+
+use crate::task::retry::Class;
+use crate::task::retry::DEFAULT_INITIAL_BACKOFF;
+use crate::task::retry::DEFAULT_JITTER;
+use crate::task::retry::DEFAULT_MAX_ATTEMPTS;
+use crate::task::retry::DEFAULT_MAX_BACKOFF;
+use crate::task::retry::DEFAULT_MULTIPLIER;
+use crate::task::retry::Policy;
+
+/// A builder for a [`Policy`].
+#[derive(Debug, Default)]
+pub struct Builder {
+    /// The maximum number of attempts (including the first).
+    max_attempts: Option<u32>,
+
+    /// The delay before the first retry.
+    initial_backoff: Option<Duration>,
+
+    /// The maximum delay between attempts.
+    max_backoff: Option<Duration>,
+
+    /// The factor the delay is multiplied by after each attempt.
+    multiplier: Option<f64>,
+
+    /// The fraction of each delay that is randomized.
+    jitter: Option<f64>,
+
+    /// The classes of failure that are retried.
+    classes: Option<Vec<Class>>,
+}
+
+impl Builder {
+    /// Sets the maximum number of attempts (including the first) within the
+    /// [`Builder`].
+    ///
+    /// Values less than one are treated as one (i.e., no retries).
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous maximum number of attempts
+    /// provided to the builder.
+    pub fn max_attempts(mut self, value: impl Into<u32>) -> Self {
+        self.max_attempts = Some(value.into());
+        self
+    }
+
+    /// Sets the delay before the first retry within the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous initial backoff provided to
+    /// the builder.
+    pub fn initial_backoff(mut self, value: Duration) -> Self {
+        self.initial_backoff = Some(value);
+        self
+    }
+
+    /// Sets the maximum delay between attempts within the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous maximum backoff provided to
+    /// the builder.
+    pub fn max_backoff(mut self, value: Duration) -> Self {
+        self.max_backoff = Some(value);
+        self
+    }
+
+    /// Sets the factor the delay is multiplied by after each attempt within
+    /// the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous multiplier provided to the
+    /// builder.
+    pub fn multiplier(mut self, value: impl Into<f64>) -> Self {
+        self.multiplier = Some(value.into());
+        self
+    }
+
+    /// Sets the fraction of each delay that is randomized within the
+    /// [`Builder`].
+    ///
+    /// The value is clamped between zero (no jitter) and one (a delay anywhere
+    /// between zero and the full backoff).
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous jitter provided to the
+    /// builder.
+    pub fn jitter(mut self, value: impl Into<f64>) -> Self {
+        self.jitter = Some(value.into());
+        self
+    }
+
+    /// Adds classes of failure to retry to the [`Builder`].
+    ///
+    /// If no classes are provided (or only an empty set of classes is
+    /// provided), all classes are retried.
+    ///
+    /// # Notes
+    ///
+    /// This will append to any previously provided classes.
+    pub fn classes(mut self, values: impl IntoIterator<Item = Class>) -> Self {
+        self.classes.get_or_insert_with(Vec::new).extend(values);
+        self
+    }
+
+    /// Consumes `self` and returns a built [`Policy`].
+    pub fn build(self) -> Policy {
+        Policy {
+            max_attempts: self.max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS).max(1),
+            initial_backoff: self.initial_backoff.unwrap_or(DEFAULT_INITIAL_BACKOFF),
+            max_backoff: self.max_backoff.unwrap_or(DEFAULT_MAX_BACKOFF),
+            multiplier: self.multiplier.unwrap_or(DEFAULT_MULTIPLIER).max(1.0),
+            jitter: self.jitter.unwrap_or(DEFAULT_JITTER).clamp(0.0, 1.0),
+            // NOTE: an empty set of classes retries everything rather than
+            // nothing, as documented on [`Self::classes()`].
+            classes: self
+                .classes
+                .filter(|classes| !classes.is_empty())
+                .unwrap_or_else(|| {
+                    vec![
+                        Class::Transient,
+                        Class::Preempted,
+                        Class::NonZeroExit,
+                        Class::OutOfMemory,
+                    ]
+                }),
+        }
+    }
+}
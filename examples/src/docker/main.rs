@@ -5,12 +5,15 @@
 //! `cargo run --release --example docker`
 
 use std::env::current_dir;
+use std::sync::Arc;
 
 use clap::Parser;
 use crankshaft::Engine;
 use crankshaft::config::backend::Kind;
 use crankshaft::config::backend::docker::Config;
+use crankshaft::engine::Shutdown;
 use crankshaft::engine::Task;
+use crankshaft::engine::progress::bars::Bars;
 use crankshaft::engine::task::Execution;
 use eyre::Context;
 use eyre::Result;
@@ -42,6 +45,7 @@ async fn run(args: Args) -> Result<()> {
         .context("building backend configuration")?;
 
     let engine = Engine::default()
+        .with_reporter(Arc::new(Bars::new()))
         .with(config)
         .await
         .context("initializing Docker backend")?;
@@ -65,9 +69,16 @@ async fn run(args: Args) -> Result<()> {
         .unwrap();
 
     let receivers = (0..args.n_jobs)
-        .map(|_| engine.submit("docker", task.clone()).callback)
-        .collect::<Vec<_>>();
+        .map(|_| {
+            engine
+                .submit("docker", task.clone())
+                .map(|handle| handle.callback)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .context("submitting tasks")?;
 
+    // NOTE: interrupting the example cleans up the tasks it submitted.
+    engine.shutdown_on_signal(Shutdown::Cancel);
     engine.run().await;
 
     for rx in receivers {
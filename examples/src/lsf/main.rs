@@ -4,10 +4,14 @@
 //!
 //! `cargo run --release --example lsf`
 
+use std::sync::Arc;
+
 use clap::Parser;
 use crankshaft::Config;
 use crankshaft::Engine;
+use crankshaft::engine::Shutdown;
 use crankshaft::engine::Task;
+use crankshaft::engine::progress::bars::Bars;
 use crankshaft::engine::task::Execution;
 use eyre::Context as _;
 use eyre::ContextCompat as _;
@@ -62,7 +66,10 @@ async fn run(args: Args) -> Result<()> {
         .find(|backend| backend.name() == "lsf")
         .context("locating configuration with name `lsf`")?;
 
-    let engine = Engine::default().with(config).await?;
+    let engine = Engine::default()
+        .with_reporter(Arc::new(Bars::new()))
+        .with(config)
+        .await?;
 
     let task = Task::builder()
         .name("my-example-task")
@@ -79,9 +86,16 @@ async fn run(args: Args) -> Result<()> {
         .unwrap();
 
     let receivers = (0..args.n_jobs)
-        .map(|_| engine.submit("lsf", task.clone()).callback)
-        .collect::<Vec<_>>();
+        .map(|_| {
+            engine
+                .submit("lsf", task.clone())
+                .map(|handle| handle.callback)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .context("submitting tasks")?;
 
+    // NOTE: interrupting the example cleans up the tasks it submitted.
+    engine.shutdown_on_signal(Shutdown::Cancel);
     engine.run().await;
 
     for rx in receivers {
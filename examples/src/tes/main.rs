@@ -8,12 +8,16 @@
 //! If needed, you can also set the `BASIC_AUTH_TOKEN` to add a basic auth
 //! header with that token.
 
+use std::sync::Arc;
+
 use clap::Parser;
 use crankshaft::Engine;
 use crankshaft::config::backend::Kind;
 use crankshaft::config::backend::tes::Config;
 use crankshaft::config::backend::tes::http;
+use crankshaft::engine::Shutdown;
 use crankshaft::engine::Task;
+use crankshaft::engine::progress::bars::Bars;
 use crankshaft::engine::task::Execution;
 use eyre::Context;
 use eyre::Result;
@@ -63,7 +67,10 @@ async fn run(args: Args) -> Result<()> {
         .try_build()
         .context("building backend configuration")?;
 
-    let engine = Engine::default().with(config).await?;
+    let engine = Engine::default()
+        .with_reporter(Arc::new(Bars::new()))
+        .with(config)
+        .await?;
 
     let task = Task::builder()
         .name("my-example-task")
@@ -80,12 +87,19 @@ async fn run(args: Args) -> Result<()> {
         .unwrap();
 
     let receivers = (0..args.n_jobs)
-        .map(|_| engine.submit("tes", task.clone()).callback)
-        .collect::<Vec<_>>();
+        .map(|_| {
+            engine
+                .submit("tes", task.clone())
+                .map(|handle| handle.callback)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .context("submitting tasks")?;
 
     #[cfg(tokio_unstable)]
     Engine::start_instrument(3000);
 
+    // NOTE: interrupting the example cleans up the tasks it submitted.
+    engine.shutdown_on_signal(Shutdown::Cancel);
     engine.run().await;
 
     for rx in receivers {